@@ -0,0 +1,42 @@
+//Counts the client's clipboard reads so Simba can tell whether a paste reached a text field.
+use retour::GenericDetour;
+use std::sync::OnceLock;
+
+use windows::{core::s, Win32::Foundation::HANDLE};
+
+use super::hooks::{hook_function, user32_function};
+use crate::shared::{memory::MEMORY_MANAGER, windows::CF_UNICODETEXT};
+
+type GetClipboardDataFn = unsafe extern "system" fn(u32) -> HANDLE;
+
+static ORIGINAL_GET_CLIPBOARD_DATA: OnceLock<GenericDetour<GetClipboardDataFn>> = OnceLock::new();
+
+unsafe extern "system" fn hooked_get_clipboard_data(format: u32) -> HANDLE {
+    if format == CF_UNICODETEXT {
+        if let Some(mem_manager) = MEMORY_MANAGER.get() {
+            mem_manager.lock().unwrap().clipboard_read();
+        }
+    }
+    ORIGINAL_GET_CLIPBOARD_DATA.get().unwrap().call(format)
+}
+
+pub unsafe fn hook_clipboard() {
+    let get_clipboard_data: GetClipboardDataFn =
+        std::mem::transmute(user32_function(s!("GetClipboardData")));
+
+    hook_function(
+        &ORIGINAL_GET_CLIPBOARD_DATA,
+        get_clipboard_data,
+        hooked_get_clipboard_data,
+        "GetClipboardData",
+    );
+}
+
+pub unsafe fn unhook_clipboard() {
+    let _ = ORIGINAL_GET_CLIPBOARD_DATA.get().map(|d| d.disable());
+    println!("[WaspInput]: GetClipboardData successfully unhooked.\r\n");
+}
+
+pub unsafe fn reenable_clipboard() {
+    let _ = ORIGINAL_GET_CLIPBOARD_DATA.get().map(|d| d.enable());
+}
@@ -0,0 +1,159 @@
+//Virtual cursor so the client sees the bot's mouse instead of the user's.
+use retour::GenericDetour;
+use std::{
+    ffi::c_void,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+    },
+};
+
+use windows::{
+    core::{s, BOOL},
+    Win32::{
+        Foundation::{HWND, POINT, RECT, TRUE},
+        Graphics::Gdi::{ClientToScreen, ScreenToClient},
+        UI::{Input::KeyboardAndMouse::IsWindowEnabled, WindowsAndMessaging::GetClientRect},
+    },
+};
+
+use super::hooks::{hook_function, user32_function};
+use crate::shared::memory::{CursorMode, UserInputPolicy, MEMORY_MANAGER};
+
+type CursorPosFn = unsafe extern "system" fn(*mut POINT) -> BOOL;
+type WindowFromPointFn = unsafe extern "system" fn(POINT) -> HWND;
+
+static ORIGINAL_GET_CURSOR_POS: OnceLock<GenericDetour<CursorPosFn>> = OnceLock::new();
+static ORIGINAL_GET_PHYSICAL_CURSOR_POS: OnceLock<GenericDetour<CursorPosFn>> = OnceLock::new();
+static ORIGINAL_WINDOW_FROM_POINT: OnceLock<GenericDetour<WindowFromPointFn>> = OnceLock::new();
+
+static CURSOR_WINDOW: AtomicU64 = AtomicU64::new(0);
+
+fn cursor_window() -> HWND {
+    HWND(CURSOR_WINDOW.load(Ordering::Acquire) as *mut c_void)
+}
+
+//The bot's cursor in client coordinates, None while the real one should be reported or it's unknown.
+pub fn virtual_cursor() -> Option<(i32, i32)> {
+    let hwnd = cursor_window();
+    let mem_manager = MEMORY_MANAGER.get()?.lock().unwrap();
+
+    let active = match unsafe { mem_manager.get_cursor_mode() } {
+        CursorMode::Auto => {
+            !unsafe { IsWindowEnabled(hwnd) }.as_bool()
+                || (unsafe { mem_manager.get_user_policy().0 } == UserInputPolicy::Block)
+        }
+        CursorMode::Virtual => true,
+        CursorMode::Real => false,
+    };
+    if !active {
+        return None;
+    }
+
+    let (x, y) = unsafe { mem_manager.get_mouse_position() };
+    if (x == -1) || (y == -1) {
+        return None;
+    }
+    Some((x, y))
+}
+
+//True while the bot's cursor is over the client, the user's cursor leaving doesn't count then.
+pub fn cursor_inside() -> bool {
+    let (x, y) = match virtual_cursor() {
+        Some(point) => point,
+        None => return false,
+    };
+
+    let mut rect = RECT::default();
+    if unsafe { GetClientRect(cursor_window(), &mut rect) }.is_err() {
+        return false;
+    }
+    (rect.left..rect.right).contains(&x) && (rect.top..rect.bottom).contains(&y)
+}
+
+unsafe fn screen_cursor(point: *mut POINT) -> bool {
+    let (x, y) = match virtual_cursor() {
+        Some(p) => p,
+        None => return false,
+    };
+
+    let mut screen = POINT { x, y };
+    if !ClientToScreen(cursor_window(), &mut screen).as_bool() {
+        return false;
+    }
+    *point = screen;
+    true
+}
+
+unsafe extern "system" fn hooked_get_cursor_pos(point: *mut POINT) -> BOOL {
+    if !point.is_null() && screen_cursor(point) {
+        return TRUE;
+    }
+    ORIGINAL_GET_CURSOR_POS.get().unwrap().call(point)
+}
+
+unsafe extern "system" fn hooked_get_physical_cursor_pos(point: *mut POINT) -> BOOL {
+    if !point.is_null() && screen_cursor(point) {
+        return TRUE;
+    }
+    ORIGINAL_GET_PHYSICAL_CURSOR_POS.get().unwrap().call(point)
+}
+
+//Points inside the client belong to it even when another window covers it.
+unsafe extern "system" fn hooked_window_from_point(point: POINT) -> HWND {
+    if virtual_cursor().is_some() {
+        let hwnd = cursor_window();
+        let mut client = point;
+        let mut rect = RECT::default();
+        if ScreenToClient(hwnd, &mut client).as_bool()
+            && GetClientRect(hwnd, &mut rect).is_ok()
+            && (rect.left..rect.right).contains(&client.x)
+            && (rect.top..rect.bottom).contains(&client.y)
+        {
+            return hwnd;
+        }
+    }
+    ORIGINAL_WINDOW_FROM_POINT.get().unwrap().call(point)
+}
+
+pub unsafe fn hook_cursor(hwnd: u64) {
+    CURSOR_WINDOW.store(hwnd, Ordering::Release);
+
+    let get_cursor_pos: CursorPosFn = std::mem::transmute(user32_function(s!("GetCursorPos")));
+    let get_physical_cursor_pos: CursorPosFn =
+        std::mem::transmute(user32_function(s!("GetPhysicalCursorPos")));
+    let window_from_point: WindowFromPointFn =
+        std::mem::transmute(user32_function(s!("WindowFromPoint")));
+
+    hook_function(
+        &ORIGINAL_GET_CURSOR_POS,
+        get_cursor_pos,
+        hooked_get_cursor_pos,
+        "GetCursorPos",
+    );
+    hook_function(
+        &ORIGINAL_GET_PHYSICAL_CURSOR_POS,
+        get_physical_cursor_pos,
+        hooked_get_physical_cursor_pos,
+        "GetPhysicalCursorPos",
+    );
+    hook_function(
+        &ORIGINAL_WINDOW_FROM_POINT,
+        window_from_point,
+        hooked_window_from_point,
+        "WindowFromPoint",
+    );
+}
+
+pub unsafe fn unhook_cursor() {
+    let _ = ORIGINAL_GET_CURSOR_POS.get().map(|d| d.disable());
+    let _ = ORIGINAL_GET_PHYSICAL_CURSOR_POS.get().map(|d| d.disable());
+    let _ = ORIGINAL_WINDOW_FROM_POINT.get().map(|d| d.disable());
+    println!("[WaspInput]: Cursor functions successfully unhooked.\r\n");
+}
+
+pub unsafe fn reenable_cursor() {
+    let _ = ORIGINAL_GET_CURSOR_POS.get().map(|d| d.enable());
+    let _ = ORIGINAL_GET_PHYSICAL_CURSOR_POS.get().map(|d| d.enable());
+    let _ = ORIGINAL_WINDOW_FROM_POINT.get().map(|d| d.enable());
+}
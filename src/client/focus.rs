@@ -0,0 +1,119 @@
+//Focus emulation so the client behaves as if focused while the user works in another window.
+use retour::GenericDetour;
+use std::{
+    ffi::c_void,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        OnceLock,
+    },
+};
+
+use windows::{
+    core::s,
+    Win32::{
+        Foundation::{HWND, LPARAM, WPARAM},
+        UI::WindowsAndMessaging::{
+            GetAncestor, SendMessageW, GA_ROOT, WA_ACTIVE, WM_ACTIVATE, WM_ACTIVATEAPP,
+            WM_NCACTIVATE, WM_SETFOCUS,
+        },
+    },
+};
+
+use super::hooks::{hook_function, user32_function};
+
+type WindowFn = unsafe extern "system" fn() -> HWND;
+
+static ORIGINAL_GET_FOCUS: OnceLock<GenericDetour<WindowFn>> = OnceLock::new();
+static ORIGINAL_GET_FOREGROUND_WINDOW: OnceLock<GenericDetour<WindowFn>> = OnceLock::new();
+static ORIGINAL_GET_ACTIVE_WINDOW: OnceLock<GenericDetour<WindowFn>> = OnceLock::new();
+
+static FAKE_FOCUS: AtomicBool = AtomicBool::new(false);
+static FOCUS_WINDOW: AtomicU64 = AtomicU64::new(0);
+
+//The window to report as focused, None while the real focus should be reported.
+fn focus_window() -> Option<HWND> {
+    if !FAKE_FOCUS.load(Ordering::Acquire) {
+        return None;
+    }
+    Some(HWND(FOCUS_WINDOW.load(Ordering::Acquire) as *mut c_void))
+}
+
+unsafe extern "system" fn hooked_get_focus() -> HWND {
+    match focus_window() {
+        Some(hwnd) => hwnd,
+        None => ORIGINAL_GET_FOCUS.get().unwrap().call(),
+    }
+}
+
+unsafe extern "system" fn hooked_get_foreground_window() -> HWND {
+    match focus_window() {
+        Some(hwnd) => GetAncestor(hwnd, GA_ROOT),
+        None => ORIGINAL_GET_FOREGROUND_WINDOW.get().unwrap().call(),
+    }
+}
+
+unsafe extern "system" fn hooked_get_active_window() -> HWND {
+    match focus_window() {
+        Some(hwnd) => GetAncestor(hwnd, GA_ROOT),
+        None => ORIGINAL_GET_ACTIVE_WINDOW.get().unwrap().call(),
+    }
+}
+
+//The messages Windows sends when the user clicks into the client.
+unsafe fn activate(hwnd: HWND) {
+    let root = GetAncestor(hwnd, GA_ROOT);
+    let _ = SendMessageW(root, WM_ACTIVATEAPP, Some(WPARAM(1)), Some(LPARAM(0)));
+    let _ = SendMessageW(root, WM_NCACTIVATE, Some(WPARAM(1)), Some(LPARAM(0)));
+    let _ = SendMessageW(
+        root,
+        WM_ACTIVATE,
+        Some(WPARAM(WA_ACTIVE as usize)),
+        Some(LPARAM(0)),
+    );
+    let _ = SendMessageW(hwnd, WM_SETFOCUS, Some(WPARAM(0)), Some(LPARAM(0)));
+}
+
+//Called from the WndProc on WI_FOCUS.
+pub unsafe fn set_fake_focus(hwnd: HWND, enabled: bool) {
+    FOCUS_WINDOW.store(hwnd.0 as u64, Ordering::Release);
+    let was_enabled = FAKE_FOCUS.swap(enabled, Ordering::AcqRel);
+
+    if enabled && !was_enabled {
+        activate(hwnd);
+    }
+}
+
+pub unsafe fn hook_focus() {
+    let get_focus: WindowFn = std::mem::transmute(user32_function(s!("GetFocus")));
+    let get_foreground_window: WindowFn =
+        std::mem::transmute(user32_function(s!("GetForegroundWindow")));
+    let get_active_window: WindowFn = std::mem::transmute(user32_function(s!("GetActiveWindow")));
+
+    hook_function(&ORIGINAL_GET_FOCUS, get_focus, hooked_get_focus, "GetFocus");
+    hook_function(
+        &ORIGINAL_GET_FOREGROUND_WINDOW,
+        get_foreground_window,
+        hooked_get_foreground_window,
+        "GetForegroundWindow",
+    );
+    hook_function(
+        &ORIGINAL_GET_ACTIVE_WINDOW,
+        get_active_window,
+        hooked_get_active_window,
+        "GetActiveWindow",
+    );
+}
+
+pub unsafe fn unhook_focus() {
+    FAKE_FOCUS.store(false, Ordering::Release);
+    let _ = ORIGINAL_GET_FOCUS.get().map(|d| d.disable());
+    let _ = ORIGINAL_GET_FOREGROUND_WINDOW.get().map(|d| d.disable());
+    let _ = ORIGINAL_GET_ACTIVE_WINDOW.get().map(|d| d.disable());
+    println!("[WaspInput]: Focus functions successfully unhooked.\r\n");
+}
+
+pub unsafe fn reenable_focus() {
+    let _ = ORIGINAL_GET_FOCUS.get().map(|d| d.enable());
+    let _ = ORIGINAL_GET_FOREGROUND_WINDOW.get().map(|d| d.enable());
+    let _ = ORIGINAL_GET_ACTIVE_WINDOW.get().map(|d| d.enable());
+}
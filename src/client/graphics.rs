@@ -7,19 +7,22 @@ use std::{
 
 use gl::{
     types::{GLboolean, GLchar, GLenum, GLfloat, GLint, GLsizei, GLsizeiptr, GLuint, GLvoid},
-    BGRA, CLAMP_TO_EDGE, FRAGMENT_SHADER, LINEAR, PIXEL_PACK_BUFFER, POINTS, READ_ONLY, RGBA8,
-    STREAM_READ, TEXTURE0, TEXTURE_2D, TEXTURE_MAG_FILTER, TEXTURE_MIN_FILTER, TEXTURE_WRAP_S,
-    TEXTURE_WRAP_T, TRIANGLE_STRIP, UNSIGNED_BYTE, VERTEX_SHADER,
+    BGRA, CLAMP_TO_EDGE, FRAGMENT_SHADER, LINEAR, PACK_ALIGNMENT, PIXEL_PACK_BUFFER, POINTS,
+    READ_ONLY, RGB, RGBA, RGBA8, STREAM_READ, TEXTURE0, TEXTURE_2D, TEXTURE_MAG_FILTER,
+    TEXTURE_MIN_FILTER, TEXTURE_WRAP_S, TEXTURE_WRAP_T, TRIANGLE_STRIP, UNSIGNED_BYTE,
+    VERTEX_SHADER,
 };
 
 use windows::{
     core::PCSTR,
     Win32::Graphics::OpenGL::{
-        glBindTexture, glDrawArrays, glGenTextures, glPointSize, glReadPixels, glTexParameteri,
-        glTexSubImage2D, glViewport, wglGetProcAddress,
+        glBindTexture, glDrawArrays, glGenTextures, glGetIntegerv, glPixelStorei, glPointSize,
+        glReadPixels, glTexParameteri, glTexSubImage2D, glViewport, wglGetProcAddress,
     },
 };
 
+use crate::shared::image::PixelFormat;
+
 type GlGenBuffersFn = unsafe extern "system" fn(n: GLsizei, buffers: *mut GLuint);
 type GlDeleteBuffersFn = unsafe extern "system" fn(n: GLsizei, buffers: *const GLuint);
 type GlBindBufferFn = unsafe extern "system" fn(target: GLenum, buffer: GLuint);
@@ -164,13 +167,31 @@ pub unsafe fn load_opengl_extensions() -> bool {
         )
 }
 
+//(PBOs, size, index, format each PBO was read with)
+type PboData = (Vec<u32>, i32, usize, [Option<PixelFormat>; 2]);
+
 lazy_static! {
-    static ref PBO_DATA: Mutex<(Vec<u32>, i32, usize)> = Mutex::new((vec![0, 0], 0, 0)); //(PBOs, size, index)
+    static ref PBO_DATA: Mutex<PboData> = Mutex::new((vec![0, 0], 0, 0, [None, None]));
+}
+
+//Only formats OpenGL can pack are read here, Simba converts to the others.
+fn gl_read_format(format: PixelFormat) -> GLenum {
+    match format {
+        PixelFormat::Rgba => RGBA,
+        PixelFormat::Rgb => RGB,
+        _ => BGRA,
+    }
 }
 
-pub fn read_frame(width: i32, height: i32, size: i32, dest: *mut u8) {
+//Returns the format and row stride of what was written to dest, gray and HSV are written as BGRA.
+pub fn read_frame(
+    width: i32,
+    height: i32,
+    format: PixelFormat,
+    dest: *mut u8,
+) -> Option<(PixelFormat, i32)> {
     if dest.is_null() {
-        return;
+        return None;
     }
 
     let gl_bind_buffer = *GL_BIND_BUFFER.get().unwrap();
@@ -178,9 +199,11 @@ pub fn read_frame(width: i32, height: i32, size: i32, dest: *mut u8) {
     let gl_unmap_buffer = *GL_UNMAP_BUFFER.get().unwrap();
 
     let mut pbo_data = PBO_DATA.lock().unwrap();
-    let (ref mut pbos, ref mut old_size, ref mut index) = *pbo_data;
+    let (ref mut pbos, ref mut old_size, ref mut index, ref mut formats) = *pbo_data;
 
-    let row_stride = (width * 4) as usize;
+    let format = format.capture_format();
+    let gl_format = gl_read_format(format);
+    let size = width * height * format.bytes_per_pixel() as i32;
 
     if pbos[0] == 0 {
         let gl_gen_buffers = *GL_GEN_BUFFERS.get().unwrap();
@@ -205,32 +228,48 @@ pub fn read_frame(width: i32, height: i32, size: i32, dest: *mut u8) {
         }
 
         *old_size = size;
+        *formats = [None, None]; // contents are undefined after reallocating
     }
 
     let read_index = *index;
     let map_index = (read_index + 1) % 2;
+    let mut written = None;
 
     unsafe {
-        // Read pixels into the read_index PBO
+        // Read pixels into the read_index PBO, RGB rows are tightly packed
+        let mut prev_alignment = 4;
+        glGetIntegerv(PACK_ALIGNMENT, &mut prev_alignment);
+        glPixelStorei(PACK_ALIGNMENT, 1);
+
         gl_bind_buffer(PIXEL_PACK_BUFFER, pbos[read_index]);
-        glReadPixels(0, 0, width, height, BGRA, UNSIGNED_BYTE, null_mut());
+        glReadPixels(0, 0, width, height, gl_format, UNSIGNED_BYTE, null_mut());
+        glPixelStorei(PACK_ALIGNMENT, prev_alignment);
 
         // Map the previous frame's PBO to read its contents
         gl_bind_buffer(PIXEL_PACK_BUFFER, pbos[map_index]);
         let ptr = gl_map_buffer(PIXEL_PACK_BUFFER, READ_ONLY) as *const u8;
 
         if !ptr.is_null() {
-            for row in 0..height as usize {
-                let src_row = ptr.add(row * row_stride);
-                let dest_row = dest.add((height as usize - 1 - row) * row_stride);
-                copy_nonoverlapping(src_row, dest_row, row_stride);
+            if let Some(mapped_format) = formats[map_index] {
+                let dest_stride = width as usize * mapped_format.bytes_per_pixel();
+
+                for row in 0..height as usize {
+                    let src_row = ptr.add(row * dest_stride);
+                    let dest_row = dest.add((height as usize - 1 - row) * dest_stride);
+                    copy_nonoverlapping(src_row, dest_row, dest_stride);
+                }
+
+                written = Some((mapped_format, dest_stride as i32));
             }
 
             gl_unmap_buffer(PIXEL_PACK_BUFFER); // Optional but recommended
         }
 
+        formats[read_index] = Some(format);
         *index = map_index; // Swap indices
     }
+
+    written
 }
 
 fn compile_shader(source: &str, shader_type: GLenum) -> GLuint {
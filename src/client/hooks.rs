@@ -4,13 +4,18 @@ use retour::GenericDetour;
 use std::{
     ffi::c_void,
     ptr::null_mut,
-    sync::{Mutex, OnceLock},
+    slice::from_raw_parts,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    thread::spawn,
 };
 
 use std::char::from_u32;
 
 use windows::{
-    core::{BOOL, PCSTR},
+    core::{s, BOOL, PCSTR},
     Win32::{
         Foundation::{GetLastError, HWND, LPARAM, LRESULT, WPARAM},
         Graphics::{
@@ -23,29 +28,52 @@ use windows::{
         },
         UI::{
             Input::KeyboardAndMouse::{
-                GetKeyboardState, MapVirtualKeyA, MapVirtualKeyW, ToUnicode, MAPVK_VK_TO_VSC,
+                GetKeyboardState, MapVirtualKeyW, ToUnicode, MAPVK_VK_TO_VSC,
             },
+            Input::{GetCurrentInputMessageSource, IMO_HARDWARE, INPUT_MESSAGE_SOURCE},
             WindowsAndMessaging::{
-                GetWindowLongPtrW, IsWindowVisible, ShowWindow, GWLP_WNDPROC, SW_HIDE,
-                SW_SHOWNORMAL, WM_CHAR, WM_IME_NOTIFY, WM_IME_SETCONTEXT, WM_KEYDOWN, WM_KEYUP,
-                WM_KILLFOCUS, WM_MOUSEMOVE,
+                GetWindowLongPtrW, IsWindowVisible, KillTimer, SetTimer, ShowWindow, GWLP_WNDPROC,
+                SW_HIDE, SW_SHOWNORMAL, WM_CHAR, WM_IME_NOTIFY, WM_IME_SETCONTEXT, WM_INPUT,
+                WM_KEYDOWN, WM_KEYFIRST, WM_KEYLAST, WM_KILLFOCUS, WM_MOUSEFIRST, WM_MOUSEHWHEEL,
+                WM_MOUSELAST, WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_SETCURSOR, WM_TIMER,
             },
         },
     },
 };
 
-use super::graphics::{
-    draw_overlay, draw_point, load_opengl_extensions, read_frame, restore_state,
+use super::{
+    clipboard::{hook_clipboard, reenable_clipboard, unhook_clipboard},
+    cursor::{cursor_inside, hook_cursor, reenable_cursor, unhook_cursor},
+    focus::{hook_focus, reenable_focus, set_fake_focus, unhook_focus},
+    graphics::{draw_overlay, draw_point, load_opengl_extensions, read_frame, restore_state},
+    hover::{
+        hook_hover, hover_elapsed, mouse_moved, reenable_hover, set_cursor_lparam, unhook_hover,
+        HOVER_TIMER,
+    },
+    inputlog::{log_message, start_input_log, stop_input_log},
+    keystate::{hook_key_state, reenable_key_state, unhook_key_state},
+    rawinput::{hook_raw_input, push_record, reenable_raw_input, unhook_raw_input},
+    sequence::replay_sequence,
 };
 use crate::shared::{
-    memory::{MemoryManager, MEMORY_MANAGER},
+    botinput::{InputClassifier, InputSource},
+    image::{hash_tiles, PixelFormat, TILE_COUNT},
+    layout::{fixed_key, Layout, MOD_ALT, MOD_CTRL, MOD_SHIFT},
+    memory::{MemoryManager, UserInputPolicy, Visibility, MEMORY_MANAGER},
+    message::{key_message, unpack_point, KeyFlags, WM_MOUSEHOVER, WM_MOUSELEAVE},
+    rawinput::{decode_key, decode_mouse, key_record, mouse_record, RIM_INPUT},
     sync::event_listener,
-    windows::{WI_CONSOLE, WI_DETACH, WI_MODIFIERS},
+    windows::{
+        set_virtual_key, window_visibility, WI_CAPTURE_FORMAT, WI_CONSOLE, WI_DETACH, WI_FOCUS,
+        WI_INPUT_LOG, WI_MODIFIERS, WI_RAW_KEY, WI_RAW_MOUSE, WI_RELEASE, WI_SEQUENCE,
+    },
 };
 
 lazy_static! {
     static ref KEYBOARD_MODIFIERS: Mutex<(bool, bool, bool)> = Mutex::new((false, false, false));
     static ref LAST_CHAR: Mutex<i32> = Mutex::new(0);
+    static ref CAPTURE_FORMAT: Mutex<PixelFormat> = Mutex::new(PixelFormat::Bgra);
+    static ref INPUT_CLASSIFIER: Mutex<InputClassifier> = Mutex::new(InputClassifier::default());
 }
 
 pub unsafe fn open_client_console() {
@@ -63,19 +91,63 @@ pub unsafe fn open_client_console() {
     }
 }
 
+//Visibility is published from a timer so it stays current while the client doesn't render.
+//SetTimer only works on the window's own thread, the hooked window waits here for its first message.
+const VISIBILITY_TIMER: usize = 0x5742;
+const VISIBILITY_INTERVAL: u32 = 250;
+static VISIBILITY_WINDOW: AtomicU64 = AtomicU64::new(0);
+
+unsafe fn publish_visibility(hwnd: HWND) {
+    let visibility = window_visibility(hwnd.0 as u64);
+    if let Some(mem_manager) = MEMORY_MANAGER.get() {
+        mem_manager.lock().unwrap().set_visibility(visibility);
+    }
+}
+
 //WndProc hook
 static ORIGINAL_WNDPROC: OnceLock<
     GenericDetour<unsafe extern "system" fn(HWND, u32, WPARAM, LPARAM) -> LRESULT>,
 > = OnceLock::new();
 
-fn get_modifier_lparam(key: i32, down: bool) -> LPARAM {
-    let scancode = unsafe { MapVirtualKeyA(key as u32, MAPVK_VK_TO_VSC) };
+//Sends a shift, ctrl or alt keystroke straight to the client's WndProc.
+unsafe fn call_modifier(hwnd: HWND, vkey: u8, down: bool, ctrl: bool, alt: bool) {
+    let key = match fixed_key(vkey) {
+        Some(key) => key,
+        None => return,
+    };
 
-    let mut lparam = 1 | (scancode << 16) | (0 << 24);
-    if !down {
-        lparam |= (1 << 30) | (1 << 31);
+    let flags = KeyFlags {
+        alt,
+        ctrl,
+        ..Default::default()
+    };
+    let (msg, wparam, lparam) = key_message(&key, down, flags);
+    set_virtual_key(vkey, down);
+    log_message(hwnd, msg, wparam, lparam, false);
+
+    let original = ORIGINAL_WNDPROC.get().unwrap();
+    let _ = original.call(hwnd, msg, WPARAM(wparam), LPARAM(lparam));
+}
+
+//Releases whatever WI_MODIFIERS left toggled on.
+unsafe fn release_toggled_modifiers(hwnd: HWND) {
+    let mut modifiers = KEYBOARD_MODIFIERS.lock().unwrap();
+    let (shift, ctrl, alt) = &mut *modifiers;
+
+    if *shift {
+        *shift = false;
+        call_modifier(hwnd, 0x10, false, *ctrl, *alt);
+    }
+
+    if *ctrl {
+        *ctrl = false;
+        call_modifier(hwnd, 0x11, false, false, *alt);
+    }
+
+    if *alt {
+        *alt = false;
+        call_modifier(hwnd, 0x12, false, false, false);
     }
-    LPARAM(lparam as isize)
 }
 
 fn decode_modifiers(wparam: WPARAM) -> (bool, bool, bool) {
@@ -118,18 +190,47 @@ unsafe fn get_updated_wparam(vkey: u32, shift: bool, ctrl: bool, alt: bool) -> W
 fn rebuild_key(key: u8, shift: bool, ctrl: bool, alt: bool) -> u16 {
     let mut modifiers: u8 = 0;
     if shift {
-        modifiers |= 0x01;
+        modifiers |= MOD_SHIFT;
     }
     if ctrl {
-        modifiers |= 0x02;
+        modifiers |= MOD_CTRL;
     }
     if alt {
-        modifiers |= 0x04;
+        modifiers |= MOD_ALT;
     }
 
     ((modifiers as u16) << 8) | (key as u16)
 }
 
+//Mouse and keyboard messages that came from the real devices, not from the bot or other programs.
+fn is_user_input(msg: u32, wparam: WPARAM, lparam: LPARAM) -> bool {
+    let input =
+        (WM_KEYFIRST..=WM_KEYLAST).contains(&msg) || (WM_MOUSEFIRST..=WM_MOUSELAST).contains(&msg);
+    if !input {
+        return false;
+    }
+
+    //the source only says where the message being dispatched came from, the bot's own are told apart by their tag
+    let mut source = INPUT_MESSAGE_SOURCE::default();
+    let hardware = unsafe { GetCurrentInputMessageSource(&mut source) }.is_ok()
+        && (source.originId == IMO_HARDWARE);
+
+    let mem_manager = MEMORY_MANAGER
+        .get()
+        .expect("[WaspInput]: Memory manager is not initialized!\r\n")
+        .lock()
+        .unwrap();
+    let tags = unsafe { mem_manager.bot_tags() };
+    let source = INPUT_CLASSIFIER.lock().unwrap().classify(
+        &tags,
+        msg,
+        wparam.0 as u32,
+        lparam.0 as u32,
+        hardware,
+    );
+    source == InputSource::User
+}
+
 unsafe extern "system" fn hooked_wndproc(
     hwnd: HWND,
     msg: u32,
@@ -138,6 +239,41 @@ unsafe extern "system" fn hooked_wndproc(
 ) -> LRESULT {
     let original = ORIGINAL_WNDPROC.get().unwrap();
 
+    let window = hwnd.0 as u64;
+    if VISIBILITY_WINDOW
+        .compare_exchange(window, 0, Ordering::AcqRel, Ordering::Acquire)
+        .is_ok()
+    {
+        SetTimer(Some(hwnd), VISIBILITY_TIMER, VISIBILITY_INTERVAL, None);
+        publish_visibility(hwnd);
+    }
+
+    let user = is_user_input(msg, wparam, lparam);
+    if user {
+        let mem_manager = MEMORY_MANAGER
+            .get()
+            .expect("[WaspInput]: Memory manager is not initialized!\r\n")
+            .lock()
+            .unwrap();
+        if mem_manager.get_user_policy().0 == UserInputPolicy::Block {
+            return LRESULT(0);
+        }
+        mem_manager.user_input();
+    }
+    log_message(hwnd, msg, wparam.0, lparam.0, user);
+
+    //Windows asks for the cursor shape before every mouse message the real cursor makes
+    if !user
+        && (WM_MOUSEFIRST..=WM_MOUSELAST).contains(&msg)
+        && (msg != WM_MOUSEWHEEL)
+        && (msg != WM_MOUSEHWHEEL)
+    {
+        let (x, y) = unpack_point(lparam.0);
+        if let Some(hit) = set_cursor_lparam(hwnd, msg, x, y) {
+            original.call(hwnd, WM_SETCURSOR, WPARAM(hwnd.0 as usize), hit);
+        }
+    }
+
     match msg {
         WI_CONSOLE => {
             open_client_console();
@@ -148,37 +284,70 @@ unsafe extern "system" fn hooked_wndproc(
             let (shift, ctrl, alt) = &mut *modifiers;
             let (nshift, nctrl, nalt) = decode_modifiers(wparam);
 
-            if nshift {
-                let msg = if *shift { WM_KEYUP } else { WM_KEYDOWN };
-                let lparam = get_modifier_lparam(0x10, !*shift);
-                let _ = original.call(hwnd, msg, WPARAM(0x10), lparam);
-            }
-
-            if nctrl {
-                let msg = if *ctrl { WM_KEYUP } else { WM_KEYDOWN };
-                let lparam = get_modifier_lparam(0x11, !*ctrl);
-                let _ = original.call(hwnd, msg, WPARAM(0x11), lparam);
-            }
-
-            if nalt {
-                let msg = if *alt { WM_KEYUP } else { WM_KEYDOWN };
-                let lparam = get_modifier_lparam(0x12, !*alt);
-                let _ = original.call(hwnd, msg, WPARAM(0x12), lparam);
-            }
-
             if nshift {
                 *shift = !*shift;
+                call_modifier(hwnd, 0x10, *shift, *ctrl, *alt);
             }
+
             if nctrl {
                 *ctrl = !*ctrl;
+                call_modifier(hwnd, 0x11, *ctrl, *ctrl, *alt);
             }
+
             if nalt {
                 *alt = !*alt;
+                call_modifier(hwnd, 0x12, *alt, *ctrl, *alt);
             }
 
             return LRESULT(0);
         }
+        WI_CAPTURE_FORMAT => {
+            match PixelFormat::from_i32(wparam.0 as i32) {
+                Some(format) => *CAPTURE_FORMAT.lock().unwrap() = format,
+                None => println!("[WaspInput]: Unknown capture format: {}\r\n", wparam.0),
+            };
+            return LRESULT(0);
+        }
+        WI_SEQUENCE => {
+            let (hwnd, id) = (hwnd.0 as u64, wparam.0 as u32);
+            let layout = Layout::from_i32(lparam.0 as i32).unwrap_or(Layout::Os);
+            spawn(move || replay_sequence(hwnd, id, layout));
+            return LRESULT(0);
+        }
+        WI_INPUT_LOG => {
+            if wparam.0 != 0 {
+                start_input_log();
+            } else {
+                stop_input_log();
+            }
+            return LRESULT(0);
+        }
+        WI_FOCUS => {
+            set_fake_focus(hwnd, wparam.0 != 0);
+            return LRESULT(0);
+        }
+        WI_RAW_MOUSE => {
+            let handle = push_record(mouse_record(&decode_mouse(wparam.0, lparam.0)));
+            return original.call(hwnd, WM_INPUT, WPARAM(RIM_INPUT), LPARAM(handle));
+        }
+        WI_RAW_KEY => {
+            let handle = push_record(key_record(&decode_key(wparam.0, lparam.0)));
+            return original.call(hwnd, WM_INPUT, WPARAM(RIM_INPUT), LPARAM(handle));
+        }
+        WI_RELEASE => {
+            release_toggled_modifiers(hwnd);
+            return LRESULT(0);
+        }
         WI_DETACH => {
+            let _ = KillTimer(Some(hwnd), VISIBILITY_TIMER);
+            release_toggled_modifiers(hwnd);
+            unhook_key_state();
+            unhook_focus();
+            unhook_cursor();
+            unhook_raw_input();
+            unhook_hover();
+            unhook_clipboard();
+            stop_input_log();
             unhook_wgl_swap_buffers();
             unhook_wndproc();
 
@@ -217,12 +386,22 @@ unsafe extern "system" fn hooked_wndproc(
 
             WM_CHAR
         }
+        WM_TIMER if wparam.0 == VISIBILITY_TIMER => {
+            publish_visibility(hwnd);
+            return LRESULT(0);
+        }
         WM_KILLFOCUS => return LRESULT(0),
+        WM_MOUSELEAVE if cursor_inside() => return LRESULT(0),
+        WM_TIMER if wparam.0 == HOVER_TIMER => {
+            if let Some((wparam, lparam)) = hover_elapsed(hwnd) {
+                original.call(hwnd, WM_MOUSEHOVER, wparam, lparam);
+            }
+            return LRESULT(0);
+        }
         WM_IME_SETCONTEXT => return LRESULT(0),
         WM_IME_NOTIFY => return LRESULT(0),
         WM_MOUSEMOVE => {
-            let x = (lparam.0 & 0xFFFF) as u16 as i32;
-            let y = ((lparam.0 >> 16) & 0xFFFF) as u16 as i32;
+            let (x, y) = unpack_point(lparam.0);
 
             let mem_manager = MEMORY_MANAGER
                 .get()
@@ -230,7 +409,11 @@ unsafe extern "system" fn hooked_wndproc(
                 .lock()
                 .unwrap();
             mem_manager.set_mouse_position(x, y);
+            drop(mem_manager);
 
+            if !user {
+                mouse_moved(hwnd, x, y, wparam.0);
+            }
             WM_MOUSEMOVE
         }
         _ => msg,
@@ -262,6 +445,7 @@ unsafe fn hook_wndproc(hwnd: u64) {
             .expect("[WaspInput]: Failed to save original WndProc function.\r\n");
     }
 
+    VISIBILITY_WINDOW.store(hwnd, Ordering::Release);
     println!("[WaspInput]: WndProc successfully hooked.\r\n");
 }
 
@@ -277,6 +461,38 @@ pub unsafe fn unhook_wndproc() {
     println!("[WaspInput]: WndProc successfully unhooked.\r\n");
 }
 
+//Address of a user32 export, transmuted by the caller to the real signature.
+pub unsafe fn user32_function(name: PCSTR) -> unsafe extern "system" fn() -> isize {
+    let module =
+        GetModuleHandleA(s!("user32.dll")).expect("[WaspInput]: user32.dll module not found.\r\n");
+
+    GetProcAddress(module, name).expect("[WaspInput]: user32 function not found.\r\n")
+}
+
+//Detours original to hooked and keeps the detour in detours so the original can still be called.
+pub unsafe fn hook_function<T: retour::Function>(
+    detours: &OnceLock<GenericDetour<T>>,
+    original: T,
+    hooked: T,
+    name: &str,
+) {
+    let detour = GenericDetour::new(original, hooked)
+        .unwrap_or_else(|_| panic!("[WaspInput]: Failed to create {} hook.\r\n", name));
+
+    detour
+        .enable()
+        .unwrap_or_else(|_| panic!("[WaspInput]: Failed to enable {} hook.\r\n", name));
+
+    if detours.set(detour).is_err() {
+        panic!(
+            "[WaspInput]: Failed to save original {} function.\r\n",
+            name
+        );
+    }
+
+    println!("[WaspInput]: {} successfully hooked.\r\n", name);
+}
+
 //OpenGL Hook
 static ORIGINAL_WGL_SWAPBUFFERS: OnceLock<GenericDetour<unsafe extern "system" fn(HDC) -> BOOL>> =
     OnceLock::new();
@@ -299,13 +515,31 @@ unsafe extern "system" fn hooked_wgl_swap_buffers(hdc: HDC) -> BOOL {
 
     let width = viewport[2];
     let height = viewport[3];
-    let frame_size = width * height * 4;
-
-    mem_manager.set_dimensions(width, height);
-
-    if load_opengl_extensions() {
+    let format = *CAPTURE_FORMAT.lock().unwrap();
+
+    //Occluded clients are still composed by DWM and keep rendering
+    let renderable = (width > 0)
+        && (height > 0)
+        && !matches!(
+            mem_manager.get_visibility(),
+            Visibility::Minimized | Visibility::Hidden
+        );
+
+    //Keep the last good frame and dimensions while the client can't render
+    if renderable && load_opengl_extensions() {
+        mem_manager.begin_frame();
+        mem_manager.set_dimensions(width, height);
         let dest = mem_manager.image_ptr();
-        read_frame(width, height, frame_size, dest);
+        if let Some((format, stride)) = read_frame(width, height, format, dest) {
+            mem_manager.set_format(format, stride);
+
+            let data = from_raw_parts(dest, (stride * height) as usize);
+            let mut tiles = [0u32; TILE_COUNT];
+            let bpp = format.bytes_per_pixel();
+            let hash = hash_tiles(data, width, height, stride as usize, bpp, &mut tiles);
+            mem_manager.set_frame_hashes(hash, &tiles);
+        }
+        mem_manager.end_frame();
 
         let overlay = mem_manager.overlay_ptr();
         draw_overlay(width, height, overlay);
@@ -380,12 +614,25 @@ pub unsafe fn reenable_hooks() {
     wndproc_detour
         .enable()
         .expect("[WaspInput]: Failed to enable WndProc hook.\r\n");
+
+    reenable_key_state();
+    reenable_focus();
+    reenable_cursor();
+    reenable_raw_input();
+    reenable_hover();
+    reenable_clipboard();
 }
 pub unsafe extern "system" fn start(lparam: *mut c_void) -> u32 {
     let _ = MEMORY_MANAGER.set(Mutex::new(MemoryManager::create_map()));
 
     hook_wndproc(lparam as u64);
     hook_wgl_swap_buffers();
+    hook_key_state();
+    hook_focus();
+    hook_cursor(lparam as u64);
+    hook_raw_input();
+    hook_hover(lparam as u64);
+    hook_clipboard();
 
     event_listener(lparam as u64);
     0
@@ -0,0 +1,206 @@
+//Hover and leave tracking for the bot's cursor, Windows only tracks the real one.
+use retour::GenericDetour;
+use std::{
+    ffi::c_void,
+    sync::{Mutex, OnceLock},
+};
+
+use windows::{
+    core::{s, BOOL},
+    Win32::{
+        Foundation::{HWND, LPARAM, RECT, WPARAM},
+        UI::{
+            Input::KeyboardAndMouse::{
+                TME_CANCEL, TME_HOVER, TME_LEAVE, TME_NONCLIENT, TME_QUERY, TRACKMOUSEEVENT,
+                TRACKMOUSEEVENT_FLAGS,
+            },
+            WindowsAndMessaging::{
+                GetClientRect, KillTimer, PostMessageW, SetTimer, SystemParametersInfoW, HTCLIENT,
+                SPI_GETMOUSEHOVERTIME, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS,
+            },
+        },
+    },
+};
+
+use super::hooks::{hook_function, user32_function};
+use crate::shared::{
+    memory::{InputOwner, MEMORY_MANAGER},
+    message::{pack_point, WM_MOUSELEAVE},
+};
+
+type TrackMouseEventFn = unsafe extern "system" fn(*mut TRACKMOUSEEVENT) -> BOOL;
+
+static ORIGINAL_TRACK_MOUSE_EVENT: OnceLock<GenericDetour<TrackMouseEventFn>> = OnceLock::new();
+
+const HOVER_DEFAULT: u32 = 0xFFFFFFFF;
+//WM_TIMER id of the bot's hover timer.
+pub const HOVER_TIMER: usize = 0x5741;
+
+struct Tracking {
+    hwnd: usize,
+    leave: bool, //the client asked for WM_MOUSELEAVE
+    hover: bool, //the client asked for WM_MOUSEHOVER
+    hover_time: u32,
+    inside: bool,
+    position: (i32, i32),
+    keys: usize,
+}
+
+static TRACKING: Mutex<Tracking> = Mutex::new(Tracking {
+    hwnd: 0,
+    leave: false,
+    hover: false,
+    hover_time: 400,
+    inside: false,
+    position: (0, 0),
+    keys: 0,
+});
+
+fn system_hover_time() -> u32 {
+    let mut time = 400u32;
+    let _ = unsafe {
+        SystemParametersInfoW(
+            SPI_GETMOUSEHOVERTIME,
+            0,
+            Some(&mut time as *mut u32 as *mut c_void),
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+        )
+    };
+    time
+}
+
+//The bot's cursor is tracked here instead of by Windows while the bot owns input.
+//Windows tracks the real cursor, which is usually outside the client, and would post false leaves next to the bot's.
+fn bot_drives() -> bool {
+    match MEMORY_MANAGER.get() {
+        Some(mem_manager) => unsafe {
+            mem_manager.lock().unwrap().get_input_owner() == InputOwner::Bot
+        },
+        None => false,
+    }
+}
+
+unsafe extern "system" fn hooked_track_mouse_event(event: *mut TRACKMOUSEEVENT) -> BOOL {
+    let original = ORIGINAL_TRACK_MOUSE_EVENT.get().unwrap();
+    if event.is_null() {
+        return original.call(event);
+    }
+
+    let event = &mut *event;
+    let flags = event.dwFlags;
+    let bot = bot_drives();
+    let mut tracking = TRACKING.lock().unwrap();
+
+    if flags.contains(TME_QUERY) {
+        if !tracking.leave && !tracking.hover {
+            return original.call(event);
+        }
+
+        let mut flags = TRACKMOUSEEVENT_FLAGS(0);
+        if tracking.leave {
+            flags |= TME_LEAVE;
+        }
+        if tracking.hover {
+            flags |= TME_HOVER;
+        }
+        event.dwFlags = flags;
+        event.hwndTrack = HWND(tracking.hwnd as *mut c_void);
+        event.dwHoverTime = tracking.hover_time;
+        return BOOL(1);
+    }
+
+    if (event.hwndTrack.0 as usize != tracking.hwnd) || flags.contains(TME_NONCLIENT) || !bot {
+        return original.call(event);
+    }
+
+    let cancel = flags.contains(TME_CANCEL);
+    if flags.contains(TME_LEAVE) {
+        tracking.leave = !cancel;
+    }
+
+    if flags.contains(TME_HOVER) {
+        tracking.hover = !cancel;
+        tracking.hover_time = match event.dwHoverTime {
+            HOVER_DEFAULT => system_hover_time(),
+            time => time,
+        };
+    }
+    BOOL(1)
+}
+
+fn client_contains(hwnd: HWND, x: i32, y: i32) -> bool {
+    let mut rect = RECT::default();
+    unsafe { GetClientRect(hwnd, &mut rect) }.is_ok()
+        && (rect.left..rect.right).contains(&x)
+        && (rect.top..rect.bottom).contains(&y)
+}
+
+//lParam of the WM_SETCURSOR Windows sends before a mouse message, None outside the client.
+pub fn set_cursor_lparam(hwnd: HWND, msg: u32, x: i32, y: i32) -> Option<LPARAM> {
+    if !client_contains(hwnd, x, y) {
+        return None;
+    }
+    Some(LPARAM((HTCLIENT | (msg << 16)) as isize))
+}
+
+//Called by the WndProc for the bot's WM_MOUSEMOVE, the user's moves are left to Windows.
+pub unsafe fn mouse_moved(hwnd: HWND, x: i32, y: i32, keys: usize) {
+    let inside = client_contains(hwnd, x, y);
+
+    let mut tracking = TRACKING.lock().unwrap();
+    let was_inside = tracking.inside;
+    tracking.inside = inside;
+
+    //Windows ends both kinds of tracking once the cursor leaves
+    if was_inside && !inside && tracking.leave {
+        tracking.leave = false;
+        tracking.hover = false;
+        let _ = KillTimer(Some(hwnd), HOVER_TIMER);
+        let _ = PostMessageW(Some(hwnd), WM_MOUSELEAVE, WPARAM(0), LPARAM(0));
+        return;
+    }
+
+    //every move restarts the hover time
+    if inside && tracking.hover {
+        tracking.position = (x, y);
+        tracking.keys = keys;
+        SetTimer(Some(hwnd), HOVER_TIMER, tracking.hover_time, None);
+    }
+}
+
+//Called by the WndProc on HOVER_TIMER, returns the WM_MOUSEHOVER to deliver if hover is still wanted.
+pub unsafe fn hover_elapsed(hwnd: HWND) -> Option<(WPARAM, LPARAM)> {
+    let _ = KillTimer(Some(hwnd), HOVER_TIMER);
+
+    let mut tracking = TRACKING.lock().unwrap();
+    if !tracking.hover || !tracking.inside {
+        return None;
+    }
+
+    tracking.hover = false;
+    let (x, y) = tracking.position;
+    Some((WPARAM(tracking.keys), LPARAM(pack_point(x, y))))
+}
+
+pub unsafe fn hook_hover(hwnd: u64) {
+    TRACKING.lock().unwrap().hwnd = hwnd as usize;
+
+    let track_mouse_event: TrackMouseEventFn =
+        std::mem::transmute(user32_function(s!("TrackMouseEvent")));
+
+    hook_function(
+        &ORIGINAL_TRACK_MOUSE_EVENT,
+        track_mouse_event,
+        hooked_track_mouse_event,
+        "TrackMouseEvent",
+    );
+}
+
+pub unsafe fn unhook_hover() {
+    let _ = ORIGINAL_TRACK_MOUSE_EVENT.get().map(|d| d.disable());
+    println!("[WaspInput]: TrackMouseEvent successfully unhooked.\r\n");
+}
+
+pub unsafe fn reenable_hover() {
+    let _ = ORIGINAL_TRACK_MOUSE_EVENT.get().map(|d| d.enable());
+}
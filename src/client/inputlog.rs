@@ -0,0 +1,93 @@
+//Writes every logged message the WndProc sees to the input log Simba asked for.
+use std::{
+    fs::{create_dir_all, File},
+    io::BufWriter,
+    path::Path,
+    sync::Mutex,
+    time::Instant,
+};
+
+use windows::Win32::{
+    Foundation::{HWND, RECT},
+    UI::WindowsAndMessaging::GetClientRect,
+};
+
+use crate::shared::{
+    inputlog::{message_event, LogEvent, LogWriter},
+    memory::MEMORY_MANAGER,
+};
+
+struct InputLog {
+    writer: LogWriter<BufWriter<File>>,
+    start: Instant,
+}
+
+static INPUT_LOG: Mutex<Option<InputLog>> = Mutex::new(None);
+
+//Called from the WndProc on WI_INPUT_LOG, a running log is closed first.
+pub fn start_input_log() {
+    stop_input_log();
+
+    let path = match MEMORY_MANAGER.get() {
+        Some(mem_manager) => unsafe { mem_manager.lock().unwrap().get_log_path() },
+        None => return,
+    };
+
+    let path = Path::new(&path);
+    if let Some(parent) = path.parent() {
+        let _ = create_dir_all(parent);
+    }
+
+    match File::create(path) {
+        Ok(file) => {
+            *INPUT_LOG.lock().unwrap() = Some(InputLog {
+                writer: LogWriter::new(BufWriter::new(file)),
+                start: Instant::now(),
+            });
+            println!("[WaspInput]: Logging input to {}.\r\n", path.display());
+        }
+        Err(e) => println!(
+            "[WaspInput]: Failed to create input log {}: {}\r\n",
+            path.display(),
+            e
+        ),
+    }
+}
+
+pub fn stop_input_log() {
+    if let Some(mut log) = INPUT_LOG.lock().unwrap().take() {
+        if let Err(e) = log.writer.flush() {
+            println!("[WaspInput]: Failed to write input log: {}\r\n", e);
+        }
+    }
+}
+
+pub fn log_message(hwnd: HWND, msg: u32, wparam: usize, lparam: isize, user: bool) {
+    let mut input_log = INPUT_LOG.lock().unwrap();
+    let log = match input_log.as_mut() {
+        Some(log) => log,
+        None => return,
+    };
+
+    let kind = match message_event(msg, wparam, lparam) {
+        Some(kind) => kind,
+        None => return,
+    };
+
+    let mut rect = RECT::default();
+    let _ = unsafe { GetClientRect(hwnd, &mut rect) };
+
+    let event = LogEvent {
+        time: log.start.elapsed().as_millis() as u64,
+        width: rect.right - rect.left,
+        height: rect.bottom - rect.top,
+        user,
+        kind,
+    };
+
+    //a log that can't be written stops instead of failing on every message
+    if let Err(e) = log.writer.write(&event) {
+        println!("[WaspInput]: Failed to write input log: {}\r\n", e);
+        *input_log = None;
+    }
+}
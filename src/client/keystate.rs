@@ -0,0 +1,107 @@
+//GetKeyState, GetAsyncKeyState and GetKeyboardState hooks so the client sees the keys the bot holds.
+use retour::GenericDetour;
+use std::sync::OnceLock;
+
+use windows::core::{s, BOOL};
+
+use super::hooks::{hook_function, user32_function};
+use crate::shared::{
+    keystate::{merge_async_key_state, merge_key, merge_key_state, KeyStateMode},
+    memory::MEMORY_MANAGER,
+};
+
+type KeyStateFn = unsafe extern "system" fn(i32) -> i16;
+type KeyboardStateFn = unsafe extern "system" fn(*mut u8) -> BOOL;
+
+static ORIGINAL_GET_KEY_STATE: OnceLock<GenericDetour<KeyStateFn>> = OnceLock::new();
+static ORIGINAL_GET_ASYNC_KEY_STATE: OnceLock<GenericDetour<KeyStateFn>> = OnceLock::new();
+static ORIGINAL_GET_KEYBOARD_STATE: OnceLock<GenericDetour<KeyboardStateFn>> = OnceLock::new();
+
+//Mode and bot state of vk, None when the real keyboard is all the client should see.
+fn virtual_key(vk: i32) -> Option<(KeyStateMode, u8)> {
+    let mem_manager = MEMORY_MANAGER.get()?.lock().unwrap();
+    let mode = unsafe { mem_manager.get_key_mode() };
+    if (mode == KeyStateMode::Off) || !(0..256).contains(&vk) {
+        return None;
+    }
+    Some((mode, unsafe { mem_manager.get_virtual_key(vk as u8) }))
+}
+
+unsafe extern "system" fn hooked_get_key_state(vk: i32) -> i16 {
+    let real = ORIGINAL_GET_KEY_STATE.get().unwrap().call(vk);
+    match virtual_key(vk) {
+        Some((mode, bot)) => merge_key_state(real, bot, mode),
+        None => real,
+    }
+}
+
+unsafe extern "system" fn hooked_get_async_key_state(vk: i32) -> i16 {
+    let real = ORIGINAL_GET_ASYNC_KEY_STATE.get().unwrap().call(vk);
+    match virtual_key(vk) {
+        Some((mode, bot)) => merge_async_key_state(real, bot, mode),
+        None => real,
+    }
+}
+
+unsafe extern "system" fn hooked_get_keyboard_state(keys: *mut u8) -> BOOL {
+    let result = ORIGINAL_GET_KEYBOARD_STATE.get().unwrap().call(keys);
+    if !result.as_bool() || keys.is_null() {
+        return result;
+    }
+
+    let (mode, bot) = match MEMORY_MANAGER.get() {
+        Some(m) => {
+            let mem_manager = m.lock().unwrap();
+            (mem_manager.get_key_mode(), mem_manager.get_virtual_keys())
+        }
+        None => return result,
+    };
+
+    if mode != KeyStateMode::Off {
+        let keys = &mut *(keys as *mut [u8; 256]);
+        for (real, bot) in keys.iter_mut().zip(bot) {
+            *real = merge_key(*real, bot, mode);
+        }
+    }
+    result
+}
+
+pub unsafe fn hook_key_state() {
+    let get_key_state: KeyStateFn = std::mem::transmute(user32_function(s!("GetKeyState")));
+    let get_async_key_state: KeyStateFn =
+        std::mem::transmute(user32_function(s!("GetAsyncKeyState")));
+    let get_keyboard_state: KeyboardStateFn =
+        std::mem::transmute(user32_function(s!("GetKeyboardState")));
+
+    hook_function(
+        &ORIGINAL_GET_KEY_STATE,
+        get_key_state,
+        hooked_get_key_state,
+        "GetKeyState",
+    );
+    hook_function(
+        &ORIGINAL_GET_ASYNC_KEY_STATE,
+        get_async_key_state,
+        hooked_get_async_key_state,
+        "GetAsyncKeyState",
+    );
+    hook_function(
+        &ORIGINAL_GET_KEYBOARD_STATE,
+        get_keyboard_state,
+        hooked_get_keyboard_state,
+        "GetKeyboardState",
+    );
+}
+
+pub unsafe fn unhook_key_state() {
+    let _ = ORIGINAL_GET_KEY_STATE.get().map(|d| d.disable());
+    let _ = ORIGINAL_GET_ASYNC_KEY_STATE.get().map(|d| d.disable());
+    let _ = ORIGINAL_GET_KEYBOARD_STATE.get().map(|d| d.disable());
+    println!("[WaspInput]: Key state functions successfully unhooked.\r\n");
+}
+
+pub unsafe fn reenable_key_state() {
+    let _ = ORIGINAL_GET_KEY_STATE.get().map(|d| d.enable());
+    let _ = ORIGINAL_GET_ASYNC_KEY_STATE.get().map(|d| d.enable());
+    let _ = ORIGINAL_GET_KEYBOARD_STATE.get().map(|d| d.enable());
+}
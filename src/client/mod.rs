@@ -1,2 +1,10 @@
+pub mod clipboard;
+pub mod cursor;
+pub mod focus;
 pub mod graphics;
 pub mod hooks;
+pub mod hover;
+pub mod inputlog;
+pub mod keystate;
+pub mod rawinput;
+pub mod sequence;
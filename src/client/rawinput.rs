@@ -0,0 +1,194 @@
+//Synthesised raw input records and the GetRawInputData and GetRawInputBuffer hooks that hand them out.
+use retour::GenericDetour;
+use std::{
+    collections::VecDeque,
+    ffi::c_void,
+    ptr::copy_nonoverlapping,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex, OnceLock,
+    },
+};
+
+use windows::{
+    core::s,
+    Win32::Foundation::{SetLastError, ERROR_INSUFFICIENT_BUFFER, ERROR_INVALID_PARAMETER},
+};
+
+use super::hooks::{hook_function, user32_function};
+use crate::shared::rawinput::{align_record, header_size, RID_HEADER, RID_INPUT};
+
+type GetRawInputDataFn =
+    unsafe extern "system" fn(*mut c_void, u32, *mut c_void, *mut u32, u32) -> u32;
+type GetRawInputBufferFn = unsafe extern "system" fn(*mut c_void, *mut u32, u32) -> u32;
+
+static ORIGINAL_GET_RAW_INPUT_DATA: OnceLock<GenericDetour<GetRawInputDataFn>> = OnceLock::new();
+static ORIGINAL_GET_RAW_INPUT_BUFFER: OnceLock<GenericDetour<GetRawInputBufferFn>> =
+    OnceLock::new();
+
+//Only the latest records are kept, a WM_INPUT handler reads its record right away.
+const QUEUE_SIZE: usize = 64;
+//Fake HRAWINPUT handles are this tag with a 16 bit id.
+const HANDLE_TAG: usize = 0x5741_0000;
+
+struct Record {
+    handle: usize,
+    bytes: Vec<u8>,
+    read: bool, //already handed out, GetRawInputBuffer skips it
+}
+
+static RECORDS: Mutex<VecDeque<Record>> = Mutex::new(VecDeque::new());
+static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+
+//Queues a complete RAWINPUT and returns the handle to send with WM_INPUT.
+pub fn push_record(bytes: Vec<u8>) -> isize {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed) & 0xFFFF;
+    let handle = HANDLE_TAG | id as usize;
+
+    let mut records = RECORDS.lock().unwrap();
+    if records.len() >= QUEUE_SIZE {
+        records.pop_front();
+    }
+    records.push_back(Record {
+        handle,
+        bytes,
+        read: false,
+    });
+    handle as isize
+}
+
+fn find_record(handle: usize, mark_read: bool) -> Option<Vec<u8>> {
+    if (handle & !0xFFFF) != HANDLE_TAG {
+        return None;
+    }
+
+    let mut records = RECORDS.lock().unwrap();
+    let record = records.iter_mut().find(|r| r.handle == handle)?;
+    record.read |= mark_read;
+    Some(record.bytes.clone())
+}
+
+unsafe extern "system" fn hooked_get_raw_input_data(
+    handle: *mut c_void,
+    command: u32,
+    data: *mut c_void,
+    size: *mut u32,
+    header: u32,
+) -> u32 {
+    let original = ORIGINAL_GET_RAW_INPUT_DATA.get().unwrap();
+    let bytes = match find_record(handle as usize, !data.is_null() && (command == RID_INPUT)) {
+        Some(bytes) => bytes,
+        None => return original.call(handle, command, data, size, header),
+    };
+
+    if size.is_null() || (header as usize != header_size()) {
+        SetLastError(ERROR_INVALID_PARAMETER);
+        return u32::MAX;
+    }
+
+    let bytes = match command {
+        RID_INPUT => &bytes[..],
+        RID_HEADER => &bytes[..header_size()],
+        _ => {
+            SetLastError(ERROR_INVALID_PARAMETER);
+            return u32::MAX;
+        }
+    };
+
+    if data.is_null() {
+        *size = bytes.len() as u32;
+        return 0;
+    }
+
+    if (*size as usize) < bytes.len() {
+        *size = bytes.len() as u32;
+        SetLastError(ERROR_INSUFFICIENT_BUFFER);
+        return u32::MAX;
+    }
+
+    copy_nonoverlapping(bytes.as_ptr(), data as *mut u8, bytes.len());
+    bytes.len() as u32
+}
+
+//Unread records are appended after whatever the real buffer returned.
+unsafe extern "system" fn hooked_get_raw_input_buffer(
+    data: *mut c_void,
+    size: *mut u32,
+    header: u32,
+) -> u32 {
+    let count = ORIGINAL_GET_RAW_INPUT_BUFFER
+        .get()
+        .unwrap()
+        .call(data, size, header);
+    if (count == u32::MAX) || size.is_null() || (header as usize != header_size()) {
+        return count;
+    }
+
+    let mut records = RECORDS.lock().unwrap();
+    if data.is_null() {
+        if *size == 0 {
+            if let Some(record) = records.iter().find(|r| !r.read) {
+                *size = align_record(record.bytes.len()) as u32;
+            }
+        }
+        return count;
+    }
+
+    //skip over the records the real buffer wrote, dwSize follows dwType in every header
+    let buffer = data as *mut u8;
+    let mut offset = 0;
+    for _ in 0..count {
+        let record_size = (buffer.add(offset + 4) as *const u32).read_unaligned() as usize;
+        offset = align_record(offset + record_size);
+    }
+
+    let capacity = *size as usize;
+    let mut count = count;
+    for record in records.iter_mut().filter(|r| !r.read) {
+        if offset + record.bytes.len() > capacity {
+            break;
+        }
+
+        copy_nonoverlapping(
+            record.bytes.as_ptr(),
+            buffer.add(offset),
+            record.bytes.len(),
+        );
+        offset = align_record(offset + record.bytes.len());
+        record.read = true;
+        count += 1;
+    }
+    count
+}
+
+pub unsafe fn hook_raw_input() {
+    let get_raw_input_data: GetRawInputDataFn =
+        std::mem::transmute(user32_function(s!("GetRawInputData")));
+    let get_raw_input_buffer: GetRawInputBufferFn =
+        std::mem::transmute(user32_function(s!("GetRawInputBuffer")));
+
+    hook_function(
+        &ORIGINAL_GET_RAW_INPUT_DATA,
+        get_raw_input_data,
+        hooked_get_raw_input_data,
+        "GetRawInputData",
+    );
+    hook_function(
+        &ORIGINAL_GET_RAW_INPUT_BUFFER,
+        get_raw_input_buffer,
+        hooked_get_raw_input_buffer,
+        "GetRawInputBuffer",
+    );
+}
+
+pub unsafe fn unhook_raw_input() {
+    let _ = ORIGINAL_GET_RAW_INPUT_DATA.get().map(|d| d.disable());
+    let _ = ORIGINAL_GET_RAW_INPUT_BUFFER.get().map(|d| d.disable());
+    RECORDS.lock().unwrap().clear();
+    println!("[WaspInput]: Raw input functions successfully unhooked.\r\n");
+}
+
+pub unsafe fn reenable_raw_input() {
+    let _ = ORIGINAL_GET_RAW_INPUT_DATA.get().map(|d| d.enable());
+    let _ = ORIGINAL_GET_RAW_INPUT_BUFFER.get().map(|d| d.enable());
+}
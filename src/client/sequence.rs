@@ -0,0 +1,237 @@
+//Replays input sequences submitted by Simba on a client thread so timing doesn't depend on Simba.
+use std::{
+    thread::{sleep, yield_now},
+    time::{Duration, Instant},
+};
+
+use crate::shared::{
+    layout::{KeyboardLayout, Layout},
+    memory::MEMORY_MANAGER,
+    message::{button_index, mouse_key_state, split_scroll, KeyFlags, ScrollMode, BUTTON_COUNT},
+    sequence::{EventKind, InputEvent, SequenceState, FLAG_ALIGN_FRAME, FLAG_HORIZONTAL},
+    windows::{button, key_down, key_up, keyboard_layout, mouse_move, scroll},
+};
+
+//Sleeping is only trusted up to this close to an event, the rest is spun.
+const SPIN_TIME: Duration = Duration::from_millis(2);
+//Frame aligned events give up waiting after this, so a minimized client doesn't stall the sequence.
+const FRAME_TIMEOUT: Duration = Duration::from_millis(1000);
+
+fn is_running(id: u32) -> bool {
+    let mem_manager = MEMORY_MANAGER
+        .get()
+        .expect("[WaspInput]: Memory manager is not initialized!\r\n")
+        .lock()
+        .unwrap();
+    unsafe { mem_manager.get_sequence_state(id) == SequenceState::Running }
+}
+
+fn frame_count() -> u32 {
+    let mem_manager = MEMORY_MANAGER
+        .get()
+        .expect("[WaspInput]: Memory manager is not initialized!\r\n")
+        .lock()
+        .unwrap();
+    unsafe { mem_manager.get_frame_count() }
+}
+
+//Returns false if the sequence was cancelled while waiting.
+fn wait_until(id: u32, deadline: Instant) -> bool {
+    loop {
+        if !is_running(id) {
+            return false;
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+
+        let left = deadline - now;
+        if left > SPIN_TIME {
+            sleep((left - SPIN_TIME).min(Duration::from_millis(10)));
+        } else {
+            yield_now();
+        }
+    }
+}
+
+//Waits while the user is using the client, returns how long that was or None if cancelled meanwhile.
+fn wait_for_user(id: u32) -> Option<Duration> {
+    let start = Instant::now();
+    loop {
+        let paused = {
+            let mem_manager = MEMORY_MANAGER
+                .get()
+                .expect("[WaspInput]: Memory manager is not initialized!\r\n")
+                .lock()
+                .unwrap();
+            unsafe { mem_manager.bot_paused() }
+        };
+
+        if !paused {
+            return Some(start.elapsed());
+        }
+        if !is_running(id) {
+            return None;
+        }
+        sleep(Duration::from_millis(10));
+    }
+}
+
+fn wait_frame(id: u32) -> bool {
+    let count = frame_count();
+    let start = Instant::now();
+    while frame_count() == count {
+        if !is_running(id) {
+            return false;
+        }
+        if start.elapsed() >= FRAME_TIMEOUT {
+            break;
+        }
+        sleep(Duration::from_millis(1));
+    }
+    true
+}
+
+//What the sequence is holding, so a cancelled one doesn't leave anything pressed.
+struct Held {
+    position: (i32, i32),
+    buttons: [bool; BUTTON_COUNT],
+    keys: [bool; 255],
+}
+
+impl Held {
+    fn key_state(&self) -> u16 {
+        mouse_key_state(&self.buttons, &self.keys)
+    }
+
+    fn key_flags(&self, vk: u8, down: bool) -> KeyFlags {
+        let held = |keys: [usize; 3]| {
+            keys.iter()
+                .any(|&k| if k == vk as usize { down } else { self.keys[k] })
+        };
+        KeyFlags {
+            alt: held([0x12, 0xA4, 0xA5]),
+            ctrl: held([0x11, 0xA2, 0xA3]),
+            previous: self.keys[vk as usize],
+            repeat: 1,
+        }
+    }
+}
+
+fn run_event(hwnd: u64, layout: &dyn KeyboardLayout, event: &InputEvent, held: &mut Held) {
+    let (x, y) = held.position;
+    match EventKind::from_i32(event.kind) {
+        Some(EventKind::Move) => {
+            let keys = held.key_state();
+            mouse_move(hwnd, event.x, event.y, keys);
+            held.position = (event.x, event.y);
+        }
+        Some(EventKind::ButtonDown) | Some(EventKind::ButtonUp) => {
+            let down = event.kind == EventKind::ButtonDown as i32;
+            if let Some(index) = button_index(event.data) {
+                held.buttons[index] = down;
+                button(hwnd, index, down, false, x, y, held.key_state());
+            }
+        }
+        Some(EventKind::KeyDown) => {
+            let vk = event.data as u8;
+            key_down(hwnd, layout.vk_key(vk), held.key_flags(vk, true));
+            held.keys[event.data as usize] = true;
+        }
+        Some(EventKind::KeyUp) => {
+            let vk = event.data as u8;
+            key_up(hwnd, layout.vk_key(vk), held.key_flags(vk, false));
+            held.keys[event.data as usize] = false;
+        }
+        Some(EventKind::Scroll) => {
+            let horizontal = (event.flags & FLAG_HORIZONTAL) != 0;
+            let notches = if horizontal {
+                event.data
+            } else {
+                event.data.saturating_neg()
+            };
+            let keys = held.key_state();
+            for delta in split_scroll(notches, ScrollMode::Instant) {
+                scroll(hwnd, delta, horizontal, keys, x, y);
+            }
+        }
+        None => {}
+    }
+}
+
+fn release_held(hwnd: u64, layout: &dyn KeyboardLayout, held: &mut Held) {
+    let (x, y) = held.position;
+    for index in 0..BUTTON_COUNT {
+        if held.buttons[index] {
+            held.buttons[index] = false;
+            button(hwnd, index, false, false, x, y, held.key_state());
+        }
+    }
+
+    for (key, &down) in held.keys.iter().enumerate() {
+        if down {
+            key_up(
+                hwnd,
+                layout.vk_key(key as u8),
+                held.key_flags(key as u8, false),
+            );
+        }
+    }
+}
+
+//layout is the one the script pinned on the target, the same KeyDown and KeySend map with.
+pub fn replay_sequence(hwnd: u64, id: u32, layout: Layout) {
+    let (events, position) = {
+        let mem_manager = MEMORY_MANAGER
+            .get()
+            .expect("[WaspInput]: Memory manager is not initialized!\r\n")
+            .lock()
+            .unwrap();
+        match unsafe { mem_manager.take_sequence(id) } {
+            Some(events) => (events, unsafe { mem_manager.get_mouse_position() }),
+            None => return,
+        }
+    };
+
+    let mut held = Held {
+        position,
+        buttons: [false; BUTTON_COUNT],
+        keys: [false; 255],
+    };
+
+    let layout = keyboard_layout(hwnd, layout);
+    let mut start = Instant::now();
+    for event in &events {
+        //a pause for the user shifts the rest of the sequence instead of rushing it
+        match wait_for_user(id) {
+            Some(paused) => start += paused,
+            None => break,
+        }
+
+        let deadline = start + Duration::from_millis(event.time as u64);
+        if !wait_until(id, deadline) {
+            break;
+        }
+
+        if ((event.flags & FLAG_ALIGN_FRAME) != 0) && !wait_frame(id) {
+            break;
+        }
+
+        run_event(hwnd, &*layout, event, &mut held);
+    }
+
+    let finished = {
+        let mem_manager = MEMORY_MANAGER
+            .get()
+            .expect("[WaspInput]: Memory manager is not initialized!\r\n")
+            .lock()
+            .unwrap();
+        unsafe { mem_manager.finish_sequence(id) }
+    };
+
+    if !finished {
+        release_held(hwnd, &*layout, &mut held);
+    }
+}
@@ -1,16 +1,46 @@
 use std::ffi::CStr;
-use std::os::raw::c_char;
+use std::os::raw::{c_char, c_void};
+use std::path::Path;
+use std::ptr::null_mut;
 use std::sync::Mutex;
 
-use shared::windows::{get_jagrenderview, inject, is_input_enabled, open_console, toggle_input};
-use simba::target::{SimbaTarget, TARGETS};
+use shared::autorepeat::{RepeatMode, RepeatTiming};
+use shared::image::{composite, crop, save_image, PixelFormat, TILE_COLUMNS, TILE_ROWS};
+use shared::keystate::KeyStateMode;
+use shared::layout::Layout;
+use shared::memory::{CursorMode, InputOwner, UserInputPolicy, Visibility, MEMORY_MANAGER};
+use shared::message::ScrollMode;
+use shared::rawinput::InputDelivery;
+use shared::sequence::{validate_sequence, EventKind, InputEvent, SequenceState};
+use shared::windows::{
+    fake_focus, get_jagrenderview, inject, input_log, is_input_enabled, open_console,
+    set_capture_format, start_sequence, toggle_input, window_visibility,
+};
+use simba::path::{box_point, PathKind, PathSettings};
+use simba::random::Random;
+use simba::recorder::{dump_recording, start_recording, stop_recording, RecorderConfig};
+use simba::replay::replay_log;
+use simba::target::{
+    check_point, double_click_target, drag_target, input_allowed, move_along_path, paste_target,
+    release_all, scroll_target, wait_for_input, CoordinatePolicy, HiddenInput, SimbaTarget,
+    TARGETS,
+};
+use simba::timing::{find_profile, Distribution, Timing, TimingKind, PROFILES};
 
 mod client;
 mod shared;
 mod simba;
 
 // Pascal types as tuples (name, definition)
-const PASCAL_TYPES: &[(&str, &str)] = &[("PHelloChar", "^Char;"), ("PTestInt", "^Int32;")];
+const PASCAL_TYPES: &[(&str, &str)] = &[
+    ("PHelloChar", "^Char;"),
+    ("PTestInt", "^Int32;"),
+    (
+        "TInputEvent",
+        "record Kind: Int32; Time: UInt32; X, Y, Data: Int32; Flags: UInt32; end;",
+    ),
+    ("PInputEvent", "^TInputEvent;"),
+];
 
 // Pascal exports as (name, declaration)
 //name as to match the dll function name exactly
@@ -25,6 +55,145 @@ const PASCAL_EXPORTS: &[(&str, &str)] = &[
         "SetInputState",
         "function SetInputState(state: Boolean): Boolean;",
     ),
+    (
+        "SaveScreenshot",
+        "function SaveScreenshot(path: String; x1, y1, x2, y2: Int32; overlay: Boolean): Boolean;",
+    ),
+    (
+        "RecordStart",
+        "function RecordStart(pid: UInt32; path: String; fps, width, height, rolling: Int32; annotate: Boolean): Boolean;",
+    ),
+    ("RecordStop", "procedure RecordStop();"),
+    ("RecordDump", "function RecordDump(path: String): Boolean;"),
+    (
+        "SetCaptureFormat",
+        "function SetCaptureFormat(pid: UInt32; format: Int32): Boolean;",
+    ),
+    (
+        "GetCaptureBuffer",
+        "function GetCaptureBuffer(out width, height, stride, format: Int32): Pointer;",
+    ),
+    ("GetFrameHash", "function GetFrameHash(): UInt64;"),
+    ("GetFramesSinceChange", "function GetFramesSinceChange(): UInt32;"),
+    (
+        "IsClientFrozen",
+        "function IsClientFrozen(timeout: UInt32): Boolean;",
+    ),
+    (
+        "GetTileHashes",
+        "function GetTileHashes(out columns, rows: Int32): PUInt32;",
+    ),
+    (
+        "GetClientVisibility",
+        "function GetClientVisibility(pid: UInt32): Int32;",
+    ),
+    (
+        "SetHiddenInputPolicy",
+        "function SetHiddenInputPolicy(pid: UInt32; policy: Int32; timeout: UInt32): Boolean;",
+    ),
+    (
+        "SetCoordinatePolicy",
+        "function SetCoordinatePolicy(pid: UInt32; policy: Int32): Boolean;",
+    ),
+    (
+        "SetScrollMode",
+        "function SetScrollMode(pid: UInt32; mode: Int32; delay: UInt32): Boolean;",
+    ),
+    (
+        "MouseScrollHorizontal",
+        "function MouseScrollHorizontal(pid: UInt32; scrolls: Int32): Boolean;",
+    ),
+    (
+        "SetMousePath",
+        "function SetMousePath(pid: UInt32; kind: Int32; speed, gravity, wind, overshoot: Double; jitter: Int32): Boolean;",
+    ),
+    (
+        "MouseMoveTo",
+        "function MouseMoveTo(pid: UInt32; x, y: Int32): Boolean;",
+    ),
+    (
+        "MouseMoveToBox",
+        "function MouseMoveToBox(pid: UInt32; x1, y1, x2, y2: Int32): Boolean;",
+    ),
+    (
+        "SendSequence",
+        "function SendSequence(pid: UInt32; events: PInputEvent; count: Int32): UInt32;",
+    ),
+    (
+        "GetSequenceState",
+        "function GetSequenceState(id: UInt32): Int32;",
+    ),
+    ("CancelSequence", "function CancelSequence(id: UInt32): Boolean;"),
+    (
+        "MouseDrag",
+        "function MouseDrag(pid: UInt32; button, x, y: Int32): Boolean;",
+    ),
+    (
+        "DoubleClick",
+        "function DoubleClick(pid: UInt32; button: Int32): Boolean;",
+    ),
+    (
+        "ReleaseAllInput",
+        "function ReleaseAllInput(pid: UInt32): Boolean;",
+    ),
+    (
+        "SetKeyStateMode",
+        "function SetKeyStateMode(pid: UInt32; mode: Int32): Boolean;",
+    ),
+    (
+        "SetFakeFocus",
+        "function SetFakeFocus(pid: UInt32; enabled: Boolean): Boolean;",
+    ),
+    (
+        "SetCursorMode",
+        "function SetCursorMode(pid: UInt32; mode: Int32): Boolean;",
+    ),
+    (
+        "SetInputDelivery",
+        "function SetInputDelivery(pid: UInt32; delivery: Int32): Boolean;",
+    ),
+    (
+        "SetUserInputPolicy",
+        "function SetUserInputPolicy(pid: UInt32; policy: Int32; idle: UInt32): Boolean;",
+    ),
+    ("GetInputOwner", "function GetInputOwner(pid: UInt32): Int32;"),
+    (
+        "SetKeyboardLayout",
+        "function SetKeyboardLayout(pid: UInt32; layout: Int32): Boolean;",
+    ),
+    (
+        "SetKeyRepeat",
+        "function SetKeyRepeat(pid: UInt32; mode: Int32; delay, interval: UInt32): Boolean;",
+    ),
+    (
+        "PasteText",
+        "function PasteText(pid: UInt32; text: PChar; len: Int32; sleeptimes: PInt32): Boolean;",
+    ),
+    (
+        "SetPasteTimeout",
+        "function SetPasteTimeout(pid: UInt32; timeout: UInt32): Boolean;",
+    ),
+    (
+        "InputLogStart",
+        "function InputLogStart(pid: UInt32; path: PChar): Boolean;",
+    ),
+    ("InputLogStop", "function InputLogStop(pid: UInt32): Boolean;"),
+    (
+        "InputLogReplay",
+        "function InputLogReplay(pid: UInt32; path: PChar): Boolean;",
+    ),
+    (
+        "SetTimingProfile",
+        "function SetTimingProfile(pid: UInt32; name: PChar; seed: UInt32): Boolean;",
+    ),
+    (
+        "SetTimingDistribution",
+        "function SetTimingDistribution(pid: UInt32; kind, distribution: Int32; a, b: Double): Boolean;",
+    ),
+    (
+        "SetTimingFatigue",
+        "function SetTimingFatigue(pid: UInt32; fatigue: Double): Boolean;",
+    ),
 ];
 
 lazy_static::lazy_static! {
@@ -58,12 +227,7 @@ pub extern "system" fn Inject(path: *const c_char, pid: u32) -> bool {
         }
     };
 
-    let new_target = SimbaTarget {
-        pid,
-        hwnd,
-        keyboard: [false; 255],
-        mouse: [false; 3],
-    };
+    let new_target = SimbaTarget::new(pid, hwnd);
 
     let mut targets = TARGETS.lock().unwrap();
     targets.insert(pid, new_target);
@@ -98,3 +262,893 @@ pub extern "system" fn SetInputState(state: bool) -> bool {
         None => false,
     }
 }
+
+/// # Safety
+/// path has to be null or a nul terminated string.
+#[no_mangle]
+pub unsafe extern "system" fn SaveScreenshot(
+    path: *const c_char,
+    x1: i32,
+    y1: i32,
+    x2: i32,
+    y2: i32,
+    overlay: bool,
+) -> bool {
+    if path.is_null() {
+        println!("[WaspInput]: Invalid string\n");
+        return false;
+    }
+
+    let path = match unsafe { CStr::from_ptr(path) }.to_str() {
+        Ok(s) => Path::new(s),
+        Err(_) => {
+            println!("[WaspInput]: Invalid UTF-8\n");
+            return false;
+        }
+    };
+
+    let mem_manager = match MEMORY_MANAGER.get() {
+        Some(m) => m.lock().unwrap(),
+        None => {
+            println!("[WaspInput]: Memory manager is not initialized!\r\n");
+            return false;
+        }
+    };
+
+    let (width, height, mut img) = match unsafe { mem_manager.copy_frame() } {
+        Some(frame) => frame,
+        None => {
+            println!("[WaspInput]: No complete frame available.\r\n");
+            return false;
+        }
+    };
+
+    if overlay {
+        let layer = unsafe { mem_manager.copy_overlay(width, height) };
+        composite(&mut img, &layer);
+    }
+    drop(mem_manager);
+
+    let (width, height, img) = crop(&img, width, height, x1, y1, x2, y2);
+    match save_image(path, width, height, &img) {
+        Ok(_) => true,
+        Err(e) => {
+            println!("[WaspInput]: Failed to save screenshot: {}\r\n", e);
+            false
+        }
+    }
+}
+
+/// # Safety
+/// path has to be null or a nul terminated string.
+#[no_mangle]
+pub unsafe extern "system" fn RecordStart(
+    pid: u32,
+    path: *const c_char,
+    fps: i32,
+    width: i32,
+    height: i32,
+    rolling: i32,
+    annotate: bool,
+) -> bool {
+    if path.is_null() {
+        println!("[WaspInput]: Invalid string\n");
+        return false;
+    }
+
+    let path = match unsafe { CStr::from_ptr(path) }.to_str() {
+        Ok(s) => Path::new(s),
+        Err(_) => {
+            println!("[WaspInput]: Invalid UTF-8\n");
+            return false;
+        }
+    };
+
+    start_recording(RecorderConfig {
+        pid,
+        fps: fps.max(0) as u32,
+        width: width.max(0),
+        height: height.max(0),
+        rolling: rolling.max(0) as u32,
+        annotate,
+        path: path.to_path_buf(),
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn RecordStop() {
+    stop_recording();
+}
+
+/// # Safety
+/// path has to be null or a nul terminated string.
+#[no_mangle]
+pub unsafe extern "system" fn RecordDump(path: *const c_char) -> bool {
+    if path.is_null() {
+        println!("[WaspInput]: Invalid string\n");
+        return false;
+    }
+
+    match unsafe { CStr::from_ptr(path) }.to_str() {
+        Ok(s) => dump_recording(Path::new(s)),
+        Err(_) => {
+            println!("[WaspInput]: Invalid UTF-8\n");
+            false
+        }
+    }
+}
+
+//Gray and HSV are converted when Simba reads the frame, the client only copies what OpenGL packs.
+#[no_mangle]
+pub extern "system" fn SetCaptureFormat(pid: u32, format: i32) -> bool {
+    let format = match PixelFormat::from_i32(format) {
+        Some(f) => f,
+        None => {
+            println!("[WaspInput]: Unknown capture format: {}\r\n", format);
+            return false;
+        }
+    };
+
+    let targets = TARGETS.lock().unwrap();
+    let hwnd = match targets.get(&pid) {
+        Some(target) => target.hwnd,
+        None => return false,
+    };
+
+    if let Some(mem_manager) = MEMORY_MANAGER.get() {
+        mem_manager.lock().unwrap().set_requested_format(format);
+    }
+    set_capture_format(hwnd, format.capture_format())
+}
+
+//The latest frame in the format set with SetCaptureFormat, a converted frame stays valid until the next call.
+/// # Safety
+/// width, height, stride and format have to be null or valid to write an Int32 to.
+#[no_mangle]
+pub unsafe extern "system" fn GetCaptureBuffer(
+    width: *mut i32,
+    height: *mut i32,
+    stride: *mut i32,
+    format: *mut i32,
+) -> *mut c_void {
+    if width.is_null() || height.is_null() || stride.is_null() || format.is_null() {
+        return null_mut();
+    }
+
+    let mut mem_manager = match MEMORY_MANAGER.get() {
+        Some(m) => m.lock().unwrap(),
+        None => return null_mut(),
+    };
+
+    unsafe {
+        let (ptr, s, f) = match mem_manager.capture_buffer() {
+            Some(buffer) => buffer,
+            None => return null_mut(),
+        };
+        (*width, *height) = mem_manager.get_dimensions();
+        (*format, *stride) = (f as i32, s);
+        ptr as *mut c_void
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn GetFrameHash() -> u64 {
+    match MEMORY_MANAGER.get() {
+        Some(m) => unsafe { m.lock().unwrap().get_frame_hash() },
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn GetFramesSinceChange() -> u32 {
+    match MEMORY_MANAGER.get() {
+        Some(m) => unsafe { m.lock().unwrap().frames_since_change() },
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn IsClientFrozen(timeout: u32) -> bool {
+    match MEMORY_MANAGER.get() {
+        Some(m) => unsafe { m.lock().unwrap().is_frozen(timeout as u64) },
+        None => false,
+    }
+}
+
+/// # Safety
+/// columns and rows have to be null or valid to write an Int32 to.
+#[no_mangle]
+pub unsafe extern "system" fn GetTileHashes(columns: *mut i32, rows: *mut i32) -> *mut u32 {
+    if columns.is_null() || rows.is_null() {
+        return null_mut();
+    }
+
+    let mem_manager = match MEMORY_MANAGER.get() {
+        Some(m) => m.lock().unwrap(),
+        None => return null_mut(),
+    };
+
+    unsafe {
+        (*columns, *rows) = (TILE_COLUMNS as i32, TILE_ROWS as i32);
+        mem_manager.tile_hashes_ptr()
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn GetClientVisibility(pid: u32) -> i32 {
+    let targets = TARGETS.lock().unwrap();
+    match targets.get(&pid) {
+        Some(target) => window_visibility(target.hwnd) as i32,
+        None => Visibility::Unknown as i32,
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn SetHiddenInputPolicy(pid: u32, policy: i32, timeout: u32) -> bool {
+    let policy = match HiddenInput::from_i32(policy) {
+        Some(p) => p,
+        None => {
+            println!("[WaspInput]: Unknown hidden input policy: {}\r\n", policy);
+            return false;
+        }
+    };
+
+    let mut targets = TARGETS.lock().unwrap();
+    match targets.get_mut(&pid) {
+        Some(target) => {
+            target.hidden_input = policy;
+            target.hidden_timeout = timeout as u64;
+            true
+        }
+        None => false,
+    }
+}
+
+//Allow (0), clamp (1) or reject (2) points outside the client.
+#[no_mangle]
+pub extern "system" fn SetCoordinatePolicy(pid: u32, policy: i32) -> bool {
+    let policy = match CoordinatePolicy::from_i32(policy) {
+        Some(p) => p,
+        None => {
+            println!("[WaspInput]: Unknown coordinate policy: {}\r\n", policy);
+            return false;
+        }
+    };
+
+    let mut targets = TARGETS.lock().unwrap();
+    match targets.get_mut(&pid) {
+        Some(target) => {
+            target.coordinates = policy;
+            true
+        }
+        None => false,
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn SetScrollMode(pid: u32, mode: i32, delay: u32) -> bool {
+    let mode = match ScrollMode::from_i32(mode) {
+        Some(m) => m,
+        None => {
+            println!("[WaspInput]: Unknown scroll mode: {}\r\n", mode);
+            return false;
+        }
+    };
+
+    let mut targets = TARGETS.lock().unwrap();
+    match targets.get_mut(&pid) {
+        Some(target) => {
+            target.scroll_mode = mode;
+            target.scroll_delay = delay as u64;
+            true
+        }
+        None => false,
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn MouseScrollHorizontal(pid: u32, scrolls: i32) -> bool {
+    if !wait_for_input(pid) {
+        return false;
+    }
+
+    let targets = TARGETS.lock().unwrap();
+    match targets.get(&pid) {
+        Some(target) if input_allowed(target) => {
+            scroll_target(target, scrolls, true);
+            true
+        }
+        _ => false,
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn SetMousePath(
+    pid: u32,
+    kind: i32,
+    speed: f64,
+    gravity: f64,
+    wind: f64,
+    overshoot: f64,
+    jitter: i32,
+) -> bool {
+    let kind = match PathKind::from_i32(kind) {
+        Some(k) => k,
+        None => {
+            println!("[WaspInput]: Unknown mouse path kind: {}\r\n", kind);
+            return false;
+        }
+    };
+
+    if !speed.is_finite() || (speed <= 0.0) {
+        println!("[WaspInput]: Mouse path speed has to be above 0.\r\n");
+        return false;
+    }
+
+    if !gravity.is_finite() || !wind.is_finite() || !overshoot.is_finite() {
+        println!("[WaspInput]: Mouse path gravity, wind and overshoot have to be finite.\r\n");
+        return false;
+    }
+
+    let mut targets = TARGETS.lock().unwrap();
+    match targets.get_mut(&pid) {
+        Some(target) => {
+            target.path = PathSettings {
+                kind,
+                speed,
+                gravity,
+                wind,
+                overshoot: overshoot.clamp(0.0, 1.0),
+                jitter,
+            };
+            true
+        }
+        None => false,
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn MouseMoveTo(pid: u32, x: i32, y: i32) -> bool {
+    if !wait_for_input(pid) {
+        return false;
+    }
+
+    let mut targets = TARGETS.lock().unwrap();
+    match targets.get_mut(&pid) {
+        Some(target) if input_allowed(target) => move_along_path(target, x as f64, y as f64),
+        _ => false,
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn MouseMoveToBox(pid: u32, x1: i32, y1: i32, x2: i32, y2: i32) -> bool {
+    if !wait_for_input(pid) {
+        return false;
+    }
+
+    let mut targets = TARGETS.lock().unwrap();
+    match targets.get_mut(&pid) {
+        Some(target) if input_allowed(target) => {
+            let (x, y) = box_point(x1, y1, x2, y2, &mut target.rng);
+            move_along_path(target, x, y)
+        }
+        _ => false,
+    }
+}
+
+//Returns the id of the queued sequence, 0 if it couldn't be queued.
+/// # Safety
+/// events has to be null or point to count InputEvents.
+#[no_mangle]
+pub unsafe extern "system" fn SendSequence(pid: u32, events: *const InputEvent, count: i32) -> u32 {
+    if events.is_null() || (count <= 0) {
+        println!("[WaspInput]: Sequence is empty.\r\n");
+        return 0;
+    }
+
+    let events = unsafe { std::slice::from_raw_parts(events, count as usize) };
+    if let Err(e) = validate_sequence(events) {
+        println!("[WaspInput]: {}.\r\n", e);
+        return 0;
+    }
+
+    if !wait_for_input(pid) {
+        return 0;
+    }
+
+    let targets = TARGETS.lock().unwrap();
+    let target = match targets.get(&pid) {
+        Some(target) if input_allowed(target) => target,
+        _ => return 0,
+    };
+
+    let mut events = events.to_vec();
+    for event in events.iter_mut() {
+        if event.kind != EventKind::Move as i32 {
+            continue;
+        }
+        match check_point(target, event.x, event.y) {
+            Some((x, y)) => (event.x, event.y) = (x, y),
+            None => return 0,
+        }
+    }
+
+    let mem_manager = match MEMORY_MANAGER.get() {
+        Some(m) => m.lock().unwrap(),
+        None => return 0,
+    };
+
+    let id = match unsafe { mem_manager.submit_sequence(&events) } {
+        Some(id) => id,
+        None => {
+            println!("[WaspInput]: A sequence is already running.\r\n");
+            return 0;
+        }
+    };
+
+    if !start_sequence(target.hwnd, id, target.layout) {
+        unsafe { mem_manager.cancel_sequence(id) };
+        return 0;
+    }
+    id
+}
+
+#[no_mangle]
+pub extern "system" fn GetSequenceState(id: u32) -> i32 {
+    match MEMORY_MANAGER.get() {
+        Some(m) => unsafe { m.lock().unwrap().get_sequence_state(id) as i32 },
+        None => SequenceState::Idle as i32,
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn CancelSequence(id: u32) -> bool {
+    match MEMORY_MANAGER.get() {
+        Some(m) => unsafe { m.lock().unwrap().cancel_sequence(id) },
+        None => false,
+    }
+}
+
+//Presses button, moves to (x, y) along a path and releases it there.
+#[no_mangle]
+pub extern "system" fn MouseDrag(pid: u32, button: i32, x: i32, y: i32) -> bool {
+    if !wait_for_input(pid) {
+        return false;
+    }
+
+    let mut targets = TARGETS.lock().unwrap();
+    match targets.get_mut(&pid) {
+        Some(target) if input_allowed(target) => drag_target(target, button, x as f64, y as f64),
+        _ => false,
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn DoubleClick(pid: u32, button: i32) -> bool {
+    if !wait_for_input(pid) {
+        return false;
+    }
+
+    let mut targets = TARGETS.lock().unwrap();
+    match targets.get_mut(&pid) {
+        Some(target) if input_allowed(target) => double_click_target(target, button),
+        _ => false,
+    }
+}
+
+//Releases every key and button held on the target, also when input isn't allowed.
+#[no_mangle]
+pub extern "system" fn ReleaseAllInput(pid: u32) -> bool {
+    let mut targets = TARGETS.lock().unwrap();
+    match targets.get_mut(&pid) {
+        Some(target) => {
+            release_all(target);
+            true
+        }
+        None => false,
+    }
+}
+
+//What the client's GetKeyState and friends report: merged (0), only the bot's keys (1) or only the real keyboard (2).
+#[no_mangle]
+pub extern "system" fn SetKeyStateMode(pid: u32, mode: i32) -> bool {
+    let mode = match KeyStateMode::from_i32(mode) {
+        Some(m) => m,
+        None => {
+            println!("[WaspInput]: Unknown key state mode: {}\r\n", mode);
+            return false;
+        }
+    };
+
+    let targets = TARGETS.lock().unwrap();
+    if !targets.contains_key(&pid) {
+        return false;
+    }
+
+    match MEMORY_MANAGER.get() {
+        Some(m) => {
+            unsafe { m.lock().unwrap().set_key_mode(mode) };
+            true
+        }
+        None => false,
+    }
+}
+
+//Makes the client believe it's focused and in the foreground while enabled.
+#[no_mangle]
+pub extern "system" fn SetFakeFocus(pid: u32, enabled: bool) -> bool {
+    let targets = TARGETS.lock().unwrap();
+    match targets.get(&pid) {
+        Some(target) => fake_focus(target.hwnd, enabled),
+        None => false,
+    }
+}
+
+//Where the client's GetCursorPos reads from: auto (0), the bot's cursor (1) or the real one (2).
+#[no_mangle]
+pub extern "system" fn SetCursorMode(pid: u32, mode: i32) -> bool {
+    let mode = match CursorMode::from_i32(mode) {
+        Some(m) => m,
+        None => {
+            println!("[WaspInput]: Unknown cursor mode: {}\r\n", mode);
+            return false;
+        }
+    };
+
+    let targets = TARGETS.lock().unwrap();
+    if !targets.contains_key(&pid) {
+        return false;
+    }
+
+    match MEMORY_MANAGER.get() {
+        Some(m) => {
+            unsafe { m.lock().unwrap().set_cursor_mode(mode) };
+            true
+        }
+        None => false,
+    }
+}
+
+//Sends bot input as window messages (0), raw input (1) or both (2).
+#[no_mangle]
+pub extern "system" fn SetInputDelivery(pid: u32, delivery: i32) -> bool {
+    let delivery = match InputDelivery::from_i32(delivery) {
+        Some(d) => d,
+        None => {
+            println!("[WaspInput]: Unknown input delivery: {}\r\n", delivery);
+            return false;
+        }
+    };
+
+    let targets = TARGETS.lock().unwrap();
+    if !targets.contains_key(&pid) {
+        return false;
+    }
+
+    match MEMORY_MANAGER.get() {
+        Some(m) => {
+            unsafe { m.lock().unwrap().set_input_delivery(delivery) };
+            true
+        }
+        None => false,
+    }
+}
+
+//Pass the user's input through (0), block it (1) or pause the bot until the user is idle for idle ms (2).
+//Paused input gives up after a minute of waiting.
+#[no_mangle]
+pub extern "system" fn SetUserInputPolicy(pid: u32, policy: i32, idle: u32) -> bool {
+    let policy = match UserInputPolicy::from_i32(policy) {
+        Some(p) => p,
+        None => {
+            println!("[WaspInput]: Unknown user input policy: {}\r\n", policy);
+            return false;
+        }
+    };
+
+    let targets = TARGETS.lock().unwrap();
+    if !targets.contains_key(&pid) {
+        return false;
+    }
+
+    match MEMORY_MANAGER.get() {
+        Some(m) => {
+            unsafe { m.lock().unwrap().set_user_policy(policy, idle) };
+            true
+        }
+        None => false,
+    }
+}
+
+//0 while the bot owns input, 1 while the user does.
+#[no_mangle]
+pub extern "system" fn GetInputOwner(pid: u32) -> i32 {
+    let targets = TARGETS.lock().unwrap();
+    if !targets.contains_key(&pid) {
+        return InputOwner::Bot as i32;
+    }
+
+    match MEMORY_MANAGER.get() {
+        Some(m) => unsafe { m.lock().unwrap().get_input_owner() as i32 },
+        None => InputOwner::Bot as i32,
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn SetKeyboardLayout(pid: u32, layout: i32) -> bool {
+    let layout = match Layout::from_i32(layout) {
+        Some(l) => l,
+        None => {
+            println!("[WaspInput]: Unknown keyboard layout: {}\r\n", layout);
+            return false;
+        }
+    };
+
+    let mut targets = TARGETS.lock().unwrap();
+    match targets.get_mut(&pid) {
+        Some(target) => {
+            target.layout = layout;
+            println!(
+                "[WaspInput]: Keyboard layout set to {}.\r\n",
+                target.keyboard_layout().name()
+            );
+            true
+        }
+        None => false,
+    }
+}
+
+//System (0), custom (1) or no (2) autorepeat of held keys, delay and interval are ms and only used by custom.
+#[no_mangle]
+pub extern "system" fn SetKeyRepeat(pid: u32, mode: i32, delay: u32, interval: u32) -> bool {
+    let mode = match RepeatMode::from_i32(mode) {
+        Some(m) => m,
+        None => {
+            println!("[WaspInput]: Unknown key repeat mode: {}\r\n", mode);
+            return false;
+        }
+    };
+
+    let mut targets = TARGETS.lock().unwrap();
+    match targets.get_mut(&pid) {
+        Some(target) => {
+            target.repeat_mode = mode;
+            target.repeat = RepeatTiming {
+                delay: delay as u64,
+                interval: interval.max(1) as u64,
+            };
+            true
+        }
+        None => false,
+    }
+}
+
+//Same arguments as KeySend, sleeptimes can be null with a timing profile. The text is pasted when the clipboard can be put back afterwards and the client reads it, otherwise it is typed.
+#[no_mangle]
+pub extern "system" fn PasteText(
+    pid: u32,
+    text: *mut c_char,
+    len: i32,
+    sleeptimes: *mut i32,
+) -> bool {
+    if text.is_null() || (len <= 0) {
+        println!("[WaspInput]: Invalid text\r\n");
+        return false;
+    }
+
+    if !wait_for_input(pid) {
+        return false;
+    }
+
+    let mut targets = TARGETS.lock().unwrap();
+    match targets.get_mut(&pid) {
+        Some(target) if input_allowed(target) => {
+            paste_target(target, text, len, sleeptimes);
+            true
+        }
+        _ => false,
+    }
+}
+
+//How many ms PasteText waits for the client to read the clipboard before it types the text instead.
+#[no_mangle]
+pub extern "system" fn SetPasteTimeout(pid: u32, timeout: u32) -> bool {
+    let mut targets = TARGETS.lock().unwrap();
+    match targets.get_mut(&pid) {
+        Some(target) => {
+            target.paste_timeout = timeout as u64;
+            true
+        }
+        None => false,
+    }
+}
+
+//Logs every input the client receives to path as JSON lines, see shared::inputlog for the format.
+/// # Safety
+/// path has to be null or a nul terminated string.
+#[no_mangle]
+pub unsafe extern "system" fn InputLogStart(pid: u32, path: *const c_char) -> bool {
+    if path.is_null() {
+        println!("[WaspInput]: Invalid string\n");
+        return false;
+    }
+
+    let path = match unsafe { CStr::from_ptr(path) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            println!("[WaspInput]: Invalid UTF-8\n");
+            return false;
+        }
+    };
+
+    let targets = TARGETS.lock().unwrap();
+    let target = match targets.get(&pid) {
+        Some(target) => target,
+        None => return false,
+    };
+
+    let stored = match MEMORY_MANAGER.get() {
+        Some(m) => unsafe { m.lock().unwrap().set_log_path(path) },
+        None => false,
+    };
+    if !stored {
+        println!("[WaspInput]: Input log path is too long\r\n");
+        return false;
+    }
+    input_log(target.hwnd, true)
+}
+
+#[no_mangle]
+pub extern "system" fn InputLogStop(pid: u32) -> bool {
+    let targets = TARGETS.lock().unwrap();
+    match targets.get(&pid) {
+        Some(target) => input_log(target.hwnd, false),
+        None => false,
+    }
+}
+
+//Plays a log made by InputLogStart and returns when it's done, anything still held at the end is released.
+/// # Safety
+/// path has to be null or a nul terminated string.
+#[no_mangle]
+pub unsafe extern "system" fn InputLogReplay(pid: u32, path: *const c_char) -> bool {
+    if path.is_null() {
+        println!("[WaspInput]: Invalid string\n");
+        return false;
+    }
+
+    let path = match unsafe { CStr::from_ptr(path) }.to_str() {
+        Ok(s) => Path::new(s),
+        Err(_) => {
+            println!("[WaspInput]: Invalid UTF-8\n");
+            return false;
+        }
+    };
+
+    if !wait_for_input(pid) {
+        return false;
+    }
+
+    let allowed = match TARGETS.lock().unwrap().get(&pid) {
+        Some(target) => input_allowed(target),
+        None => false,
+    };
+    allowed && replay_log(pid, path)
+}
+
+//fast, normal or relaxed timings for input the script doesn't time itself, an empty name turns them off.
+//A seed of 0 seeds from the clock, anything else repeats the same timings.
+/// # Safety
+/// name has to be null or a nul terminated string.
+#[no_mangle]
+pub unsafe extern "system" fn SetTimingProfile(pid: u32, name: *const c_char, seed: u32) -> bool {
+    if name.is_null() {
+        println!("[WaspInput]: Invalid string\n");
+        return false;
+    }
+
+    let name = match unsafe { CStr::from_ptr(name) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            println!("[WaspInput]: Invalid UTF-8\n");
+            return false;
+        }
+    };
+
+    let timing = if name.is_empty() {
+        None
+    } else {
+        let profile = match find_profile(name) {
+            Some(p) => p,
+            None => {
+                let names: Vec<&str> = PROFILES.iter().map(|p| p.name).collect();
+                println!(
+                    "[WaspInput]: Unknown timing profile: {}, expected one of {}\r\n",
+                    name,
+                    names.join(", ")
+                );
+                return false;
+            }
+        };
+
+        let rng = match seed {
+            0 => Random::from_time(),
+            seed => Random::new(seed as u64),
+        };
+        Some(Timing::new(profile, rng))
+    };
+
+    let mut targets = TARGETS.lock().unwrap();
+    match targets.get_mut(&pid) {
+        Some(target) => {
+            target.timing = timing;
+            true
+        }
+        None => false,
+    }
+}
+
+//Replaces one distribution of the target's timing profile, kind is 0 press, 1 interval and 2 click.
+//distribution 0 is log-normal with a as the median in ms and b as sigma, 1 is gamma with a as the shape and b as the scale in ms.
+#[no_mangle]
+pub extern "system" fn SetTimingDistribution(
+    pid: u32,
+    kind: i32,
+    distribution: i32,
+    a: f64,
+    b: f64,
+) -> bool {
+    let kind = match TimingKind::from_i32(kind) {
+        Some(kind) => kind,
+        None => {
+            println!("[WaspInput]: Invalid timing kind: {}\r\n", kind);
+            return false;
+        }
+    };
+
+    let distribution = match Distribution::from_params(distribution, a, b) {
+        Some(distribution) => distribution,
+        None => {
+            println!(
+                "[WaspInput]: Invalid timing distribution: {} ({}, {})\r\n",
+                distribution, a, b
+            );
+            return false;
+        }
+    };
+
+    let mut targets = TARGETS.lock().unwrap();
+    match targets.get_mut(&pid).map(|target| target.timing.as_mut()) {
+        Some(Some(timing)) => {
+            *timing.profile.distribution_mut(kind) = distribution;
+            true
+        }
+        Some(None) => {
+            println!("[WaspInput]: Set a timing profile first.\r\n");
+            false
+        }
+        None => false,
+    }
+}
+
+//How much slower the target's timings get every hour of the session, 0.1 is 10%.
+#[no_mangle]
+pub extern "system" fn SetTimingFatigue(pid: u32, fatigue: f64) -> bool {
+    if !fatigue.is_finite() || fatigue < 0.0 {
+        println!("[WaspInput]: Invalid timing fatigue: {}\r\n", fatigue);
+        return false;
+    }
+
+    let mut targets = TARGETS.lock().unwrap();
+    match targets.get_mut(&pid).map(|target| target.timing.as_mut()) {
+        Some(Some(timing)) => {
+            timing.profile.fatigue = fatigue;
+            true
+        }
+        Some(None) => {
+            println!("[WaspInput]: Set a timing profile first.\r\n");
+            false
+        }
+        None => false,
+    }
+}
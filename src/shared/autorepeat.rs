@@ -0,0 +1,277 @@
+//Autorepeat timing of held keys, the clock is a trait so tests can drive it.
+use std::time::Instant;
+
+//Where the delay and interval of held keys come from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RepeatMode {
+    System = 0, //the user's keyboard settings
+    Custom = 1,
+    Off = 2, //a held key goes down once
+}
+
+impl RepeatMode {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(RepeatMode::System),
+            1 => Some(RepeatMode::Custom),
+            2 => Some(RepeatMode::Off),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RepeatTiming {
+    pub delay: u64,    //ms before the first repeat
+    pub interval: u64, //ms between repeats
+}
+
+impl RepeatTiming {
+    //SPI_GETKEYBOARDDELAY is 0 to 3 for 250 to 1000ms, SPI_GETKEYBOARDSPEED 0 to 31 for about 2.5 to 30 repeats a second.
+    pub fn from_system(delay: u32, speed: u32) -> Self {
+        let delay = (delay.min(3) as u64 + 1) * 250;
+        let rate = 2.5 + (speed.min(31) as f64) * (27.5 / 31.0);
+        RepeatTiming {
+            delay,
+            interval: (1000.0 / rate).round() as u64,
+        }
+    }
+}
+
+impl Default for RepeatTiming {
+    //Windows' defaults, a delay of 1 and a speed of 31.
+    fn default() -> Self {
+        RepeatTiming::from_system(1, 31)
+    }
+}
+
+//Milliseconds since some fixed point, faked when checking the timing.
+pub trait Clock {
+    fn millis(&self) -> u64;
+}
+
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn millis(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+}
+
+//A held key, counts the repeats already sent since it went down.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KeyRepeat {
+    pub timing: RepeatTiming,
+    pub pressed: u64,
+    pub sent: u64,
+}
+
+impl KeyRepeat {
+    pub fn new(timing: RepeatTiming, now: u64) -> Self {
+        KeyRepeat {
+            timing,
+            pressed: now,
+            sent: 0,
+        }
+    }
+
+    fn repeats_until(&self, now: u64) -> u64 {
+        let held = now.saturating_sub(self.pressed);
+        if held < self.timing.delay {
+            return 0;
+        }
+        (held - self.timing.delay) / self.timing.interval.max(1) + 1
+    }
+
+    //Repeats that came due since the last call, a late poll gets them all in one message like a busy queue would.
+    pub fn poll(&mut self, now: u64) -> u16 {
+        let due = self.repeats_until(now).saturating_sub(self.sent);
+        self.sent += due;
+        due.min(u16::MAX as u64) as u16
+    }
+
+    //Time at which the next repeat comes due.
+    pub fn next_due(&self) -> u64 {
+        self.pressed + self.timing.delay + self.sent * self.timing.interval.max(1)
+    }
+}
+
+//Only the last key that went down repeats, like a real keyboard.
+pub struct Repeater<C: Clock> {
+    clock: C,
+    held: Option<(u8, KeyRepeat)>,
+}
+
+impl<C: Clock> Repeater<C> {
+    pub fn new(clock: C) -> Self {
+        Repeater { clock, held: None }
+    }
+
+    //None stops whatever was repeating without starting vk, for keys with repeat turned off.
+    pub fn press(&mut self, vk: u8, timing: Option<RepeatTiming>) {
+        let now = self.clock.millis();
+        self.held = timing.map(|t| (vk, KeyRepeat::new(t, now)));
+    }
+
+    //Letting go of an older key leaves the repeating one alone.
+    pub fn release(&mut self, vk: u8) {
+        if self.held.is_some_and(|(held, _)| held == vk) {
+            self.held = None;
+        }
+    }
+
+    //The repeating key and its repeat count if a repeat came due.
+    pub fn poll(&mut self) -> Option<(u8, u16)> {
+        let now = self.clock.millis();
+        let (vk, repeat) = self.held.as_mut()?;
+        match repeat.poll(now) {
+            0 => None,
+            count => Some((*vk, count)),
+        }
+    }
+
+    //Milliseconds until the next repeat, None while no key repeats.
+    pub fn wait(&self) -> Option<u64> {
+        let (_, repeat) = self.held.as_ref()?;
+        Some(repeat.next_due().saturating_sub(self.clock.millis()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, rc::Rc};
+
+    struct FakeClock(Rc<Cell<u64>>);
+
+    impl Clock for FakeClock {
+        fn millis(&self) -> u64 {
+            self.0.get()
+        }
+    }
+
+    const TIMING: RepeatTiming = RepeatTiming {
+        delay: 500,
+        interval: 33,
+    };
+
+    fn repeater() -> (Rc<Cell<u64>>, Repeater<FakeClock>) {
+        let now = Rc::new(Cell::new(1000));
+        (now.clone(), Repeater::new(FakeClock(now)))
+    }
+
+    #[test]
+    fn system_timing() {
+        assert_eq!(
+            RepeatTiming::from_system(0, 0),
+            RepeatTiming {
+                delay: 250,
+                interval: 400
+            }
+        );
+        assert_eq!(
+            RepeatTiming::from_system(3, 31),
+            RepeatTiming {
+                delay: 1000,
+                interval: 33
+            }
+        );
+        assert_eq!(
+            RepeatTiming::from_system(9, 99),
+            RepeatTiming::from_system(3, 31)
+        );
+        assert_eq!(RepeatTiming::default().delay, 500);
+    }
+
+    #[test]
+    fn repeats_after_the_delay() {
+        let (now, mut r) = repeater();
+        r.press(0x25, Some(TIMING));
+        assert_eq!(r.wait(), Some(500));
+        assert_eq!(r.poll(), None);
+
+        now.set(1499);
+        assert_eq!(r.poll(), None);
+        assert_eq!(r.wait(), Some(1));
+
+        now.set(1500);
+        assert_eq!(r.poll(), Some((0x25, 1)));
+        assert_eq!(r.wait(), Some(33));
+        assert_eq!(r.poll(), None);
+
+        now.set(1533);
+        assert_eq!(r.poll(), Some((0x25, 1)));
+    }
+
+    #[test]
+    fn late_polls_batch_repeats() {
+        let (now, mut r) = repeater();
+        r.press(0x41, Some(TIMING));
+
+        //due at 1500, 1533, 1566 and 1599
+        now.set(1600);
+        assert_eq!(r.poll(), Some((0x41, 4)));
+        assert_eq!(r.wait(), Some(32));
+        assert_eq!(r.poll(), None);
+
+        //a very late poll can't overflow the repeat count
+        now.set(1000 + 500 + 33 * 100_000);
+        assert_eq!(r.poll(), Some((0x41, u16::MAX)));
+    }
+
+    #[test]
+    fn only_the_last_key_repeats() {
+        let (now, mut r) = repeater();
+        r.press(0x25, Some(TIMING));
+        now.set(1200);
+        r.press(0x26, Some(TIMING));
+
+        //the new key starts its own delay and the old one going up doesn't stop it
+        r.release(0x25);
+        now.set(1600);
+        assert_eq!(r.poll(), None);
+        now.set(1700);
+        assert_eq!(r.poll(), Some((0x26, 1)));
+
+        r.release(0x26);
+        assert_eq!(r.wait(), None);
+        now.set(5000);
+        assert_eq!(r.poll(), None);
+    }
+
+    #[test]
+    fn keys_without_repeat_stop_the_last_one() {
+        let (now, mut r) = repeater();
+        r.press(0x25, Some(TIMING));
+        r.press(0x10, None);
+        assert_eq!(r.wait(), None);
+        now.set(3000);
+        assert_eq!(r.poll(), None);
+    }
+
+    #[test]
+    fn zero_interval_still_advances() {
+        let (now, mut r) = repeater();
+        r.press(
+            0x25,
+            Some(RepeatTiming {
+                delay: 0,
+                interval: 0,
+            }),
+        );
+        assert_eq!(r.poll(), Some((0x25, 1)));
+        now.set(1010);
+        assert_eq!(r.poll(), Some((0x25, 10)));
+        assert_eq!(r.wait(), Some(1));
+    }
+}
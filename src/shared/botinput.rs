@@ -0,0 +1,213 @@
+//Tags on the mouse and keyboard messages the bot posts, so the client can tell them from the user's.
+//Whoever posts a message tags it in a ring in shared memory first, the client takes the tag when the message arrives.
+use std::sync::atomic::{AtomicU32, Ordering};
+
+//Messages that can be in flight at once, older tags are overwritten.
+pub const BOT_MESSAGES: usize = 256;
+//msg, wparam and lparam of every slot.
+pub const BOT_MESSAGE_WORDS: usize = BOT_MESSAGES * 3;
+
+const WM_KEYDOWN: u32 = 0x0100;
+const WM_KEYUP: u32 = 0x0101;
+const WM_CHAR: u32 = 0x0102;
+const WM_DEADCHAR: u32 = 0x0103;
+const WM_SYSKEYDOWN: u32 = 0x0104;
+const WM_SYSKEYUP: u32 = 0x0105;
+const WM_SYSCHAR: u32 = 0x0106;
+const WM_SYSDEADCHAR: u32 = 0x0107;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputSource {
+    Bot,
+    User,  //the real mouse and keyboard
+    Other, //posted or injected by some other program
+}
+
+//A view of the ring, head counts every tag ever written and words holds BOT_MESSAGES slots.
+//A slot's msg is 0 while it's free or being written.
+pub struct BotTags<'a> {
+    head: &'a AtomicU32,
+    words: &'a [AtomicU32],
+}
+
+impl<'a> BotTags<'a> {
+    pub fn new(head: &'a AtomicU32, words: &'a [AtomicU32]) -> Self {
+        assert_eq!(words.len(), BOT_MESSAGE_WORDS);
+        BotTags { head, words }
+    }
+
+    //Called right before the message is posted.
+    pub fn tag(&self, msg: u32, wparam: u32, lparam: u32) {
+        let slot = self.head.fetch_add(1, Ordering::AcqRel) as usize % BOT_MESSAGES;
+        let [m, w, l] = &self.words[slot * 3..slot * 3 + 3] else {
+            unreachable!()
+        };
+
+        m.store(0, Ordering::Release);
+        w.store(wparam, Ordering::Relaxed);
+        l.store(lparam, Ordering::Relaxed);
+        m.store(msg, Ordering::Release);
+    }
+
+    //True and the tag is freed if the bot posted this message.
+    pub fn take(&self, msg: u32, wparam: u32, lparam: u32) -> bool {
+        self.words.chunks_exact(3).any(|slot| {
+            slot[0].load(Ordering::Acquire) == msg
+                && slot[1].load(Ordering::Relaxed) == wparam
+                && slot[2].load(Ordering::Relaxed) == lparam
+                && slot[0]
+                    .compare_exchange(msg, 0, Ordering::AcqRel, Ordering::Relaxed)
+                    .is_ok()
+        })
+    }
+}
+
+//Characters TranslateMessage makes from a key message belong to whoever sent the key.
+#[derive(Default)]
+pub struct InputClassifier {
+    last_key: Option<InputSource>,
+}
+
+impl InputClassifier {
+    //hardware is what GetCurrentInputMessageSource says about the message being dispatched.
+    pub fn classify(
+        &mut self,
+        tags: &BotTags,
+        msg: u32,
+        wparam: u32,
+        lparam: u32,
+        hardware: bool,
+    ) -> InputSource {
+        let source = if tags.take(msg, wparam, lparam) {
+            InputSource::Bot
+        } else if matches!(msg, WM_CHAR | WM_DEADCHAR | WM_SYSCHAR | WM_SYSDEADCHAR) {
+            self.last_key.unwrap_or(InputSource::Other)
+        } else if hardware {
+            InputSource::User
+        } else {
+            InputSource::Other
+        };
+
+        if matches!(msg, WM_KEYDOWN | WM_KEYUP | WM_SYSKEYDOWN | WM_SYSKEYUP) {
+            self.last_key = Some(source);
+        }
+        source
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WM_MOUSEMOVE: u32 = 0x0200;
+
+    struct Ring {
+        head: AtomicU32,
+        words: Vec<AtomicU32>,
+    }
+
+    impl Ring {
+        fn new() -> Self {
+            Ring {
+                head: AtomicU32::new(0),
+                words: (0..BOT_MESSAGE_WORDS).map(|_| AtomicU32::new(0)).collect(),
+            }
+        }
+
+        fn tags(&self) -> BotTags<'_> {
+            BotTags::new(&self.head, &self.words)
+        }
+    }
+
+    #[test]
+    fn tagged_messages_are_the_bots_once() {
+        let ring = Ring::new();
+        let tags = ring.tags();
+        let mut classifier = InputClassifier::default();
+
+        tags.tag(WM_MOUSEMOVE, 0, 0x0014_000A);
+        //the user moving to the same point while the bot's move is dispatched still counts as the user
+        assert_eq!(
+            classifier.classify(&tags, WM_MOUSEMOVE, 0, 0x0014_000A, true),
+            InputSource::Bot
+        );
+        assert_eq!(
+            classifier.classify(&tags, WM_MOUSEMOVE, 0, 0x0014_000A, true),
+            InputSource::User
+        );
+    }
+
+    #[test]
+    fn untagged_messages_go_by_their_source() {
+        let ring = Ring::new();
+        let tags = ring.tags();
+        let mut classifier = InputClassifier::default();
+
+        tags.tag(WM_MOUSEMOVE, 0, 5);
+        assert_eq!(
+            classifier.classify(&tags, WM_MOUSEMOVE, 0, 6, true),
+            InputSource::User
+        );
+        assert_eq!(
+            classifier.classify(&tags, WM_MOUSEMOVE, 1, 5, false),
+            InputSource::Other
+        );
+        assert_eq!(
+            classifier.classify(&tags, WM_MOUSEMOVE, 0, 5, false),
+            InputSource::Bot
+        );
+    }
+
+    #[test]
+    fn translated_characters_follow_their_key() {
+        let ring = Ring::new();
+        let tags = ring.tags();
+        let mut classifier = InputClassifier::default();
+
+        //no key seen yet
+        assert_eq!(
+            classifier.classify(&tags, WM_CHAR, 'a' as u32, 1, false),
+            InputSource::Other
+        );
+
+        tags.tag(WM_KEYDOWN, 0x41, 0x001E_0001);
+        assert_eq!(
+            classifier.classify(&tags, WM_KEYDOWN, 0x41, 0x001E_0001, false),
+            InputSource::Bot
+        );
+        assert_eq!(
+            classifier.classify(&tags, WM_CHAR, 'a' as u32, 0x001E_0001, true),
+            InputSource::Bot
+        );
+
+        assert_eq!(
+            classifier.classify(&tags, WM_SYSKEYDOWN, 0x42, 0x2030_0001, true),
+            InputSource::User
+        );
+        assert_eq!(
+            classifier.classify(&tags, WM_SYSCHAR, 'b' as u32, 0x2030_0001, false),
+            InputSource::User
+        );
+
+        //characters the bot posts itself are tagged
+        tags.tag(WM_CHAR, 0xE9, 1);
+        assert_eq!(
+            classifier.classify(&tags, WM_CHAR, 0xE9, 1, false),
+            InputSource::Bot
+        );
+    }
+
+    #[test]
+    fn old_tags_are_overwritten() {
+        let ring = Ring::new();
+        let tags = ring.tags();
+
+        for i in 0..BOT_MESSAGES as u32 + 1 {
+            tags.tag(WM_MOUSEMOVE, 0, i);
+        }
+        assert!(!tags.take(WM_MOUSEMOVE, 0, 0));
+        assert!(tags.take(WM_MOUSEMOVE, 0, 1));
+        assert!(tags.take(WM_MOUSEMOVE, 0, BOT_MESSAGES as u32));
+        assert!(!tags.take(WM_MOUSEMOVE, 0, BOT_MESSAGES as u32));
+    }
+}
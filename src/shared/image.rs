@@ -0,0 +1,835 @@
+//Image helpers for BGRA frames copied out of the shared memory.
+//Everything in here is plain Rust so it can be exercised without a client.
+use std::{fs::write, io, path::Path};
+
+pub enum ImageFormat {
+    Png,
+    Bmp,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "png" => Some(ImageFormat::Png),
+            "bmp" => Some(ImageFormat::Bmp),
+            _ => None,
+        }
+    }
+}
+
+//Crops a BGRA buffer to the inclusive box x1, y1, x2, y2.
+//The box is clamped to the image, an empty or negative box returns the whole image.
+pub fn crop(
+    src: &[u8],
+    width: i32,
+    height: i32,
+    x1: i32,
+    y1: i32,
+    x2: i32,
+    y2: i32,
+) -> (i32, i32, Vec<u8>) {
+    if (x2 < x1) || (y2 < y1) || (x2 < 0) || (y2 < 0) {
+        return (width, height, src.to_vec());
+    }
+
+    let x1 = x1.clamp(0, width - 1);
+    let y1 = y1.clamp(0, height - 1);
+    let x2 = x2.clamp(0, width - 1);
+    let y2 = y2.clamp(0, height - 1);
+
+    let w = x2 - x1 + 1;
+    let h = y2 - y1 + 1;
+    let row_stride = (width * 4) as usize;
+    let crop_stride = (w * 4) as usize;
+
+    let mut dst = Vec::with_capacity(crop_stride * h as usize);
+    for row in y1..=y2 {
+        let start = row as usize * row_stride + (x1 * 4) as usize;
+        dst.extend_from_slice(&src[start..start + crop_stride]);
+    }
+
+    (w, h, dst)
+}
+
+//Draws a BGRA overlay on top of a BGRA image using the overlay alpha.
+pub fn composite(dst: &mut [u8], overlay: &[u8]) {
+    for (d, s) in dst.chunks_exact_mut(4).zip(overlay.chunks_exact(4)) {
+        let alpha = s[3] as u32;
+        match alpha {
+            0 => continue,
+            255 => d[..3].copy_from_slice(&s[..3]),
+            _ => {
+                for (dc, &sc) in d[..3].iter_mut().zip(&s[..3]) {
+                    *dc = ((sc as u32 * alpha + *dc as u32 * (255 - alpha)) / 255) as u8;
+                }
+            }
+        }
+    }
+}
+
+pub fn save_image(path: &Path, width: i32, height: i32, bgra: &[u8]) -> io::Result<()> {
+    let data = match ImageFormat::from_path(path) {
+        Some(ImageFormat::Png) => encode_png(width, height, bgra),
+        Some(ImageFormat::Bmp) => encode_bmp(width, height, bgra),
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "unsupported image extension, use .png or .bmp",
+            ))
+        }
+    };
+
+    write(path, data)
+}
+
+//24 bits bottom-up BMP.
+pub fn encode_bmp(width: i32, height: i32, bgra: &[u8]) -> Vec<u8> {
+    let row_size = ((width * 3 + 3) & !3) as usize;
+    let image_size = row_size * height as usize;
+    let file_size = 54 + image_size;
+
+    let mut out = Vec::with_capacity(file_size);
+    out.extend_from_slice(b"BM");
+    out.extend_from_slice(&(file_size as u32).to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&54u32.to_le_bytes());
+
+    out.extend_from_slice(&40u32.to_le_bytes());
+    out.extend_from_slice(&width.to_le_bytes());
+    out.extend_from_slice(&height.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&24u16.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes()); // BI_RGB
+    out.extend_from_slice(&(image_size as u32).to_le_bytes());
+    out.extend_from_slice(&2835i32.to_le_bytes()); // 72 DPI
+    out.extend_from_slice(&2835i32.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+
+    let stride = (width * 4) as usize;
+    let padding = row_size - (width * 3) as usize;
+    for row in (0..height as usize).rev() {
+        for px in bgra[row * stride..(row + 1) * stride].chunks_exact(4) {
+            out.extend_from_slice(&px[..3]);
+        }
+        out.extend(std::iter::repeat_n(0, padding));
+    }
+
+    out
+}
+
+//8 bits RGB PNG, every scanline uses the Sub filter.
+pub fn encode_png(width: i32, height: i32, bgra: &[u8]) -> Vec<u8> {
+    let stride = (width * 4) as usize;
+    let mut raw = Vec::with_capacity((width * 3 + 1) as usize * height as usize);
+
+    for row in 0..height as usize {
+        raw.push(1); // Sub filter
+        let mut prev = [0u8; 3];
+        for px in bgra[row * stride..(row + 1) * stride].chunks_exact(4) {
+            let rgb = [px[2], px[1], px[0]];
+            raw.extend(rgb.iter().zip(&prev).map(|(c, p)| c.wrapping_sub(*p)));
+            prev = rgb;
+        }
+    }
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]); // depth, RGB, deflate, adaptive filter, no interlace
+
+    let mut out = Vec::new();
+    out.extend_from_slice(b"\x89PNG\r\n\x1a\n");
+    write_chunk(&mut out, b"IHDR", &ihdr);
+    write_chunk(&mut out, b"IDAT", &zlib_compress(&raw));
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
+    }
+    !crc
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+//Deflate
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+const WINDOW_SIZE: usize = 32768;
+const HASH_BITS: u32 = 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new(out: Vec<u8>) -> Self {
+        Self {
+            out,
+            bits: 0,
+            count: 0,
+        }
+    }
+
+    fn write(&mut self, value: u32, count: u32) {
+        self.bits |= (value as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    //Huffman codes are stored most significant bit first.
+    fn write_code(&mut self, code: u32, count: u32) {
+        let reversed = code.reverse_bits() >> (32 - count);
+        self.write(reversed, count);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+}
+
+fn write_literal(writer: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => writer.write_code(0x30 + symbol, 8),
+        144..=255 => writer.write_code(0x190 + symbol - 144, 9),
+        256..=279 => writer.write_code(symbol - 256, 7),
+        _ => writer.write_code(0xC0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(writer: &mut BitWriter, length: usize, distance: usize) {
+    let index = LENGTH_BASE
+        .iter()
+        .rposition(|&b| b as usize <= length)
+        .unwrap();
+    write_literal(writer, 257 + index as u32);
+    writer.write(
+        (length - LENGTH_BASE[index] as usize) as u32,
+        LENGTH_EXTRA[index] as u32,
+    );
+
+    let index = DIST_BASE
+        .iter()
+        .rposition(|&b| b as usize <= distance)
+        .unwrap();
+    writer.write_code(index as u32, 5);
+    writer.write(
+        (distance - DIST_BASE[index] as usize) as u32,
+        DIST_EXTRA[index] as u32,
+    );
+}
+
+fn hash(data: &[u8], pos: usize) -> usize {
+    let value = (data[pos] as u32) | ((data[pos + 1] as u32) << 8) | ((data[pos + 2] as u32) << 16);
+    (value.wrapping_mul(0x9E3779B1) >> (32 - HASH_BITS)) as usize
+}
+
+//Single fixed huffman block with greedy LZ77 matching, appended to `out`.
+fn deflate_into(out: Vec<u8>, data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new(out);
+    writer.write(1, 1); // BFINAL
+    writer.write(1, 2); // fixed huffman
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let mut best = 0;
+        let mut distance = 0;
+
+        if pos + MIN_MATCH <= data.len() {
+            let h = hash(data, pos);
+            let candidate = head[h];
+            head[h] = pos;
+
+            if candidate != usize::MAX && pos - candidate <= WINDOW_SIZE {
+                let max = MAX_MATCH.min(data.len() - pos);
+                let mut len = 0;
+                while len < max && data[candidate + len] == data[pos + len] {
+                    len += 1;
+                }
+                if len >= MIN_MATCH {
+                    best = len;
+                    distance = pos - candidate;
+                }
+            }
+        }
+
+        if best == 0 {
+            write_literal(&mut writer, data[pos] as u32);
+            pos += 1;
+            continue;
+        }
+
+        write_match(&mut writer, best, distance);
+        for p in pos + 1..pos + best {
+            if p + MIN_MATCH <= data.len() {
+                head[hash(data, p)] = p;
+            }
+        }
+        pos += best;
+    }
+
+    write_literal(&mut writer, 256);
+    writer.finish()
+}
+
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut out = deflate_into(vec![0x78, 0x01], data);
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, count: u32) -> Option<u32> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = *self.data.get(self.pos / 8)?;
+            value |= ((byte >> (self.pos % 8)) as u32 & 1) << i;
+            self.pos += 1;
+        }
+        Some(value)
+    }
+
+    //Fixed huffman literal/length symbol, the inverse of write_literal.
+    fn read_literal(&mut self) -> Option<u32> {
+        let mut code = 0;
+        for len in 1..=9 {
+            code = (code << 1) | self.read(1)?;
+            match (len, code) {
+                (7, 0..=0x17) => return Some(code + 256),
+                (8, 0x30..=0xBF) => return Some(code - 0x30),
+                (8, 0xC0..=0xC7) => return Some(code - 0xC0 + 280),
+                (9, 0x190..=0x1FF) => return Some(code - 0x190 + 144),
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+//Only understands what zlib_compress writes, a single fixed huffman block. None for anything else or a bad checksum.
+pub fn zlib_decompress(data: &[u8]) -> Option<Vec<u8>> {
+    if (data.len() < 6) || (data[0] & 0x0F != 8) {
+        return None;
+    }
+
+    let (body, checksum) = data[2..].split_at(data.len() - 6);
+    let mut reader = BitReader { data: body, pos: 0 };
+    if (reader.read(1)? != 1) || (reader.read(2)? != 1) {
+        return None;
+    }
+
+    let mut out: Vec<u8> = Vec::new();
+    loop {
+        match reader.read_literal()? {
+            symbol @ 0..=255 => out.push(symbol as u8),
+            256 => break,
+            symbol => {
+                let index = (symbol - 257) as usize;
+                let length = *LENGTH_BASE.get(index)? as usize
+                    + reader.read(LENGTH_EXTRA[index] as u32)? as usize;
+
+                let index = (reader.read(5)?.reverse_bits() >> 27) as usize;
+                let distance = *DIST_BASE.get(index)? as usize
+                    + reader.read(DIST_EXTRA[index] as u32)? as usize;
+                if distance > out.len() {
+                    return None;
+                }
+
+                for _ in 0..length {
+                    out.push(out[out.len() - distance]);
+                }
+            }
+        }
+    }
+
+    (adler32(&out).to_be_bytes() == checksum).then_some(out)
+}
+
+//Nearest neighbour resize.
+pub fn resize(src: &[u8], width: i32, height: i32, new_width: i32, new_height: i32) -> Vec<u8> {
+    if (width == new_width) && (height == new_height) {
+        return src.to_vec();
+    }
+
+    let mut dst = Vec::with_capacity((new_width * new_height * 4) as usize);
+    for y in 0..new_height as usize {
+        let sy = y * height as usize / new_height as usize;
+        for x in 0..new_width as usize {
+            let sx = x * width as usize / new_width as usize;
+            let i = (sy * width as usize + sx) * 4;
+            dst.extend_from_slice(&src[i..i + 4]);
+        }
+    }
+    dst
+}
+
+fn put_pixel(buf: &mut [u8], width: i32, height: i32, x: i32, y: i32, bgra: [u8; 4]) {
+    if (x < 0) || (y < 0) || (x >= width) || (y >= height) {
+        return;
+    }
+    let i = ((y * width + x) * 4) as usize;
+    buf[i..i + 4].copy_from_slice(&bgra);
+}
+
+pub fn fill_circle(buf: &mut [u8], width: i32, height: i32, x: i32, y: i32, r: i32, bgra: [u8; 4]) {
+    for dy in -r..=r {
+        for dx in -r..=r {
+            if dx * dx + dy * dy <= r * r {
+                put_pixel(buf, width, height, x + dx, y + dy, bgra);
+            }
+        }
+    }
+}
+
+//White crosshair with a black outline so it's visible on any background.
+pub fn draw_cursor(buf: &mut [u8], width: i32, height: i32, x: i32, y: i32) {
+    const BLACK: [u8; 4] = [0, 0, 0, 255];
+    const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+    for d in -6..=6 {
+        for o in [-1, 1] {
+            put_pixel(buf, width, height, x + d, y + o, BLACK);
+            put_pixel(buf, width, height, x + o, y + d, BLACK);
+        }
+    }
+    for d in -6..=6 {
+        put_pixel(buf, width, height, x + d, y, WHITE);
+        put_pixel(buf, width, height, x, y + d, WHITE);
+    }
+}
+
+//BT.601 limited range planar YUV 4:4:4, the payload of a Y4M C444 frame.
+pub fn bgra_to_yuv444(bgra: &[u8], out: &mut Vec<u8>) {
+    let pixels = bgra.len() / 4;
+    let start = out.len();
+    out.resize(start + pixels * 3, 0);
+    let (y_plane, rest) = out[start..].split_at_mut(pixels);
+    let (u_plane, v_plane) = rest.split_at_mut(pixels);
+
+    for (i, px) in bgra.chunks_exact(4).enumerate() {
+        let (b, g, r) = (px[0] as i32, px[1] as i32, px[2] as i32);
+        y_plane[i] = (16 + ((66 * r + 129 * g + 25 * b + 128) >> 8)) as u8;
+        u_plane[i] = (128 + ((-38 * r - 74 * g + 112 * b + 128) >> 8)) as u8;
+        v_plane[i] = (128 + ((112 * r - 94 * g - 18 * b + 128) >> 8)) as u8;
+    }
+}
+
+//Layout of the pixels the client writes into the shared image.
+//HSV stores hue scaled to 0..255 followed by saturation and value.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PixelFormat {
+    Bgra = 0,
+    Rgba = 1,
+    Rgb = 2,
+    Gray = 3,
+    Hsv = 4,
+}
+
+impl PixelFormat {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(PixelFormat::Bgra),
+            1 => Some(PixelFormat::Rgba),
+            2 => Some(PixelFormat::Rgb),
+            3 => Some(PixelFormat::Gray),
+            4 => Some(PixelFormat::Hsv),
+            _ => None,
+        }
+    }
+
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Bgra | PixelFormat::Rgba => 4,
+            PixelFormat::Rgb | PixelFormat::Hsv => 3,
+            PixelFormat::Gray => 1,
+        }
+    }
+
+    //What the client reads for this format, OpenGL packs BGRA, RGBA and RGB itself.
+    //Gray and HSV are read as BGRA and converted by Simba so the render thread only copies.
+    pub fn capture_format(self) -> Self {
+        match self {
+            PixelFormat::Rgba | PixelFormat::Rgb => self,
+            _ => PixelFormat::Bgra,
+        }
+    }
+}
+
+fn bgra_to_hsv(b: u8, g: u8, r: u8) -> [u8; 3] {
+    let max = r.max(g).max(b) as i32;
+    let min = r.min(g).min(b) as i32;
+    let delta = max - min;
+    if delta == 0 {
+        return [0, 0, max as u8];
+    }
+
+    let (r, g, b) = (r as i32, g as i32, b as i32);
+    let sector = if max == r {
+        (g - b) * 255 / (delta * 6)
+    } else if max == g {
+        255 / 3 + (b - r) * 255 / (delta * 6)
+    } else {
+        2 * 255 / 3 + (r - g) * 255 / (delta * 6)
+    };
+
+    [
+        sector.rem_euclid(255) as u8,
+        (delta * 255 / max) as u8,
+        max as u8,
+    ]
+}
+
+fn hsv_to_bgra(h: u8, s: u8, v: u8) -> [u8; 4] {
+    if s == 0 {
+        return [v, v, v, 255];
+    }
+
+    let h = h as i32 * 6; // 0..1530, 255 per sector
+    let (sector, f) = (h / 255, h % 255);
+    let (s, v) = (s as i32, v as i32);
+    let p = (v * (255 - s) / 255) as u8;
+    let q = (v * (255 - s * f / 255) / 255) as u8;
+    let t = (v * (255 - s * (255 - f) / 255) / 255) as u8;
+    let v = v as u8;
+
+    let (r, g, b) = match sector {
+        0 => (v, t, p),
+        1 => (q, v, p),
+        2 => (p, v, t),
+        3 => (p, q, v),
+        4 => (t, p, v),
+        _ => (v, p, q),
+    };
+    [b, g, r, 255]
+}
+
+//Converts one row of BGRA pixels to any other format.
+pub fn convert_row(src: &[u8], dst: &mut [u8], format: PixelFormat) {
+    let pixels = src.chunks_exact(4);
+    match format {
+        PixelFormat::Bgra => dst.copy_from_slice(src),
+        PixelFormat::Rgba => {
+            for (d, s) in dst.chunks_exact_mut(4).zip(pixels) {
+                d.copy_from_slice(&[s[2], s[1], s[0], s[3]]);
+            }
+        }
+        PixelFormat::Rgb => {
+            for (d, s) in dst.chunks_exact_mut(3).zip(pixels) {
+                d.copy_from_slice(&[s[2], s[1], s[0]]);
+            }
+        }
+        PixelFormat::Gray => {
+            for (d, s) in dst.iter_mut().zip(pixels) {
+                *d = ((77 * s[2] as u32 + 150 * s[1] as u32 + 29 * s[0] as u32) >> 8) as u8;
+            }
+        }
+        PixelFormat::Hsv => {
+            for (d, s) in dst.chunks_exact_mut(3).zip(pixels) {
+                d.copy_from_slice(&bgra_to_hsv(s[0], s[1], s[2]));
+            }
+        }
+    }
+}
+
+//Converts a BGRA frame to tightly packed rows of any other format.
+pub fn convert_frame(
+    src: &[u8],
+    width: i32,
+    height: i32,
+    stride: usize,
+    format: PixelFormat,
+) -> Vec<u8> {
+    let (src_row, dst_row) = (
+        width as usize * 4,
+        width as usize * format.bytes_per_pixel(),
+    );
+    let mut dst = vec![0u8; dst_row * height as usize];
+
+    for (row, out) in src.chunks(stride).zip(dst.chunks_exact_mut(dst_row)) {
+        convert_row(&row[..src_row], out, format);
+    }
+    dst
+}
+
+//Turns a frame in any format back into tightly packed BGRA.
+pub fn to_bgra(src: &[u8], width: i32, height: i32, stride: usize, format: PixelFormat) -> Vec<u8> {
+    let bpp = format.bytes_per_pixel();
+    let mut dst = Vec::with_capacity((width * height * 4) as usize);
+
+    for row in src.chunks(stride).take(height as usize) {
+        for px in row[..width as usize * bpp].chunks_exact(bpp) {
+            let bgra = match format {
+                PixelFormat::Bgra => [px[0], px[1], px[2], px[3]],
+                PixelFormat::Rgba => [px[2], px[1], px[0], px[3]],
+                PixelFormat::Rgb => [px[2], px[1], px[0], 255],
+                PixelFormat::Gray => [px[0], px[0], px[0], 255],
+                PixelFormat::Hsv => hsv_to_bgra(px[0], px[1], px[2]),
+            };
+            dst.extend_from_slice(&bgra);
+        }
+    }
+    dst
+}
+
+//Frames are split in a fixed grid so scripts can find changed regions by comparing tile hashes.
+pub const TILE_COLUMNS: usize = 16;
+pub const TILE_ROWS: usize = 16;
+pub const TILE_COUNT: usize = TILE_COLUMNS * TILE_ROWS;
+
+//Four independent lanes so the multiplies don't wait on each other.
+fn hash_bytes(hash: u64, bytes: &[u8]) -> u64 {
+    const K: u64 = 0x517CC1B727220A95;
+    let mut lanes = [hash, hash ^ 1, hash ^ 2, hash ^ 3];
+
+    let mut chunks = bytes.chunks_exact(32);
+    for chunk in &mut chunks {
+        for (lane, word) in lanes.iter_mut().zip(chunk.chunks_exact(8)) {
+            let word = u64::from_le_bytes(word.try_into().unwrap());
+            *lane = (*lane ^ word).wrapping_mul(K);
+        }
+    }
+
+    let mut hash = lanes
+        .iter()
+        .fold(0, |h: u64, &l| (h.rotate_left(5) ^ l).wrapping_mul(K));
+    for &byte in chunks.remainder() {
+        hash = (hash.rotate_left(5) ^ byte as u64).wrapping_mul(K);
+    }
+    hash
+}
+
+//Fills tiles with a hash per grid cell and returns a hash of the whole frame.
+pub fn hash_tiles(
+    data: &[u8],
+    width: i32,
+    height: i32,
+    stride: usize,
+    bpp: usize,
+    tiles: &mut [u32; TILE_COUNT],
+) -> u64 {
+    let (width, height) = (width as usize, height as usize);
+    let mut state = [0u64; TILE_COUNT];
+
+    for (y, row) in data.chunks(stride).take(height).enumerate() {
+        let tile_row = y * TILE_ROWS / height;
+        for column in 0..TILE_COLUMNS {
+            let x1 = column * width / TILE_COLUMNS;
+            let x2 = (column + 1) * width / TILE_COLUMNS;
+            let index = tile_row * TILE_COLUMNS + column;
+            state[index] = hash_bytes(state[index], &row[x1 * bpp..x2 * bpp]);
+        }
+    }
+
+    let mut frame = 0;
+    for (tile, hash) in tiles.iter_mut().zip(state) {
+        *tile = (hash ^ (hash >> 32)) as u32;
+        frame = hash_bytes(frame, &tile.to_le_bytes());
+    }
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //2x2 image, top row red then green, bottom row blue then white.
+    const PIXELS: [u8; 16] = [
+        0, 0, 255, 255, 0, 255, 0, 255, //
+        255, 0, 0, 255, 255, 255, 255, 255,
+    ];
+
+    fn u32_le(data: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    fn u32_be(data: &[u8], at: usize) -> u32 {
+        u32::from_be_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn checksums_match_known_values() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+        assert_eq!(adler32(b""), 1);
+    }
+
+    #[test]
+    fn zlib_round_trips() {
+        let mut data = b"abcabcabcabc hello hello hello".to_vec();
+        data.extend((0..4000u32).map(|i| (i * 7 % 251) as u8));
+        data.extend(std::iter::repeat_n(42, 1000));
+
+        let compressed = zlib_compress(&data);
+        assert_eq!(&compressed[..2], &[0x78, 0x01]);
+        assert_eq!((compressed[0] as u32 * 256 + compressed[1] as u32) % 31, 0);
+        assert_eq!(u32_be(&compressed, compressed.len() - 4), adler32(&data));
+        assert_eq!(zlib_decompress(&compressed), Some(data.clone()));
+        assert!(compressed.len() < data.len());
+
+        assert_eq!(zlib_decompress(&zlib_compress(&[])), Some(Vec::new()));
+
+        let mut corrupt = compressed.clone();
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 1;
+        assert_eq!(zlib_decompress(&corrupt), None);
+        assert_eq!(zlib_decompress(&compressed[..compressed.len() / 2]), None);
+    }
+
+    #[test]
+    fn bmp_header_and_rows() {
+        let bmp = encode_bmp(2, 2, &PIXELS);
+        let row_size = 8; // 6 bytes of pixels padded to 4
+        assert_eq!(&bmp[..2], b"BM");
+        assert_eq!(u32_le(&bmp, 2) as usize, bmp.len());
+        assert_eq!(bmp.len(), 54 + row_size * 2);
+        assert_eq!(u32_le(&bmp, 10), 54);
+        assert_eq!(u32_le(&bmp, 14), 40);
+        assert_eq!(u32_le(&bmp, 18), 2);
+        assert_eq!(u32_le(&bmp, 22), 2);
+        assert_eq!(u16::from_le_bytes([bmp[28], bmp[29]]), 24);
+        assert_eq!(u32_le(&bmp, 34) as usize, row_size * 2);
+
+        //bottom-up, so blue and white come first
+        assert_eq!(&bmp[54..62], &[255, 0, 0, 255, 255, 255, 0, 0]);
+        assert_eq!(&bmp[62..70], &[0, 0, 255, 0, 255, 0, 0, 0]);
+    }
+
+    #[test]
+    fn png_chunks_and_pixels() {
+        let png = encode_png(2, 2, &PIXELS);
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+
+        let mut chunks = Vec::new();
+        let mut at = 8;
+        while at < png.len() {
+            let len = u32_be(&png, at) as usize;
+            let body = &png[at + 4..at + 8 + len];
+            assert_eq!(u32_be(&png, at + 8 + len), crc32(body));
+            chunks.push((body[..4].to_vec(), body[4..].to_vec()));
+            at += 12 + len;
+        }
+        assert_eq!(at, png.len());
+
+        let kinds: Vec<&[u8]> = chunks.iter().map(|(k, _)| k.as_slice()).collect();
+        assert_eq!(kinds, [&b"IHDR"[..], b"IDAT", b"IEND"]);
+
+        let ihdr = &chunks[0].1;
+        assert_eq!(u32_be(ihdr, 0), 2);
+        assert_eq!(u32_be(ihdr, 4), 2);
+        assert_eq!(&ihdr[8..], &[8, 2, 0, 0, 0]);
+
+        let raw = zlib_decompress(&chunks[1].1).unwrap();
+        let mut rows = Vec::new();
+        for line in raw.chunks_exact(7) {
+            assert_eq!(line[0], 1);
+            let mut prev = [0u8; 3];
+            for px in line[1..].chunks_exact(3) {
+                for (p, c) in prev.iter_mut().zip(px) {
+                    *p = c.wrapping_add(*p);
+                }
+                rows.extend_from_slice(&prev);
+            }
+        }
+        assert_eq!(rows, [255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn format_from_extension() {
+        assert!(matches!(
+            ImageFormat::from_path(Path::new("a/shot.PNG")),
+            Some(ImageFormat::Png)
+        ));
+        assert!(matches!(
+            ImageFormat::from_path(Path::new("shot.bmp")),
+            Some(ImageFormat::Bmp)
+        ));
+        assert!(ImageFormat::from_path(Path::new("shot.jpg")).is_none());
+        assert!(ImageFormat::from_path(Path::new("shot")).is_none());
+    }
+    #[test]
+    fn frames_convert_on_the_reader() {
+        assert_eq!(PixelFormat::Gray.capture_format(), PixelFormat::Bgra);
+        assert_eq!(PixelFormat::Hsv.capture_format(), PixelFormat::Bgra);
+        assert_eq!(PixelFormat::Rgb.capture_format(), PixelFormat::Rgb);
+
+        //rows padded to 12 bytes like a frame with a larger stride
+        let mut padded = PIXELS[..8].to_vec();
+        padded.extend([9; 4]);
+        padded.extend(&PIXELS[8..]);
+        padded.extend([9; 4]);
+
+        assert_eq!(
+            convert_frame(&padded, 2, 2, 12, PixelFormat::Rgb),
+            [255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255]
+        );
+        assert_eq!(
+            convert_frame(&padded, 2, 2, 12, PixelFormat::Gray),
+            [76, 149, 28, 255]
+        );
+
+        let hsv = convert_frame(&padded, 2, 2, 12, PixelFormat::Hsv);
+        assert_eq!(to_bgra(&hsv, 2, 2, 6, PixelFormat::Hsv), PIXELS);
+    }
+}
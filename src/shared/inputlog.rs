@@ -0,0 +1,522 @@
+//Input logs, a timeline of the moves, buttons, keys and scrolls the client received.
+//
+//A log is JSON lines, one flat object per event:
+//  {"time":1520,"width":765,"height":503,"source":"bot","event":"move","x":312,"y":140}
+//  time     ms since the log started
+//  width    client size when the event happened, replays scale points to the current size
+//  height
+//  source   "user" for the real mouse and keyboard, "bot" for everything else
+//  event    "move" with x and y
+//           "button" with button (0 left, 1 middle, 2 right, 3 X1, 4 X2) and down (true or false)
+//           "key" with vk and down
+//           "scroll" with delta (WHEEL_DELTA per notch, positive is up or right) and horizontal
+//           "char" with unit, a UTF-16 code unit sent as WM_CHAR without a key behind it
+use std::io::{self, BufRead, Write};
+
+use super::message::{
+    unpack_point, BUTTON_COUNT, WM_KEYDOWN, WM_KEYUP, WM_SYSKEYDOWN, WM_SYSKEYUP,
+};
+
+const WM_CHAR: u32 = 0x0102;
+const WM_MOUSEMOVE: u32 = 0x0200;
+const WM_MOUSEWHEEL: u32 = 0x020A;
+const WM_MOUSEHWHEEL: u32 = 0x020E;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LogKind {
+    Move { x: i32, y: i32 },
+    Button { index: usize, down: bool },
+    Key { vk: u8, down: bool },
+    Scroll { delta: i16, horizontal: bool },
+    Char { unit: u16 },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LogEvent {
+    pub time: u64,
+    pub width: i32,
+    pub height: i32,
+    pub user: bool,
+    pub kind: LogKind,
+}
+
+//The event of a window message, None for messages that aren't logged.
+//Autorepeated key downs are left out, replays repeat held keys on their own.
+//So are characters TranslateMessage made from a key, replaying the key types them again.
+//Those carry the key's scancode, characters sent on their own don't have one.
+pub fn message_event(msg: u32, wparam: usize, lparam: isize) -> Option<LogKind> {
+    let xbutton = || match (wparam >> 16) & 0xFFFF {
+        1 => Some(3),
+        2 => Some(4),
+        _ => None,
+    };
+    let button = |index, down| Some(LogKind::Button { index, down });
+
+    match msg {
+        WM_MOUSEMOVE => {
+            let (x, y) = unpack_point(lparam);
+            Some(LogKind::Move { x, y })
+        }
+        0x0201 | 0x0203 => button(0, true),
+        0x0202 => button(0, false),
+        0x0204 | 0x0206 => button(2, true),
+        0x0205 => button(2, false),
+        0x0207 | 0x0209 => button(1, true),
+        0x0208 => button(1, false),
+        0x020B | 0x020D => button(xbutton()?, true),
+        0x020C => button(xbutton()?, false),
+        WM_MOUSEWHEEL | WM_MOUSEHWHEEL => Some(LogKind::Scroll {
+            delta: ((wparam >> 16) & 0xFFFF) as u16 as i16,
+            horizontal: msg == WM_MOUSEHWHEEL,
+        }),
+        WM_KEYDOWN | WM_SYSKEYDOWN if (lparam >> 30) & 1 == 0 => Some(LogKind::Key {
+            vk: wparam as u8,
+            down: true,
+        }),
+        WM_KEYUP | WM_SYSKEYUP => Some(LogKind::Key {
+            vk: wparam as u8,
+            down: false,
+        }),
+        WM_CHAR if (lparam >> 16) & 0xFF == 0 => Some(LogKind::Char {
+            unit: wparam as u16,
+        }),
+        _ => None,
+    }
+}
+
+pub fn format_event(event: &LogEvent) -> String {
+    let head = format!(
+        "{{\"time\":{},\"width\":{},\"height\":{},\"source\":\"{}\"",
+        event.time,
+        event.width,
+        event.height,
+        if event.user { "user" } else { "bot" }
+    );
+
+    let body = match event.kind {
+        LogKind::Move { x, y } => format!("\"event\":\"move\",\"x\":{},\"y\":{}", x, y),
+        LogKind::Button { index, down } => {
+            format!(
+                "\"event\":\"button\",\"button\":{},\"down\":{}",
+                index, down
+            )
+        }
+        LogKind::Key { vk, down } => format!("\"event\":\"key\",\"vk\":{},\"down\":{}", vk, down),
+        LogKind::Scroll { delta, horizontal } => format!(
+            "\"event\":\"scroll\",\"delta\":{},\"horizontal\":{}",
+            delta, horizontal
+        ),
+        LogKind::Char { unit } => format!("\"event\":\"char\",\"unit\":{}", unit),
+    };
+    format!("{},{}}}", head, body)
+}
+
+//Names and raw values of a flat object, strings lose their quotes. Only what format_event writes is understood.
+fn fields(line: &str) -> Option<Vec<(&str, &str)>> {
+    let inner = line.trim().strip_prefix('{')?.strip_suffix('}')?;
+    inner
+        .split(',')
+        .map(|field| {
+            let (name, value) = field.split_once(':')?;
+            let name = name.trim().strip_prefix('"')?.strip_suffix('"')?;
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(value);
+            Some((name, value))
+        })
+        .collect()
+}
+
+pub fn parse_event(line: &str) -> Option<LogEvent> {
+    let fields = fields(line)?;
+    let get = |name| fields.iter().find(|(n, _)| *n == name).map(|(_, v)| *v);
+    let number = |name| get(name)?.parse::<i64>().ok();
+    let flag = |name| get(name)?.parse::<bool>().ok();
+
+    let kind = match get("event")? {
+        "move" => LogKind::Move {
+            x: number("x")? as i32,
+            y: number("y")? as i32,
+        },
+        "button" => LogKind::Button {
+            index: usize::try_from(number("button")?)
+                .ok()
+                .filter(|&i| i < BUTTON_COUNT)?,
+            down: flag("down")?,
+        },
+        "key" => LogKind::Key {
+            vk: u8::try_from(number("vk")?).ok()?,
+            down: flag("down")?,
+        },
+        "scroll" => LogKind::Scroll {
+            delta: i16::try_from(number("delta")?).ok()?,
+            horizontal: flag("horizontal")?,
+        },
+        "char" => LogKind::Char {
+            unit: u16::try_from(number("unit")?).ok()?,
+        },
+        _ => return None,
+    };
+
+    Some(LogEvent {
+        time: u64::try_from(number("time")?).ok()?,
+        width: number("width")? as i32,
+        height: number("height")? as i32,
+        user: get("source")? == "user",
+        kind,
+    })
+}
+
+pub struct LogWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> LogWriter<W> {
+    pub fn new(out: W) -> Self {
+        LogWriter { out }
+    }
+
+    pub fn write(&mut self, event: &LogEvent) -> io::Result<()> {
+        writeln!(self.out, "{}", format_event(event))
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+//Reads a whole log, blank lines are skipped and the line number of the first bad one is the error.
+pub fn read_log<R: BufRead>(input: R) -> Result<Vec<LogEvent>, String> {
+    let mut events = Vec::new();
+    for (number, line) in input.lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match parse_event(&line) {
+            Some(event) => events.push(event),
+            None => return Err(format!("bad event on line {}", number + 1)),
+        }
+    }
+    Ok(events)
+}
+
+//Where a replay sends its input, the plugin drives a target and anything else can stand in for it.
+pub trait InputSink {
+    //Current client size, None if unknown and points are replayed as logged.
+    fn size(&self) -> Option<(i32, i32)>;
+    fn wait(&mut self, ms: u64);
+    fn mouse_move(&mut self, x: i32, y: i32);
+    fn button(&mut self, index: usize, down: bool);
+    fn key(&mut self, vk: u8, down: bool);
+    fn scroll(&mut self, delta: i16, horizontal: bool);
+    fn char(&mut self, unit: u16);
+}
+
+fn scale(value: i32, from: i32, to: i32) -> i32 {
+    if (from <= 0) || (to <= 0) || (from == to) {
+        return value;
+    }
+    ((value as i64 * to as i64) / from as i64) as i32
+}
+
+//Plays events with their original spacing and lets go of whatever is still held at the end.
+pub fn replay(events: &[LogEvent], sink: &mut dyn InputSink) {
+    let mut buttons = [false; BUTTON_COUNT];
+    let mut keys = [false; 256];
+    let mut last = events.first().map_or(0, |e| e.time);
+
+    for event in events {
+        sink.wait(event.time.saturating_sub(last));
+        last = last.max(event.time);
+
+        match event.kind {
+            LogKind::Move { x, y } => match sink.size() {
+                Some((width, height)) => {
+                    sink.mouse_move(scale(x, event.width, width), scale(y, event.height, height))
+                }
+                None => sink.mouse_move(x, y),
+            },
+            LogKind::Button { index, down } => {
+                buttons[index] = down;
+                sink.button(index, down);
+            }
+            LogKind::Key { vk, down } => {
+                keys[vk as usize] = down;
+                sink.key(vk, down);
+            }
+            LogKind::Scroll { delta, horizontal } => sink.scroll(delta, horizontal),
+            LogKind::Char { unit } => sink.char(unit),
+        }
+    }
+
+    for (index, held) in buttons.iter().enumerate() {
+        if *held {
+            sink.button(index, false);
+        }
+    }
+    for (vk, held) in keys.iter().enumerate() {
+        if *held {
+            sink.key(vk as u8, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct MockSink {
+        size: Option<(i32, i32)>,
+        calls: Vec<String>,
+    }
+
+    impl InputSink for MockSink {
+        fn size(&self) -> Option<(i32, i32)> {
+            self.size
+        }
+
+        fn wait(&mut self, ms: u64) {
+            if ms > 0 {
+                self.calls.push(format!("wait {}", ms));
+            }
+        }
+
+        fn mouse_move(&mut self, x: i32, y: i32) {
+            self.calls.push(format!("move {} {}", x, y));
+        }
+
+        fn button(&mut self, index: usize, down: bool) {
+            self.calls.push(format!("button {} {}", index, down));
+        }
+
+        fn key(&mut self, vk: u8, down: bool) {
+            self.calls.push(format!("key {} {}", vk, down));
+        }
+
+        fn scroll(&mut self, delta: i16, horizontal: bool) {
+            self.calls.push(format!("scroll {} {}", delta, horizontal));
+        }
+
+        fn char(&mut self, unit: u16) {
+            self.calls.push(format!("char {}", unit));
+        }
+    }
+
+    fn event(time: u64, kind: LogKind) -> LogEvent {
+        LogEvent {
+            time,
+            width: 800,
+            height: 600,
+            user: time % 2 == 1,
+            kind,
+        }
+    }
+
+    fn events() -> Vec<LogEvent> {
+        vec![
+            event(100, LogKind::Move { x: 400, y: 300 }),
+            event(
+                150,
+                LogKind::Button {
+                    index: 0,
+                    down: true,
+                },
+            ),
+            event(
+                151,
+                LogKind::Key {
+                    vk: 0x25,
+                    down: true,
+                },
+            ),
+            event(
+                300,
+                LogKind::Scroll {
+                    delta: -120,
+                    horizontal: false,
+                },
+            ),
+            event(310, LogKind::Char { unit: 0xE9 }),
+            event(320, LogKind::Move { x: -5, y: 20 }),
+        ]
+    }
+
+    #[test]
+    fn log_round_trips() {
+        let mut buffer = Vec::new();
+        let mut writer = LogWriter::new(&mut buffer);
+        for event in events() {
+            writer.write(&event).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let text = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            text.lines().next().unwrap(),
+            r#"{"time":100,"width":800,"height":600,"source":"bot","event":"move","x":400,"y":300}"#
+        );
+        assert_eq!(read_log(text.as_bytes()).unwrap(), events());
+    }
+
+    #[test]
+    fn bad_lines_are_reported() {
+        let log = "\n{\"time\":1}\n";
+        assert_eq!(
+            read_log(log.as_bytes()),
+            Err("bad event on line 2".to_string())
+        );
+
+        let line = format_event(&event(
+            1,
+            LogKind::Button {
+                index: 0,
+                down: true,
+            },
+        ));
+        assert!(parse_event(&line.replace("\"button\":0", "\"button\":5")).is_none());
+        assert!(parse_event(&line.replace("\"down\":true", "\"down\":1")).is_none());
+        assert!(parse_event(&line.replace("\"time\":1", "\"time\":-1")).is_none());
+        assert!(parse_event("not json").is_none());
+    }
+
+    #[test]
+    fn replay_keeps_spacing_and_scales() {
+        let mut sink = MockSink {
+            size: Some((400, 300)),
+            ..Default::default()
+        };
+        replay(&events(), &mut sink);
+
+        assert_eq!(
+            sink.calls,
+            [
+                "move 200 150",
+                "wait 50",
+                "button 0 true",
+                "wait 1",
+                "key 37 true",
+                "wait 149",
+                "scroll -120 false",
+                "wait 10",
+                "char 233",
+                "wait 10",
+                "move -2 10",
+                //still held at the end
+                "button 0 false",
+                "key 37 false",
+            ]
+        );
+    }
+
+    #[test]
+    fn replay_without_a_size_uses_logged_points() {
+        let mut sink = MockSink::default();
+        replay(&events()[..1], &mut sink);
+        assert_eq!(sink.calls, ["move 400 300"]);
+    }
+
+    #[test]
+    fn replay_only_releases_what_is_held() {
+        let mut sink = MockSink::default();
+        let log = [
+            event(
+                0,
+                LogKind::Key {
+                    vk: 0x41,
+                    down: true,
+                },
+            ),
+            event(
+                0,
+                LogKind::Key {
+                    vk: 0x41,
+                    down: false,
+                },
+            ),
+            event(
+                0,
+                LogKind::Button {
+                    index: 4,
+                    down: true,
+                },
+            ),
+            //out of order times don't wait backwards
+            event(
+                0,
+                LogKind::Key {
+                    vk: 0x10,
+                    down: true,
+                },
+            ),
+        ];
+        replay(&log, &mut sink);
+        assert_eq!(
+            sink.calls,
+            [
+                "key 65 true",
+                "key 65 false",
+                "button 4 true",
+                "key 16 true",
+                "button 4 false",
+                "key 16 false",
+            ]
+        );
+    }
+
+    #[test]
+    fn messages_to_events() {
+        assert_eq!(
+            message_event(WM_MOUSEMOVE, 0, (20 << 16) | 10),
+            Some(LogKind::Move { x: 10, y: 20 })
+        );
+        assert_eq!(
+            message_event(WM_MOUSEMOVE, 0, 0xFFFF_FFFFu32 as i32 as isize),
+            Some(LogKind::Move { x: -1, y: -1 })
+        );
+        assert_eq!(
+            message_event(0x020B, 2 << 16, 0),
+            Some(LogKind::Button {
+                index: 4,
+                down: true
+            })
+        );
+        assert_eq!(message_event(0x020B, 3 << 16, 0), None);
+        assert_eq!(
+            message_event(WM_MOUSEWHEEL, 0xFF88 << 16, 0),
+            Some(LogKind::Scroll {
+                delta: -120,
+                horizontal: false
+            })
+        );
+
+        //autorepeat is left out, key up always counts
+        assert_eq!(message_event(WM_KEYDOWN, 0x41, 1 | (1 << 30)), None);
+        assert_eq!(
+            message_event(WM_SYSKEYDOWN, 0x12, 0x2038_0001),
+            Some(LogKind::Key {
+                vk: 0x12,
+                down: true
+            })
+        );
+        assert_eq!(
+            message_event(WM_KEYUP, 0x41, 0xC01E_0001u32 as i32 as isize),
+            Some(LogKind::Key {
+                vk: 0x41,
+                down: false
+            })
+        );
+
+        //characters of a key are left out, ones without a scancode are kept
+        assert_eq!(message_event(WM_CHAR, 'a' as usize, 0x001E_0001), None);
+        assert_eq!(
+            message_event(WM_CHAR, 0xE9, 1),
+            Some(LogKind::Char { unit: 0xE9 })
+        );
+        assert_eq!(message_event(0x0010, 0, 0), None);
+    }
+}
@@ -0,0 +1,91 @@
+//Virtual keyboard state the client sees through the GetKeyState hooks.
+
+//GetKeyboardState bits.
+pub const KEY_DOWN: u8 = 0x80;
+pub const KEY_TOGGLED: u8 = 0x01;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum KeyStateMode {
+    Merged = 0,   //keys held by the bot or the user are down
+    Isolated = 1, //the client only sees the bot's keys
+    Off = 2,      //the client only sees the real keyboard
+}
+
+impl KeyStateMode {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(KeyStateMode::Merged),
+            1 => Some(KeyStateMode::Isolated),
+            2 => Some(KeyStateMode::Off),
+            _ => None,
+        }
+    }
+}
+
+//Generic, left and right virtual keys of shift, ctrl and alt.
+fn sides(vk: u8) -> Option<(u8, u8, u8)> {
+    match vk {
+        0x10 | 0xA0 | 0xA1 => Some((0x10, 0xA0, 0xA1)),
+        0x11 | 0xA2 | 0xA3 => Some((0x11, 0xA2, 0xA3)),
+        0x12 | 0xA4 | 0xA5 => Some((0x12, 0xA4, 0xA5)),
+        _ => None,
+    }
+}
+
+//New state byte of a key going down or up, toggles flip on every press like caps lock.
+pub fn press_key(state: u8, down: bool) -> u8 {
+    let held = (state & KEY_DOWN) != 0;
+    match (down, held) {
+        (true, false) => KEY_DOWN | ((state & KEY_TOGGLED) ^ KEY_TOGGLED),
+        (true, true) => state,
+        (false, _) => state & KEY_TOGGLED,
+    }
+}
+
+//Presses or releases vk, generic modifiers go to their left side and keep both in step like Windows does.
+pub fn press_virtual(keys: &mut [u8; 256], vk: u8, down: bool) {
+    match sides(vk) {
+        Some((generic, left, right)) => {
+            let side = if vk == generic { left } else { vk } as usize;
+            keys[side] = press_key(keys[side], down);
+
+            let held = (keys[left as usize] | keys[right as usize]) & KEY_DOWN;
+            keys[generic as usize] = (keys[generic as usize] & KEY_TOGGLED) | held;
+        }
+        None => keys[vk as usize] = press_key(keys[vk as usize], down),
+    }
+}
+
+//GetKeyboardState byte the client sees, toggles come from the real keyboard when merged.
+pub fn merge_key(real: u8, bot: u8, mode: KeyStateMode) -> u8 {
+    match mode {
+        KeyStateMode::Merged => (real & KEY_TOGGLED) | ((real | bot) & KEY_DOWN),
+        KeyStateMode::Isolated => bot,
+        KeyStateMode::Off => real,
+    }
+}
+
+//GetKeyState result the client sees, high bit down and low bit toggled.
+pub fn merge_key_state(real: i16, bot: u8, mode: KeyStateMode) -> i16 {
+    let mut real_byte = (real as u16 & 1) as u8;
+    if real < 0 {
+        real_byte |= KEY_DOWN;
+    }
+
+    let state = merge_key(real_byte, bot, mode);
+    let mut result = (state & KEY_TOGGLED) as u16;
+    if (state & KEY_DOWN) != 0 {
+        result |= 0x8000;
+    }
+    result as i16
+}
+
+//GetAsyncKeyState result the client sees, the low bit is only ever taken from the real keyboard.
+pub fn merge_async_key_state(real: i16, bot: u8, mode: KeyStateMode) -> i16 {
+    let down = if (bot & KEY_DOWN) != 0 { i16::MIN } else { 0 };
+    match mode {
+        KeyStateMode::Merged => real | down,
+        KeyStateMode::Isolated => down,
+        KeyStateMode::Off => real,
+    }
+}
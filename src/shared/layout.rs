@@ -0,0 +1,734 @@
+//Keyboard layouts, character to key mapping without asking Windows.
+
+//Same bits as the high byte of VkKeyScan.
+pub const MOD_SHIFT: u8 = 0x01;
+pub const MOD_CTRL: u8 = 0x02;
+pub const MOD_ALT: u8 = 0x04;
+pub const MOD_ALTGR: u8 = MOD_CTRL | MOD_ALT;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KeyStroke {
+    pub vk: u8,
+    pub scancode: u16,
+    pub modifiers: u8,
+    pub extended: bool,
+    pub dead: bool, //only combines with the next key instead of typing
+}
+
+impl KeyStroke {
+    pub fn shift(&self) -> bool {
+        self.modifiers & MOD_SHIFT != 0
+    }
+
+    pub fn ctrl(&self) -> bool {
+        self.modifiers & MOD_CTRL != 0
+    }
+
+    pub fn alt(&self) -> bool {
+        self.modifiers & MOD_ALT != 0
+    }
+}
+
+pub trait KeyboardLayout {
+    fn name(&self) -> &str;
+    //Key and modifiers that type ch directly, None if the layout has no key for it.
+    fn char_key(&self, ch: char) -> Option<KeyStroke>;
+    //Key for a virtual key code, without modifiers.
+    fn vk_key(&self, vk: u8) -> KeyStroke;
+    //Dead key that puts accent on the next character.
+    fn dead_key(&self, accent: char) -> Option<KeyStroke> {
+        self.char_key(accent).filter(|stroke| stroke.dead)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Layout {
+    Os = 0, //whatever the client is using
+    Us = 1,
+    Uk = 2,
+    De = 3,
+    Fr = 4,
+    Nordic = 5,
+}
+
+impl Layout {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(Layout::Os),
+            1 => Some(Layout::Us),
+            2 => Some(Layout::Uk),
+            3 => Some(Layout::De),
+            4 => Some(Layout::Fr),
+            5 => Some(Layout::Nordic),
+            _ => None,
+        }
+    }
+
+    pub fn table(self) -> Option<&'static TableLayout> {
+        match self {
+            Layout::Os => None,
+            Layout::Us => Some(&US),
+            Layout::Uk => Some(&UK),
+            Layout::De => Some(&DE),
+            Layout::Fr => Some(&FR),
+            Layout::Nordic => Some(&NORDIC),
+        }
+    }
+}
+
+//Keys that don't depend on the layout as (vk, scancode, extended).
+const FIXED_KEYS: &[(u8, u16, bool)] = &[
+    (0x08, 0x0E, false), //backspace
+    (0x09, 0x0F, false), //tab
+    (0x0D, 0x1C, false), //enter
+    (0x10, 0x2A, false), //shift
+    (0x11, 0x1D, false), //ctrl
+    (0x12, 0x38, false), //alt
+    (0x13, 0x45, false), //pause
+    (0x14, 0x3A, false), //caps lock
+    (0x1B, 0x01, false), //escape
+    (0x20, 0x39, false), //space
+    (0x21, 0x49, true),  //page up
+    (0x22, 0x51, true),  //page down
+    (0x23, 0x4F, true),  //end
+    (0x24, 0x47, true),  //home
+    (0x25, 0x4B, true),  //left
+    (0x26, 0x48, true),  //up
+    (0x27, 0x4D, true),  //right
+    (0x28, 0x50, true),  //down
+    (0x2C, 0x37, true),  //print screen
+    (0x2D, 0x52, true),  //insert
+    (0x2E, 0x53, true),  //delete
+    (0x5B, 0x5B, true),  //left windows
+    (0x5C, 0x5C, true),  //right windows
+    (0x5D, 0x5D, true),  //menu
+    (0x60, 0x52, false), //numpad 0
+    (0x61, 0x4F, false),
+    (0x62, 0x50, false),
+    (0x63, 0x51, false),
+    (0x64, 0x4B, false),
+    (0x65, 0x4C, false),
+    (0x66, 0x4D, false),
+    (0x67, 0x47, false),
+    (0x68, 0x48, false),
+    (0x69, 0x49, false), //numpad 9
+    (0x6A, 0x37, false), //numpad *
+    (0x6B, 0x4E, false), //numpad +
+    (0x6D, 0x4A, false), //numpad -
+    (0x6E, 0x53, false), //numpad .
+    (0x6F, 0x35, true),  //numpad /
+    (0x70, 0x3B, false), //F1
+    (0x71, 0x3C, false),
+    (0x72, 0x3D, false),
+    (0x73, 0x3E, false),
+    (0x74, 0x3F, false),
+    (0x75, 0x40, false),
+    (0x76, 0x41, false),
+    (0x77, 0x42, false),
+    (0x78, 0x43, false),
+    (0x79, 0x44, false), //F10
+    (0x7A, 0x57, false), //F11
+    (0x7B, 0x58, false), //F12
+    (0x90, 0x45, true),  //num lock
+    (0x91, 0x46, false), //scroll lock
+    (0xA0, 0x2A, false), //left shift
+    (0xA1, 0x36, false), //right shift
+    (0xA2, 0x1D, false), //left ctrl
+    (0xA3, 0x1D, true),  //right ctrl
+    (0xA4, 0x38, false), //left alt
+    (0xA5, 0x38, true),  //right alt
+];
+
+//Characters typed by the fixed keys.
+const FIXED_CHARS: &[(char, u8)] = &[
+    (' ', 0x20),
+    ('\t', 0x09),
+    ('\r', 0x0D),
+    ('\n', 0x0D),
+    ('\u{8}', 0x08),
+];
+
+//Keystroke for a layout independent key, None for keys the layouts move around.
+pub fn fixed_key(vk: u8) -> Option<KeyStroke> {
+    FIXED_KEYS
+        .iter()
+        .find(|&&(key, _, _)| key == vk)
+        .map(|&(vk, scancode, extended)| KeyStroke {
+            vk,
+            scancode,
+            modifiers: 0,
+            extended,
+            dead: false,
+        })
+}
+
+const N: char = '\0';
+
+//One physical key: scancode, virtual key and what it types plain, with shift and with AltGr.
+//dead marks which of the three only combine with the next key.
+pub struct LayoutKey {
+    pub scancode: u16,
+    pub vk: u8,
+    pub chars: [char; 3],
+    pub dead: [bool; 3],
+}
+
+const fn key(scancode: u16, vk: u8, plain: char, shift: char, altgr: char) -> LayoutKey {
+    LayoutKey {
+        scancode,
+        vk,
+        chars: [plain, shift, altgr],
+        dead: [false; 3],
+    }
+}
+
+const fn dead(scancode: u16, vk: u8, chars: [char; 3], dead: [bool; 3]) -> LayoutKey {
+    LayoutKey {
+        scancode,
+        vk,
+        chars,
+        dead,
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct TableLayout {
+    pub name: &'static str,
+    pub keys: &'static [LayoutKey],
+}
+
+impl TableLayout {
+    fn find(&self, ch: char, dead: bool) -> Option<KeyStroke> {
+        const MODIFIERS: [u8; 3] = [0, MOD_SHIFT, MOD_ALTGR];
+
+        if ch == N {
+            return None;
+        }
+
+        for key in self.keys {
+            let entries = key.chars.iter().zip(key.dead).zip(MODIFIERS);
+            for ((&c, is_dead), modifiers) in entries {
+                if (c == ch) && (is_dead == dead) {
+                    return Some(KeyStroke {
+                        vk: key.vk,
+                        scancode: key.scancode,
+                        modifiers,
+                        extended: false,
+                        dead,
+                    });
+                }
+            }
+        }
+        None
+    }
+}
+
+impl KeyboardLayout for TableLayout {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn char_key(&self, ch: char) -> Option<KeyStroke> {
+        if let Some(&(_, vk)) = FIXED_CHARS.iter().find(|&&(c, _)| c == ch) {
+            return fixed_key(vk);
+        }
+
+        //some accents have both a normal and a dead key, typing prefers the normal one
+        self.find(ch, false).or_else(|| self.find(ch, true))
+    }
+
+    fn dead_key(&self, accent: char) -> Option<KeyStroke> {
+        self.find(accent, true)
+    }
+
+    fn vk_key(&self, vk: u8) -> KeyStroke {
+        if let Some(stroke) = fixed_key(vk) {
+            return stroke;
+        }
+
+        let scancode = self
+            .keys
+            .iter()
+            .find(|key| key.vk == vk)
+            .map_or(0, |key| key.scancode);
+        KeyStroke {
+            vk,
+            scancode,
+            modifiers: 0,
+            extended: false,
+            dead: false,
+        }
+    }
+}
+
+//Characters built from a dead key followed by a base key.
+const COMPOSED: &[(char, &str, &str)] = &[
+    ('´', "aeiouyAEIOUY", "áéíóúýÁÉÍÓÚÝ"),
+    ('`', "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+    ('^', "aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+    ('¨', "aeiouyAEIOU", "äëïöüÿÄËÏÖÜ"),
+    ('~', "anoANO", "ãñõÃÑÕ"),
+];
+
+//Keys to press for ch in order, None if it has to be sent as a character message instead.
+pub fn key_sequence(layout: &dyn KeyboardLayout, ch: char) -> Option<Vec<KeyStroke>> {
+    if let Some(stroke) = layout.char_key(ch) {
+        if !stroke.dead {
+            return Some(vec![stroke]);
+        }
+
+        //a dead key followed by space types the accent itself
+        let space = layout.char_key(' ')?;
+        return Some(vec![stroke, space]);
+    }
+
+    for &(accent, bases, composed) in COMPOSED {
+        let Some(index) = composed.chars().position(|c| c == ch) else {
+            continue;
+        };
+        let base = bases.chars().nth(index)?;
+
+        match (layout.dead_key(accent), layout.char_key(base)) {
+            (Some(accent), Some(base)) if !base.dead => {
+                return Some(vec![accent, base]);
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+const VK_OEM_1: u8 = 0xBA;
+const VK_OEM_PLUS: u8 = 0xBB;
+const VK_OEM_COMMA: u8 = 0xBC;
+const VK_OEM_MINUS: u8 = 0xBD;
+const VK_OEM_PERIOD: u8 = 0xBE;
+const VK_OEM_2: u8 = 0xBF;
+const VK_OEM_3: u8 = 0xC0;
+const VK_OEM_4: u8 = 0xDB;
+const VK_OEM_5: u8 = 0xDC;
+const VK_OEM_6: u8 = 0xDD;
+const VK_OEM_7: u8 = 0xDE;
+const VK_OEM_8: u8 = 0xDF;
+const VK_OEM_102: u8 = 0xE2;
+
+pub static US: TableLayout = TableLayout {
+    name: "US",
+    keys: &[
+        key(0x29, VK_OEM_3, '`', '~', N),
+        key(0x02, b'1', '1', '!', N),
+        key(0x03, b'2', '2', '@', N),
+        key(0x04, b'3', '3', '#', N),
+        key(0x05, b'4', '4', '$', N),
+        key(0x06, b'5', '5', '%', N),
+        key(0x07, b'6', '6', '^', N),
+        key(0x08, b'7', '7', '&', N),
+        key(0x09, b'8', '8', '*', N),
+        key(0x0A, b'9', '9', '(', N),
+        key(0x0B, b'0', '0', ')', N),
+        key(0x0C, VK_OEM_MINUS, '-', '_', N),
+        key(0x0D, VK_OEM_PLUS, '=', '+', N),
+        key(0x10, b'Q', 'q', 'Q', N),
+        key(0x11, b'W', 'w', 'W', N),
+        key(0x12, b'E', 'e', 'E', N),
+        key(0x13, b'R', 'r', 'R', N),
+        key(0x14, b'T', 't', 'T', N),
+        key(0x15, b'Y', 'y', 'Y', N),
+        key(0x16, b'U', 'u', 'U', N),
+        key(0x17, b'I', 'i', 'I', N),
+        key(0x18, b'O', 'o', 'O', N),
+        key(0x19, b'P', 'p', 'P', N),
+        key(0x1A, VK_OEM_4, '[', '{', N),
+        key(0x1B, VK_OEM_6, ']', '}', N),
+        key(0x1E, b'A', 'a', 'A', N),
+        key(0x1F, b'S', 's', 'S', N),
+        key(0x20, b'D', 'd', 'D', N),
+        key(0x21, b'F', 'f', 'F', N),
+        key(0x22, b'G', 'g', 'G', N),
+        key(0x23, b'H', 'h', 'H', N),
+        key(0x24, b'J', 'j', 'J', N),
+        key(0x25, b'K', 'k', 'K', N),
+        key(0x26, b'L', 'l', 'L', N),
+        key(0x27, VK_OEM_1, ';', ':', N),
+        key(0x28, VK_OEM_7, '\'', '"', N),
+        key(0x2B, VK_OEM_5, '\\', '|', N),
+        key(0x2C, b'Z', 'z', 'Z', N),
+        key(0x2D, b'X', 'x', 'X', N),
+        key(0x2E, b'C', 'c', 'C', N),
+        key(0x2F, b'V', 'v', 'V', N),
+        key(0x30, b'B', 'b', 'B', N),
+        key(0x31, b'N', 'n', 'N', N),
+        key(0x32, b'M', 'm', 'M', N),
+        key(0x33, VK_OEM_COMMA, ',', '<', N),
+        key(0x34, VK_OEM_PERIOD, '.', '>', N),
+        key(0x35, VK_OEM_2, '/', '?', N),
+    ],
+};
+
+pub static UK: TableLayout = TableLayout {
+    name: "UK",
+    keys: &[
+        key(0x29, VK_OEM_8, '`', '¬', '¦'),
+        key(0x02, b'1', '1', '!', N),
+        key(0x03, b'2', '2', '"', N),
+        key(0x04, b'3', '3', '£', N),
+        key(0x05, b'4', '4', '$', '€'),
+        key(0x06, b'5', '5', '%', N),
+        key(0x07, b'6', '6', '^', N),
+        key(0x08, b'7', '7', '&', N),
+        key(0x09, b'8', '8', '*', N),
+        key(0x0A, b'9', '9', '(', N),
+        key(0x0B, b'0', '0', ')', N),
+        key(0x0C, VK_OEM_MINUS, '-', '_', N),
+        key(0x0D, VK_OEM_PLUS, '=', '+', N),
+        key(0x10, b'Q', 'q', 'Q', N),
+        key(0x11, b'W', 'w', 'W', N),
+        key(0x12, b'E', 'e', 'E', 'é'),
+        key(0x13, b'R', 'r', 'R', N),
+        key(0x14, b'T', 't', 'T', N),
+        key(0x15, b'Y', 'y', 'Y', N),
+        key(0x16, b'U', 'u', 'U', 'ú'),
+        key(0x17, b'I', 'i', 'I', 'í'),
+        key(0x18, b'O', 'o', 'O', 'ó'),
+        key(0x19, b'P', 'p', 'P', N),
+        key(0x1A, VK_OEM_4, '[', '{', N),
+        key(0x1B, VK_OEM_6, ']', '}', N),
+        key(0x1E, b'A', 'a', 'A', 'á'),
+        key(0x1F, b'S', 's', 'S', N),
+        key(0x20, b'D', 'd', 'D', N),
+        key(0x21, b'F', 'f', 'F', N),
+        key(0x22, b'G', 'g', 'G', N),
+        key(0x23, b'H', 'h', 'H', N),
+        key(0x24, b'J', 'j', 'J', N),
+        key(0x25, b'K', 'k', 'K', N),
+        key(0x26, b'L', 'l', 'L', N),
+        key(0x27, VK_OEM_1, ';', ':', N),
+        key(0x28, VK_OEM_3, '\'', '@', N),
+        key(0x2B, VK_OEM_7, '#', '~', N),
+        key(0x56, VK_OEM_5, '\\', '|', N),
+        key(0x2C, b'Z', 'z', 'Z', N),
+        key(0x2D, b'X', 'x', 'X', N),
+        key(0x2E, b'C', 'c', 'C', N),
+        key(0x2F, b'V', 'v', 'V', N),
+        key(0x30, b'B', 'b', 'B', N),
+        key(0x31, b'N', 'n', 'N', N),
+        key(0x32, b'M', 'm', 'M', N),
+        key(0x33, VK_OEM_COMMA, ',', '<', N),
+        key(0x34, VK_OEM_PERIOD, '.', '>', N),
+        key(0x35, VK_OEM_2, '/', '?', N),
+    ],
+};
+
+//QWERTZ
+pub static DE: TableLayout = TableLayout {
+    name: "DE",
+    keys: &[
+        dead(0x29, VK_OEM_5, ['^', '°', N], [true, false, false]),
+        key(0x02, b'1', '1', '!', N),
+        key(0x03, b'2', '2', '"', '²'),
+        key(0x04, b'3', '3', '§', '³'),
+        key(0x05, b'4', '4', '$', N),
+        key(0x06, b'5', '5', '%', N),
+        key(0x07, b'6', '6', '&', N),
+        key(0x08, b'7', '7', '/', '{'),
+        key(0x09, b'8', '8', '(', '['),
+        key(0x0A, b'9', '9', ')', ']'),
+        key(0x0B, b'0', '0', '=', '}'),
+        key(0x0C, VK_OEM_4, 'ß', '?', '\\'),
+        dead(0x0D, VK_OEM_6, ['´', '`', N], [true, true, false]),
+        key(0x10, b'Q', 'q', 'Q', '@'),
+        key(0x11, b'W', 'w', 'W', N),
+        key(0x12, b'E', 'e', 'E', '€'),
+        key(0x13, b'R', 'r', 'R', N),
+        key(0x14, b'T', 't', 'T', N),
+        key(0x15, b'Z', 'z', 'Z', N),
+        key(0x16, b'U', 'u', 'U', N),
+        key(0x17, b'I', 'i', 'I', N),
+        key(0x18, b'O', 'o', 'O', N),
+        key(0x19, b'P', 'p', 'P', N),
+        key(0x1A, VK_OEM_1, 'ü', 'Ü', N),
+        key(0x1B, VK_OEM_PLUS, '+', '*', '~'),
+        key(0x1E, b'A', 'a', 'A', N),
+        key(0x1F, b'S', 's', 'S', N),
+        key(0x20, b'D', 'd', 'D', N),
+        key(0x21, b'F', 'f', 'F', N),
+        key(0x22, b'G', 'g', 'G', N),
+        key(0x23, b'H', 'h', 'H', N),
+        key(0x24, b'J', 'j', 'J', N),
+        key(0x25, b'K', 'k', 'K', N),
+        key(0x26, b'L', 'l', 'L', N),
+        key(0x27, VK_OEM_3, 'ö', 'Ö', N),
+        key(0x28, VK_OEM_7, 'ä', 'Ä', N),
+        key(0x2B, VK_OEM_2, '#', '\'', N),
+        key(0x56, VK_OEM_102, '<', '>', '|'),
+        key(0x2C, b'Y', 'y', 'Y', N),
+        key(0x2D, b'X', 'x', 'X', N),
+        key(0x2E, b'C', 'c', 'C', N),
+        key(0x2F, b'V', 'v', 'V', N),
+        key(0x30, b'B', 'b', 'B', N),
+        key(0x31, b'N', 'n', 'N', N),
+        key(0x32, b'M', 'm', 'M', 'µ'),
+        key(0x33, VK_OEM_COMMA, ',', ';', N),
+        key(0x34, VK_OEM_PERIOD, '.', ':', N),
+        key(0x35, VK_OEM_MINUS, '-', '_', N),
+    ],
+};
+
+//AZERTY
+pub static FR: TableLayout = TableLayout {
+    name: "FR",
+    keys: &[
+        key(0x29, VK_OEM_7, '²', N, N),
+        key(0x02, b'1', '&', '1', N),
+        dead(0x03, b'2', ['é', '2', '~'], [false, false, true]),
+        key(0x04, b'3', '"', '3', '#'),
+        key(0x05, b'4', '\'', '4', '{'),
+        key(0x06, b'5', '(', '5', '['),
+        key(0x07, b'6', '-', '6', '|'),
+        dead(0x08, b'7', ['è', '7', '`'], [false, false, true]),
+        key(0x09, b'8', '_', '8', '\\'),
+        key(0x0A, b'9', 'ç', '9', '^'),
+        key(0x0B, b'0', 'à', '0', '@'),
+        key(0x0C, VK_OEM_4, ')', '°', ']'),
+        key(0x0D, VK_OEM_PLUS, '=', '+', '}'),
+        key(0x10, b'A', 'a', 'A', N),
+        key(0x11, b'Z', 'z', 'Z', N),
+        key(0x12, b'E', 'e', 'E', '€'),
+        key(0x13, b'R', 'r', 'R', N),
+        key(0x14, b'T', 't', 'T', N),
+        key(0x15, b'Y', 'y', 'Y', N),
+        key(0x16, b'U', 'u', 'U', N),
+        key(0x17, b'I', 'i', 'I', N),
+        key(0x18, b'O', 'o', 'O', N),
+        key(0x19, b'P', 'p', 'P', N),
+        dead(0x1A, VK_OEM_6, ['^', '¨', N], [true, true, false]),
+        key(0x1B, VK_OEM_1, '$', '£', '¤'),
+        key(0x1E, b'Q', 'q', 'Q', N),
+        key(0x1F, b'S', 's', 'S', N),
+        key(0x20, b'D', 'd', 'D', N),
+        key(0x21, b'F', 'f', 'F', N),
+        key(0x22, b'G', 'g', 'G', N),
+        key(0x23, b'H', 'h', 'H', N),
+        key(0x24, b'J', 'j', 'J', N),
+        key(0x25, b'K', 'k', 'K', N),
+        key(0x26, b'L', 'l', 'L', N),
+        key(0x27, b'M', 'm', 'M', N),
+        key(0x28, VK_OEM_3, 'ù', '%', N),
+        key(0x2B, VK_OEM_5, '*', 'µ', N),
+        key(0x56, VK_OEM_102, '<', '>', N),
+        key(0x2C, b'W', 'w', 'W', N),
+        key(0x2D, b'X', 'x', 'X', N),
+        key(0x2E, b'C', 'c', 'C', N),
+        key(0x2F, b'V', 'v', 'V', N),
+        key(0x30, b'B', 'b', 'B', N),
+        key(0x31, b'N', 'n', 'N', N),
+        key(0x32, VK_OEM_COMMA, ',', '?', N),
+        key(0x33, VK_OEM_PERIOD, ';', '.', N),
+        key(0x34, VK_OEM_2, ':', '/', N),
+        key(0x35, VK_OEM_8, '!', '§', N),
+    ],
+};
+
+//Swedish and Finnish
+pub static NORDIC: TableLayout = TableLayout {
+    name: "Nordic",
+    keys: &[
+        key(0x29, VK_OEM_5, '§', '½', N),
+        key(0x02, b'1', '1', '!', N),
+        key(0x03, b'2', '2', '"', '@'),
+        key(0x04, b'3', '3', '#', '£'),
+        key(0x05, b'4', '4', '¤', '$'),
+        key(0x06, b'5', '5', '%', '€'),
+        key(0x07, b'6', '6', '&', N),
+        key(0x08, b'7', '7', '/', '{'),
+        key(0x09, b'8', '8', '(', '['),
+        key(0x0A, b'9', '9', ')', ']'),
+        key(0x0B, b'0', '0', '=', '}'),
+        key(0x0C, VK_OEM_PLUS, '+', '?', '\\'),
+        dead(0x0D, VK_OEM_4, ['´', '`', N], [true, true, false]),
+        key(0x10, b'Q', 'q', 'Q', N),
+        key(0x11, b'W', 'w', 'W', N),
+        key(0x12, b'E', 'e', 'E', '€'),
+        key(0x13, b'R', 'r', 'R', N),
+        key(0x14, b'T', 't', 'T', N),
+        key(0x15, b'Y', 'y', 'Y', N),
+        key(0x16, b'U', 'u', 'U', N),
+        key(0x17, b'I', 'i', 'I', N),
+        key(0x18, b'O', 'o', 'O', N),
+        key(0x19, b'P', 'p', 'P', N),
+        key(0x1A, VK_OEM_6, 'å', 'Å', N),
+        dead(0x1B, VK_OEM_1, ['¨', '^', '~'], [true, true, true]),
+        key(0x1E, b'A', 'a', 'A', N),
+        key(0x1F, b'S', 's', 'S', N),
+        key(0x20, b'D', 'd', 'D', N),
+        key(0x21, b'F', 'f', 'F', N),
+        key(0x22, b'G', 'g', 'G', N),
+        key(0x23, b'H', 'h', 'H', N),
+        key(0x24, b'J', 'j', 'J', N),
+        key(0x25, b'K', 'k', 'K', N),
+        key(0x26, b'L', 'l', 'L', N),
+        key(0x27, VK_OEM_3, 'ö', 'Ö', N),
+        key(0x28, VK_OEM_7, 'ä', 'Ä', N),
+        key(0x2B, VK_OEM_2, '\'', '*', N),
+        key(0x56, VK_OEM_102, '<', '>', '|'),
+        key(0x2C, b'Z', 'z', 'Z', N),
+        key(0x2D, b'X', 'x', 'X', N),
+        key(0x2E, b'C', 'c', 'C', N),
+        key(0x2F, b'V', 'v', 'V', N),
+        key(0x30, b'B', 'b', 'B', N),
+        key(0x31, b'N', 'n', 'N', N),
+        key(0x32, b'M', 'm', 'M', 'µ'),
+        key(0x33, VK_OEM_COMMA, ',', ';', N),
+        key(0x34, VK_OEM_PERIOD, '.', ':', N),
+        key(0x35, VK_OEM_MINUS, '-', '_', N),
+    ],
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLES: [&TableLayout; 5] = [&US, &UK, &DE, &FR, &NORDIC];
+
+    //What the key of stroke types on table, the reverse of char_key.
+    fn typed(table: &TableLayout, stroke: &KeyStroke) -> char {
+        let index = match stroke.modifiers {
+            0 => 0,
+            MOD_SHIFT => 1,
+            MOD_ALTGR => 2,
+            other => panic!("unexpected modifiers {:#x}", other),
+        };
+        let key = table
+            .keys
+            .iter()
+            .find(|k| (k.vk == stroke.vk) && (k.scancode == stroke.scancode))
+            .unwrap_or_else(|| panic!("{} has no key {:?}", table.name, stroke));
+        assert_eq!(key.dead[index], stroke.dead);
+        key.chars[index]
+    }
+
+    #[test]
+    fn every_char_round_trips() {
+        for table in TABLES {
+            for key in table.keys {
+                for &ch in key.chars.iter().filter(|&&c| c != N) {
+                    let stroke = table
+                        .char_key(ch)
+                        .unwrap_or_else(|| panic!("{} can't type {:?}", table.name, ch));
+                    assert_eq!(typed(table, &stroke), ch, "{}", table.name);
+                    assert_eq!(table.vk_key(stroke.vk).scancode, stroke.scancode);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn keys_are_unique() {
+        for table in TABLES {
+            for (i, a) in table.keys.iter().enumerate() {
+                for b in &table.keys[i + 1..] {
+                    assert_ne!(a.vk, b.vk, "{} vk {:#x}", table.name, a.vk);
+                    assert_ne!(
+                        a.scancode, b.scancode,
+                        "{} scancode {:#x}",
+                        table.name, a.scancode
+                    );
+                }
+                assert!(fixed_key(a.vk).is_none(), "{} vk {:#x}", table.name, a.vk);
+            }
+        }
+    }
+
+    #[test]
+    fn printable_ascii_is_typeable() {
+        for table in TABLES {
+            for ch in (0x20u8..0x7F).map(char::from) {
+                let keys = key_sequence(table, ch)
+                    .unwrap_or_else(|| panic!("{} can't type {:?}", table.name, ch));
+
+                //a dead key only types its accent when followed by space
+                match keys.as_slice() {
+                    [stroke] if ch == ' ' => assert_eq!(stroke.vk, 0x20),
+                    [stroke] => assert_eq!(typed(table, stroke), ch),
+                    [accent, space] if ch != ' ' => {
+                        assert!(accent.dead);
+                        assert_eq!(typed(table, accent), ch);
+                        assert_eq!(space.vk, 0x20);
+                    }
+                    other => panic!("{} types {:?} as {:?}", table.name, ch, other),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn accents_compose_from_dead_keys() {
+        for table in TABLES {
+            for &(accent, bases, composed) in COMPOSED {
+                for (base, ch) in bases.chars().zip(composed.chars()) {
+                    //layouts with the character on its own key type it directly
+                    if let Some(stroke) = table.char_key(ch) {
+                        assert_eq!(key_sequence(table, ch), Some(vec![stroke]));
+                        continue;
+                    }
+
+                    match (table.dead_key(accent), key_sequence(table, ch)) {
+                        (Some(dead), Some(keys)) => {
+                            assert_eq!(keys.len(), 2);
+                            assert_eq!(keys[0], dead);
+                            assert_eq!(typed(table, &keys[1]), base);
+                        }
+                        (None, keys) => assert_eq!(keys, None, "{} {:?}", table.name, ch),
+                        (Some(_), None) => panic!("{} can't compose {:?}", table.name, ch),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn layouts_differ_where_they_should() {
+        let stroke = |table: &TableLayout, ch| table.char_key(ch).unwrap();
+
+        //US and UK swap @ and "
+        assert_eq!(
+            (stroke(&US, '@').vk, stroke(&US, '@').modifiers),
+            (b'2', MOD_SHIFT)
+        );
+        assert_eq!(
+            (stroke(&UK, '@').vk, stroke(&UK, '@').modifiers),
+            (VK_OEM_3, MOD_SHIFT)
+        );
+        assert_eq!(stroke(&UK, '"').vk, b'2');
+
+        //QWERTZ and AZERTY
+        assert_eq!(stroke(&DE, 'z').scancode, 0x15);
+        assert_eq!(stroke(&FR, 'a').scancode, 0x10);
+        assert_eq!(stroke(&FR, '1').modifiers, MOD_SHIFT);
+
+        //AltGr characters
+        assert_eq!(stroke(&DE, '@').modifiers, MOD_ALTGR);
+        assert_eq!(stroke(&NORDIC, '@').modifiers, MOD_ALTGR);
+        assert_eq!(stroke(&NORDIC, 'å').scancode, 0x1A);
+
+        //^ is a dead key on DE and FR
+        assert!(DE.dead_key('^').is_some());
+        assert!(FR.dead_key('^').is_some());
+        assert!(US.dead_key('^').is_none());
+    }
+
+    #[test]
+    fn fixed_keys_are_shared() {
+        for table in TABLES {
+            assert_eq!(table.char_key('\n'), fixed_key(0x0D));
+            assert_eq!(table.char_key(' '), fixed_key(0x20));
+            assert!(table.vk_key(0x25).extended);
+        }
+        assert_eq!(
+            Layout::from_i32(2).and_then(Layout::table).unwrap().name,
+            "UK"
+        );
+        assert!(Layout::Os.table().is_none());
+        assert!(Layout::from_i32(6).is_none());
+    }
+}
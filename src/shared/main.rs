@@ -15,7 +15,10 @@ use windows::{
 use crate::{
     client::hooks::start,
     shared::{memory::MEMORY_MANAGER, sync::close_event},
-    simba::target::TARGETS,
+    simba::{
+        recorder::stop_recording,
+        target::{release_all, TARGETS},
+    },
 };
 
 use super::windows::{get_jagrenderview, WI_DETACH};
@@ -71,8 +74,11 @@ fn simba_main(hinst_dll: HINSTANCE, reason: u32) -> BOOL {
             TRUE
         }
         0 => {
-            let targets = TARGETS.lock().unwrap();
-            for target in targets.values() {
+            stop_recording();
+
+            let mut targets = TARGETS.lock().unwrap();
+            for target in targets.values_mut() {
+                release_all(target);
                 let hwnd = HWND(target.hwnd as *mut c_void);
                 let _ = unsafe { PostMessageW(Some(hwnd), WI_DETACH, WPARAM(0), LPARAM(0)) };
             }
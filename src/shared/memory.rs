@@ -1,6 +1,10 @@
 use std::{
-    ptr::{copy_nonoverlapping, null_mut, write_bytes},
-    sync::{Mutex, OnceLock},
+    mem::size_of,
+    ptr::{addr_of_mut, copy_nonoverlapping, null_mut, write_bytes},
+    sync::{
+        atomic::{AtomicI32, AtomicU32, Ordering},
+        Mutex, OnceLock,
+    },
     thread::sleep,
     time::{Duration, Instant},
 };
@@ -12,30 +16,138 @@ use windows::{
             CreateFileMappingA, MapViewOfFile, OpenFileMappingA, UnmapViewOfFile,
             FILE_MAP_ALL_ACCESS, MEMORY_MAPPED_VIEW_ADDRESS, PAGE_READWRITE,
         },
+        System::SystemInformation::GetTickCount64,
     },
 };
 
-const VERSION: &str = "8f0b348";
+use super::{
+    botinput::{BotTags, BOT_MESSAGE_WORDS},
+    image::{convert_frame, to_bgra, PixelFormat, TILE_COUNT},
+    keystate::{press_virtual, KeyStateMode},
+    rawinput::InputDelivery,
+    sequence::{InputEvent, SequenceState, MAX_SEQUENCE_EVENTS},
+};
+
+//Has to change with every change to SharedMemory, size catches a layout change that forgot to.
+const VERSION: &str = "360e8bc";
 const SHARED_MEM_NAME: &[u8] = b"WASPINPUT_DATA\0";
 const IMAGE_DATA_SIZE: usize = 33177602;
+const MAX_LOG_PATH: usize = 260;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Visibility {
+    Unknown = 0,
+    Visible = 1,
+    Minimized = 2,
+    Hidden = 3,
+    Occluded = 4, //other windows cover all of the client
+}
+
+impl Visibility {
+    pub fn from_i32(value: i32) -> Self {
+        match value {
+            1 => Visibility::Visible,
+            2 => Visibility::Minimized,
+            3 => Visibility::Hidden,
+            4 => Visibility::Occluded,
+            _ => Visibility::Unknown,
+        }
+    }
+}
+
+//Where the client's GetCursorPos and friends get the cursor from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CursorMode {
+    Auto = 0,    //the bot's cursor while user input is disabled or blocked
+    Virtual = 1, //always the bot's cursor
+    Real = 2,
+}
+
+impl CursorMode {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(CursorMode::Auto),
+            1 => Some(CursorMode::Virtual),
+            2 => Some(CursorMode::Real),
+            _ => None,
+        }
+    }
+}
+
+//What happens to the user's own mouse and keyboard input while a script runs.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UserInputPolicy {
+    PassThrough = 0,
+    Block = 1,
+    PauseBot = 2, //the bot waits until the user has been idle for user_idle ms
+}
+
+impl UserInputPolicy {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(UserInputPolicy::PassThrough),
+            1 => Some(UserInputPolicy::Block),
+            2 => Some(UserInputPolicy::PauseBot),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputOwner {
+    Bot = 0,
+    User = 1,
+}
 
 #[repr(C, packed)]
 pub struct SharedMemory {
     pub flag: u8,
     pub version: [u8; 7],
+    pub size: u32, //size_of::<SharedMemory>() of whoever created the map
     pub mouse_x: i32,
     pub mouse_y: i32,
     pub width: i32,
     pub height: i32,
+    pub frame_seq: u32, //odd while the client is writing img
+    pub stride: i32,
+    pub format: i32,
+    pub frame_count: u32,
+    pub frame_time: u64,    //GetTickCount64 of the last captured frame
+    pub changed_frame: u32, //frame_count of the last visible change
+    pub frame_hash: u64,
+    pub tile_hashes: [u32; TILE_COUNT],
+    pub visibility: i32,
+    pub key_mode: i32,
+    pub keys: [u8; 256], //GetKeyboardState bytes of the bot's keys and buttons
+    pub cursor_mode: i32,
+    pub input_delivery: i32,
+    pub user_policy: i32,
+    pub user_idle: u32,
+    pub user_input_time: u64, //GetTickCount64 of the last input from the real mouse or keyboard
+    pub clipboard_reads: u32, //text reads of the clipboard by the client
+    pub bot_head: u32,
+    pub bot_messages: [u32; BOT_MESSAGE_WORDS], //tags of the messages the bot posted
+    pub log_path: [u8; MAX_LOG_PATH],           //UTF-8 path of the input log, null terminated
+    pub sequence_id: u32,
+    pub sequence_state: i32,
+    pub sequence_len: u32,
+    pub sequence: [InputEvent; MAX_SEQUENCE_EVENTS],
     pub img: [u8; IMAGE_DATA_SIZE],
     pub overlay: [u8; IMAGE_DATA_SIZE],
 }
 
 const BUFFER_SIZE: usize = std::mem::size_of::<SharedMemory>();
+//The tags are used as atomics.
+const _: () = assert!(std::mem::offset_of!(SharedMemory, bot_head) % 4 == 0);
+const _: () = assert!(std::mem::offset_of!(SharedMemory, bot_messages) % 4 == 0);
 
 pub struct MemoryManager {
     ptr: *mut SharedMemory,
     hmap: HANDLE,
+    //Simba only, frames converted from what the client wrote
+    requested: PixelFormat,
+    capture: Vec<u8>,
+    bgra: Vec<u8>,
 }
 
 unsafe impl Send for MemoryManager {}
@@ -64,9 +176,38 @@ impl MemoryManager {
         (*ptr).mouse_y = -1;
         (*ptr).width = -1;
         (*ptr).height = -1;
+        (*ptr).frame_seq = 0;
+        (*ptr).stride = -1;
+        (*ptr).format = PixelFormat::Bgra as i32;
+        (*ptr).frame_count = 0;
+        (*ptr).frame_time = 0;
+        (*ptr).changed_frame = 0;
+        (*ptr).frame_hash = 0;
+        (*ptr).visibility = Visibility::Unknown as i32;
+        (*ptr).key_mode = KeyStateMode::Merged as i32;
+        (*ptr).keys = [0; 256];
+        (*ptr).cursor_mode = CursorMode::Auto as i32;
+        (*ptr).input_delivery = InputDelivery::Messages as i32;
+        (*ptr).user_policy = UserInputPolicy::PassThrough as i32;
+        (*ptr).user_idle = 0;
+        (*ptr).user_input_time = 0;
+        (*ptr).clipboard_reads = 0;
+        (*ptr).bot_head = 0;
+        (*ptr).bot_messages = [0; BOT_MESSAGE_WORDS];
+        (*ptr).log_path = [0; MAX_LOG_PATH];
+        (*ptr).sequence_id = 0;
+        (*ptr).sequence_state = SequenceState::Idle as i32;
+        (*ptr).sequence_len = 0;
         copy_nonoverlapping(VERSION.as_ptr(), (*ptr).version.as_mut_ptr(), 7);
+        (*ptr).size = BUFFER_SIZE as u32;
 
-        Self { ptr, hmap }
+        Self {
+            ptr,
+            hmap,
+            requested: PixelFormat::Bgra,
+            capture: Vec::new(),
+            bgra: Vec::new(),
+        }
     }
 
     pub unsafe fn open_map(time: u64) -> Self {
@@ -100,11 +241,17 @@ impl MemoryManager {
 
         let version = &(*ptr).version;
         assert!(
-            version == VERSION.as_bytes(),
+            (version == VERSION.as_bytes()) && ((*ptr).size == BUFFER_SIZE as u32),
             "[WaspInput]: Simba and Client are using different versions of WaspInput, please restart the client.\r\n"
         );
 
-        Self { ptr, hmap }
+        Self {
+            ptr,
+            hmap,
+            requested: PixelFormat::Bgra,
+            capture: Vec::new(),
+            bgra: Vec::new(),
+        }
     }
 
     pub unsafe fn close_map(&mut self) {
@@ -159,6 +306,365 @@ impl MemoryManager {
         (*self.ptr).width = width;
         (*self.ptr).height = height;
     }
+
+    pub unsafe fn get_visibility(&self) -> Visibility {
+        Visibility::from_i32((*self.ptr).visibility)
+    }
+
+    pub unsafe fn set_visibility(&self, visibility: Visibility) {
+        (*self.ptr).visibility = visibility as i32;
+    }
+
+    pub unsafe fn get_key_mode(&self) -> KeyStateMode {
+        KeyStateMode::from_i32((*self.ptr).key_mode).unwrap_or(KeyStateMode::Merged)
+    }
+
+    pub unsafe fn set_key_mode(&self, mode: KeyStateMode) {
+        (*self.ptr).key_mode = mode as i32;
+    }
+
+    pub unsafe fn get_virtual_keys(&self) -> [u8; 256] {
+        (*self.ptr).keys
+    }
+
+    pub unsafe fn get_virtual_key(&self, vk: u8) -> u8 {
+        (*self.ptr).keys[vk as usize]
+    }
+
+    //Called for every key and button the bot presses or releases, from either side.
+    pub unsafe fn set_virtual_key(&self, vk: u8, down: bool) {
+        press_virtual(&mut (*self.ptr).keys, vk, down);
+    }
+
+    pub unsafe fn get_cursor_mode(&self) -> CursorMode {
+        CursorMode::from_i32((*self.ptr).cursor_mode).unwrap_or(CursorMode::Auto)
+    }
+
+    pub unsafe fn set_cursor_mode(&self, mode: CursorMode) {
+        (*self.ptr).cursor_mode = mode as i32;
+    }
+
+    pub unsafe fn get_input_delivery(&self) -> InputDelivery {
+        InputDelivery::from_i32((*self.ptr).input_delivery).unwrap_or(InputDelivery::Messages)
+    }
+
+    pub unsafe fn set_input_delivery(&self, delivery: InputDelivery) {
+        (*self.ptr).input_delivery = delivery as i32;
+    }
+
+    pub unsafe fn get_user_policy(&self) -> (UserInputPolicy, u32) {
+        let policy = UserInputPolicy::from_i32((*self.ptr).user_policy)
+            .unwrap_or(UserInputPolicy::PassThrough);
+        (policy, (*self.ptr).user_idle)
+    }
+
+    pub unsafe fn set_user_policy(&self, policy: UserInputPolicy, idle: u32) {
+        (*self.ptr).user_policy = policy as i32;
+        (*self.ptr).user_idle = idle;
+    }
+
+    //Called by the client for every message from the real mouse or keyboard it lets through.
+    pub unsafe fn user_input(&self) {
+        (*self.ptr).user_input_time = GetTickCount64();
+    }
+
+    pub unsafe fn bot_tags(&self) -> BotTags<'_> {
+        let head = AtomicU32::from_ptr(addr_of_mut!((*self.ptr).bot_head));
+        let words = addr_of_mut!((*self.ptr).bot_messages) as *const AtomicU32;
+        BotTags::new(head, std::slice::from_raw_parts(words, BOT_MESSAGE_WORDS))
+    }
+
+    //Called by the client whenever it reads text from the clipboard.
+    pub unsafe fn clipboard_read(&self) {
+        (*self.ptr).clipboard_reads = (*self.ptr).clipboard_reads.wrapping_add(1);
+    }
+
+    pub unsafe fn get_clipboard_reads(&self) -> u32 {
+        (*self.ptr).clipboard_reads
+    }
+
+    //Called by Simba before it asks the client to start an input log, false if the path doesn't fit.
+    pub unsafe fn set_log_path(&self, path: &str) -> bool {
+        let bytes = path.as_bytes();
+        if bytes.len() >= MAX_LOG_PATH {
+            return false;
+        }
+
+        let mut log_path = [0; MAX_LOG_PATH];
+        log_path[..bytes.len()].copy_from_slice(bytes);
+        (*self.ptr).log_path = log_path;
+        true
+    }
+
+    pub unsafe fn get_log_path(&self) -> String {
+        let log_path = (*self.ptr).log_path;
+        let len = log_path
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(MAX_LOG_PATH);
+        String::from_utf8_lossy(&log_path[..len]).into_owned()
+    }
+
+    //The user owns input from their last input until they've been idle for user_idle ms, never while blocked.
+    pub unsafe fn get_input_owner(&self) -> InputOwner {
+        let (policy, idle) = self.get_user_policy();
+        let last = (*self.ptr).user_input_time;
+        if (policy == UserInputPolicy::Block) || (last == 0) {
+            return InputOwner::Bot;
+        }
+
+        if GetTickCount64().saturating_sub(last) < idle as u64 {
+            InputOwner::User
+        } else {
+            InputOwner::Bot
+        }
+    }
+
+    //True while the bot should hold off because the user is using the client.
+    pub unsafe fn bot_paused(&self) -> bool {
+        (self.get_user_policy().0 == UserInputPolicy::PauseBot)
+            && (self.get_input_owner() == InputOwner::User)
+    }
+
+    pub unsafe fn get_format(&self) -> (PixelFormat, i32) {
+        let format = PixelFormat::from_i32((*self.ptr).format).unwrap_or(PixelFormat::Bgra);
+        (format, (*self.ptr).stride)
+    }
+
+    pub unsafe fn set_format(&self, format: PixelFormat, stride: i32) {
+        (*self.ptr).format = format as i32;
+        (*self.ptr).stride = stride;
+    }
+
+    //The format GetCaptureBuffer hands out, the client is asked for its capture_format.
+    pub fn set_requested_format(&mut self, format: PixelFormat) {
+        self.requested = format;
+    }
+
+    //The latest frame in the requested format as (pointer, stride, format).
+    //Frames the client wrote in another format are converted into a buffer that lives until the next call.
+    pub unsafe fn capture_buffer(&mut self) -> Option<(*mut u8, i32, PixelFormat)> {
+        let (format, stride) = self.get_format();
+        if format == self.requested {
+            return Some((self.image_ptr(), stride, format));
+        }
+
+        let (width, height, bgra) = self.copy_frame()?;
+        self.capture = match self.requested {
+            PixelFormat::Bgra => bgra,
+            requested => convert_frame(&bgra, width, height, width as usize * 4, requested),
+        };
+        let stride = width * self.requested.bytes_per_pixel() as i32;
+        Some((self.capture.as_mut_ptr(), stride, self.requested))
+    }
+
+    //The latest frame as tightly packed BGRA for Simba's target API, converted when the client wrote another format.
+    pub unsafe fn bgra_buffer(&mut self) -> Option<*mut u8> {
+        if self.get_format().0 == PixelFormat::Bgra {
+            return Some(self.image_ptr());
+        }
+
+        let (_width, _height, bgra) = self.copy_frame()?;
+        self.bgra = bgra;
+        Some(self.bgra.as_mut_ptr())
+    }
+
+    //Called by the client for every captured frame.
+    pub unsafe fn set_frame_hashes(&self, frame_hash: u64, tiles: &[u32; TILE_COUNT]) {
+        let now = GetTickCount64();
+        let count = (*self.ptr).frame_count.wrapping_add(1);
+
+        (*self.ptr).frame_count = count;
+        (*self.ptr).frame_time = now;
+        if (*self.ptr).frame_hash != frame_hash {
+            (*self.ptr).frame_hash = frame_hash;
+            (*self.ptr).changed_frame = count;
+        }
+        (*self.ptr).tile_hashes = *tiles;
+    }
+
+    pub unsafe fn get_frame_hash(&self) -> u64 {
+        (*self.ptr).frame_hash
+    }
+
+    pub unsafe fn frames_since_change(&self) -> u32 {
+        (*self.ptr)
+            .frame_count
+            .wrapping_sub((*self.ptr).changed_frame)
+    }
+
+    //A client is frozen if it stopped presenting frames for timeout ms.
+    //A static scene still presents frames, GetFramesSinceChange is for those.
+    //Never frozen before its first frame or while it can't render.
+    pub unsafe fn is_frozen(&self, timeout: u64) -> bool {
+        if ((*self.ptr).frame_count == 0)
+            || matches!(
+                self.get_visibility(),
+                Visibility::Minimized | Visibility::Hidden
+            )
+        {
+            return false;
+        }
+
+        let frame_time = (*self.ptr).frame_time;
+        GetTickCount64().saturating_sub(frame_time) >= timeout
+    }
+
+    pub unsafe fn get_frame_count(&self) -> u32 {
+        (*self.ptr).frame_count
+    }
+
+    pub unsafe fn tile_hashes_ptr(&self) -> *mut u32 {
+        addr_of_mut!((*self.ptr).tile_hashes) as *mut u32
+    }
+
+    unsafe fn frame_seq(&self) -> &AtomicU32 {
+        AtomicU32::from_ptr(addr_of_mut!((*self.ptr).frame_seq))
+    }
+
+    pub unsafe fn begin_frame(&self) {
+        self.frame_seq().fetch_add(1, Ordering::AcqRel);
+    }
+
+    pub unsafe fn end_frame(&self) {
+        self.frame_seq().fetch_add(1, Ordering::Release);
+    }
+
+    //Copies the latest complete frame as BGRA, retrying while the client is in the middle of writing one.
+    pub unsafe fn copy_frame(&self) -> Option<(i32, i32, Vec<u8>)> {
+        for _ in 0..20 {
+            let seq = self.frame_seq().load(Ordering::Acquire);
+            if seq % 2 == 1 {
+                sleep(Duration::from_millis(1));
+                continue;
+            }
+
+            let (width, height) = self.get_dimensions();
+            let (format, stride) = self.get_format();
+            if (width <= 0) || (height <= 0) || (stride <= 0) {
+                return None;
+            }
+
+            let size = (stride * height) as usize;
+            let mut data = vec![0u8; size];
+            copy_nonoverlapping(self.image_ptr(), data.as_mut_ptr(), size);
+
+            if self.frame_seq().load(Ordering::Acquire) == seq {
+                let data = match format {
+                    PixelFormat::Bgra => data,
+                    _ => to_bgra(&data, width, height, stride as usize, format),
+                };
+                return Some((width, height, data));
+            }
+        }
+        None
+    }
+
+    unsafe fn sequence_state(&self) -> &AtomicI32 {
+        AtomicI32::from_ptr(addr_of_mut!((*self.ptr).sequence_state))
+    }
+
+    //Called by Simba, returns the id of the new sequence or None if one is still queued or running.
+    pub unsafe fn submit_sequence(&self, events: &[InputEvent]) -> Option<u32> {
+        let state = SequenceState::from_i32(self.sequence_state().load(Ordering::Acquire));
+        if matches!(state, SequenceState::Pending | SequenceState::Running) {
+            return None;
+        }
+
+        let len = events.len().min(MAX_SEQUENCE_EVENTS);
+        let dest = addr_of_mut!((*self.ptr).sequence) as *mut u8;
+        copy_nonoverlapping(
+            events.as_ptr() as *const u8,
+            dest,
+            len * size_of::<InputEvent>(),
+        );
+
+        let id = (*self.ptr).sequence_id.wrapping_add(1).max(1);
+        (*self.ptr).sequence_len = len as u32;
+        (*self.ptr).sequence_id = id;
+        self.sequence_state()
+            .store(SequenceState::Pending as i32, Ordering::Release);
+        Some(id)
+    }
+
+    //Called by the client, marks the sequence as running and copies its events out.
+    pub unsafe fn take_sequence(&self, id: u32) -> Option<Vec<InputEvent>> {
+        if (*self.ptr).sequence_id != id {
+            return None;
+        }
+
+        self.sequence_state()
+            .compare_exchange(
+                SequenceState::Pending as i32,
+                SequenceState::Running as i32,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .ok()?;
+
+        let len = ((*self.ptr).sequence_len as usize).min(MAX_SEQUENCE_EVENTS);
+        let mut events = vec![InputEvent::default(); len];
+        let src = addr_of_mut!((*self.ptr).sequence) as *const u8;
+        copy_nonoverlapping(
+            src,
+            events.as_mut_ptr() as *mut u8,
+            len * size_of::<InputEvent>(),
+        );
+        Some(events)
+    }
+
+    //Id of the latest sequence, whatever state it's in.
+    pub unsafe fn get_sequence_id(&self) -> u32 {
+        (*self.ptr).sequence_id
+    }
+
+    pub unsafe fn get_sequence_state(&self, id: u32) -> SequenceState {
+        if (*self.ptr).sequence_id != id {
+            return SequenceState::Idle;
+        }
+        SequenceState::from_i32(self.sequence_state().load(Ordering::Acquire))
+    }
+
+    //Only the running sequence can finish, returns false if it was cancelled instead.
+    pub unsafe fn finish_sequence(&self, id: u32) -> bool {
+        (*self.ptr).sequence_id == id
+            && self
+                .sequence_state()
+                .compare_exchange(
+                    SequenceState::Running as i32,
+                    SequenceState::Done as i32,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                )
+                .is_ok()
+    }
+
+    pub unsafe fn cancel_sequence(&self, id: u32) -> bool {
+        if (*self.ptr).sequence_id != id {
+            return false;
+        }
+
+        let state = self.sequence_state();
+        [SequenceState::Pending, SequenceState::Running]
+            .iter()
+            .any(|&from| {
+                state
+                    .compare_exchange(
+                        from as i32,
+                        SequenceState::Cancelled as i32,
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                    )
+                    .is_ok()
+            })
+    }
+
+    pub unsafe fn copy_overlay(&self, width: i32, height: i32) -> Vec<u8> {
+        let size = (width * height * 4) as usize;
+        let mut data = vec![0u8; size];
+        copy_nonoverlapping(self.overlay_ptr(), data.as_mut_ptr(), size);
+        data
+    }
 }
 
 pub static MEMORY_MANAGER: OnceLock<Mutex<MemoryManager>> = OnceLock::new();
@@ -0,0 +1,460 @@
+//Window message parameter encoding.
+use std::time::{Duration, Instant};
+
+use super::layout::{fixed_key, KeyStroke};
+
+pub const WHEEL_DELTA: i32 = 120;
+
+pub const MK_LBUTTON: u16 = 0x0001;
+pub const MK_RBUTTON: u16 = 0x0002;
+pub const MK_SHIFT: u16 = 0x0004;
+pub const MK_CONTROL: u16 = 0x0008;
+pub const MK_MBUTTON: u16 = 0x0010;
+pub const MK_XBUTTON1: u16 = 0x0020;
+pub const MK_XBUTTON2: u16 = 0x0040;
+
+//The windows crate only has these behind Win32_UI_Controls.
+pub const WM_MOUSEHOVER: u32 = 0x02A1;
+pub const WM_MOUSELEAVE: u32 = 0x02A3;
+
+//MAKELPARAM(x, y), both coordinates are truncated to signed 16 bits like GET_X_LPARAM expects.
+pub fn pack_point(x: i32, y: i32) -> isize {
+    let x = x.clamp(i16::MIN as i32, i16::MAX as i32) as i16 as u16 as u32;
+    let y = y.clamp(i16::MIN as i32, i16::MAX as i32) as i16 as u16 as u32;
+    ((y << 16) | x) as isize
+}
+
+//GET_X_LPARAM and GET_Y_LPARAM, the inverse of pack_point.
+pub fn unpack_point(lparam: isize) -> (i32, i32) {
+    let x = (lparam & 0xFFFF) as u16 as i16 as i32;
+    let y = ((lparam >> 16) & 0xFFFF) as u16 as i16 as i32;
+    (x, y)
+}
+
+//Buttons are ordered left, middle, right, X1, X2 like SimbaTarget.mouse.
+pub const BUTTON_COUNT: usize = 5;
+//VK_LBUTTON, VK_MBUTTON, VK_RBUTTON, VK_XBUTTON1 and VK_XBUTTON2.
+pub const BUTTON_KEYS: [u8; BUTTON_COUNT] = [0x01, 0x04, 0x02, 0x05, 0x06];
+
+//Index into the button table for a Simba mouse button, None for the scroll buttons and unknown ones.
+pub fn button_index(button: i32) -> Option<usize> {
+    match button {
+        1 => Some(0), //left
+        2 => Some(1), //middle
+        3 => Some(2), //right
+        6 => Some(3), //X1
+        7 => Some(4), //X2
+        _ => None,
+    }
+}
+
+//Message and wParam for a button going down, up or down as the second click of a double click.
+pub fn button_message(index: usize, down: bool, double: bool, keys: u16) -> (u32, usize) {
+    //down, up and double click messages of each button
+    const MESSAGES: [[u32; 3]; BUTTON_COUNT] = [
+        [0x0201, 0x0202, 0x0203],
+        [0x0207, 0x0208, 0x0209],
+        [0x0204, 0x0205, 0x0206],
+        [0x020B, 0x020C, 0x020D],
+        [0x020B, 0x020C, 0x020D],
+    ];
+
+    let msg = MESSAGES[index][match (down, double) {
+        (true, false) => 0,
+        (false, _) => 1,
+        (true, true) => 2,
+    }];
+
+    //X buttons say which one in the high word
+    let xbutton = match index {
+        3 => 1,
+        4 => 2,
+        _ => 0,
+    };
+    (msg, (xbutton << 16) | keys as usize)
+}
+
+//MK_* flags for the held buttons and keys, keys is indexed by virtual key.
+//Shift and ctrl are set by the generic key or by either side's.
+pub fn mouse_key_state(buttons: &[bool], keys: &[bool]) -> u16 {
+    const FLAGS: [u16; BUTTON_COUNT] =
+        [MK_LBUTTON, MK_MBUTTON, MK_RBUTTON, MK_XBUTTON1, MK_XBUTTON2];
+
+    let held = |vks: [usize; 3]| vks.iter().any(|&vk| keys.get(vk).copied().unwrap_or(false));
+    let shift = held([0x10, 0xA0, 0xA1]);
+    let ctrl = held([0x11, 0xA2, 0xA3]);
+
+    let mut keys = 0;
+    for (&held, flag) in buttons.iter().zip(FLAGS) {
+        if held {
+            keys |= flag;
+        }
+    }
+    if shift {
+        keys |= MK_SHIFT;
+    }
+    if ctrl {
+        keys |= MK_CONTROL;
+    }
+    keys
+}
+
+//WM_MOUSEWHEEL and WM_MOUSEHWHEEL, the delta goes in the high word and the key state in the low one.
+pub fn wheel_wparam(delta: i16, keys: u16) -> usize {
+    (((delta as u16 as u32) << 16) | keys as u32) as usize
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScrollMode {
+    Instant = 0, //one message carrying every notch
+    Notches = 1, //one message per notch
+    Smooth = 2,  //several partial deltas per notch, like high resolution wheels
+}
+
+impl ScrollMode {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(ScrollMode::Instant),
+            1 => Some(ScrollMode::Notches),
+            2 => Some(ScrollMode::Smooth),
+            _ => None,
+        }
+    }
+}
+
+pub const SMOOTH_STEPS: i32 = 4;
+//Longer scrolls are cut to this many notches.
+pub const MAX_SCROLL_NOTCHES: u32 = 1000;
+
+//Splits a scroll of n notches into the wheel deltas of each message.
+pub fn split_scroll(notches: i32, mode: ScrollMode) -> Vec<i16> {
+    let sign = notches.signum();
+    let notches = notches.unsigned_abs().min(MAX_SCROLL_NOTCHES) as i32;
+
+    let (count, step) = match mode {
+        ScrollMode::Instant => {
+            let total = notches * WHEEL_DELTA;
+            let max = (i16::MAX as i32 / WHEEL_DELTA) * WHEEL_DELTA;
+            let mut deltas = vec![(max * sign) as i16; (total / max) as usize];
+            if total % max != 0 {
+                deltas.push(((total % max) * sign) as i16);
+            }
+            return deltas;
+        }
+        ScrollMode::Notches => (notches, WHEEL_DELTA),
+        ScrollMode::Smooth => (notches * SMOOTH_STEPS, WHEEL_DELTA / SMOOTH_STEPS),
+    };
+
+    vec![(step * sign) as i16; count as usize]
+}
+
+pub const WM_KEYDOWN: u32 = 0x0100;
+pub const WM_KEYUP: u32 = 0x0101;
+pub const WM_SYSKEYDOWN: u32 = 0x0104;
+pub const WM_SYSKEYUP: u32 = 0x0105;
+
+const VK_MENU: u8 = 0x12;
+const VK_F10: u8 = 0x79;
+const VK_LMENU: u8 = 0xA4;
+const VK_RMENU: u8 = 0xA5;
+
+//State around a keystroke that ends up in its lParam.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct KeyFlags {
+    pub alt: bool,      //alt is held once this keystroke happens
+    pub ctrl: bool,     //same for ctrl, with alt this is AltGr which types normally
+    pub previous: bool, //the key was already down, set for autorepeat
+    pub repeat: u16,    //0 is treated as 1
+}
+
+fn is_alt(vk: u8) -> bool {
+    matches!(vk, VK_MENU | VK_LMENU | VK_RMENU)
+}
+
+//Message, wParam and lParam for a key going down or up, laid out like the keystroke messages Windows sends.
+pub fn key_message(key: &KeyStroke, down: bool, flags: KeyFlags) -> (u32, usize, isize) {
+    let extended = key.extended || fixed_key(key.vk).is_some_and(|k| k.extended);
+
+    //alt keys themselves are system keys and only carry the context bit while going down
+    let (system, context) = if is_alt(key.vk) {
+        (!flags.ctrl, down && !flags.ctrl)
+    } else {
+        let alt = flags.alt && !flags.ctrl;
+        (alt || (key.vk == VK_F10), alt)
+    };
+
+    let repeat = if down { flags.repeat.max(1) } else { 1 };
+    let previous = !down || flags.previous;
+
+    let mut lparam = (repeat as u32) | ((key.scancode as u32 & 0xFF) << 16);
+    if extended {
+        lparam |= 1 << 24;
+    }
+    if context {
+        lparam |= 1 << 29;
+    }
+    if previous {
+        lparam |= 1 << 30;
+    }
+    if !down {
+        lparam |= 1 << 31;
+    }
+
+    let msg = match (down, system) {
+        (true, false) => WM_KEYDOWN,
+        (false, false) => WM_KEYUP,
+        (true, true) => WM_SYSKEYDOWN,
+        (false, true) => WM_SYSKEYUP,
+    };
+    (msg, key.vk as usize, lparam as isize)
+}
+
+//Decides which presses are the second click of a double click, like Windows does for real input.
+#[derive(Default)]
+pub struct ClickTracker {
+    last: Option<(usize, Instant, i32, i32)>,
+}
+
+pub struct DoubleClickLimits {
+    pub time: Duration,
+    pub width: i32, //total size of the box the second click has to land in
+    pub height: i32,
+}
+
+impl ClickTracker {
+    pub fn press(
+        &mut self,
+        index: usize,
+        now: Instant,
+        x: i32,
+        y: i32,
+        limits: &DoubleClickLimits,
+    ) -> bool {
+        let double = match self.last {
+            Some((last, time, lx, ly)) => {
+                (last == index)
+                    && (now.saturating_duration_since(time) <= limits.time)
+                    && ((x - lx).abs() <= limits.width / 2)
+                    && ((y - ly).abs() <= limits.height / 2)
+            }
+            None => false,
+        };
+
+        //a third click starts over instead of being another double click
+        self.last = if double {
+            None
+        } else {
+            Some((index, now, x, y))
+        };
+        double
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_point_round_trips() {
+        for (x, y) in [
+            (0, 0),
+            (1, 2),
+            (-1, -1),
+            (-5, 300),
+            (765, -20),
+            (i16::MAX as i32, i16::MIN as i32),
+            (i16::MIN as i32, i16::MAX as i32),
+        ] {
+            assert_eq!(unpack_point(pack_point(x, y)), (x, y));
+        }
+
+        assert_eq!(pack_point(0, 0), 0);
+        assert_eq!(pack_point(-1, 0), 0x0000_FFFF);
+        assert_eq!(pack_point(0, -1), 0xFFFF_0000u32 as isize);
+        assert_eq!(pack_point(10, 20), 0x0014_000A);
+    }
+
+    #[test]
+    fn pack_point_clamps_out_of_range() {
+        let max = i16::MAX as i32;
+        let min = i16::MIN as i32;
+        assert_eq!(unpack_point(pack_point(max + 1, min - 1)), (max, min));
+        assert_eq!(unpack_point(pack_point(i32::MAX, i32::MIN)), (max, min));
+        assert_eq!(unpack_point(pack_point(70000, -70000)), (max, min));
+
+        //the upper bits of a 64 bit lParam stay clear
+        assert_eq!(pack_point(i32::MIN, i32::MIN) as usize as u64 >> 32, 0);
+    }
+
+    #[test]
+    fn wheel_wparam_layout() {
+        assert_eq!(wheel_wparam(120, 0), 0x0078_0000);
+        assert_eq!(wheel_wparam(-120, 0), 0xFF88_0000);
+        assert_eq!(wheel_wparam(-1, MK_SHIFT | MK_LBUTTON), 0xFFFF_0005);
+        assert_eq!(wheel_wparam(i16::MIN, 0xFFFF), 0x8000_FFFF);
+
+        //the client reads the delta back with GET_WHEEL_DELTA_WPARAM
+        let wparam = wheel_wparam(-360, MK_CONTROL);
+        assert_eq!((wparam >> 16) as u16 as i16, -360);
+        assert_eq!(wparam as u16, MK_CONTROL);
+    }
+
+    #[test]
+    fn split_scroll_keeps_the_total() {
+        for notches in [-7, -1, 0, 1, 3, 300, 1000] {
+            for mode in [ScrollMode::Instant, ScrollMode::Notches, ScrollMode::Smooth] {
+                let deltas = split_scroll(notches, mode);
+                let total: i32 = deltas.iter().map(|&d| d as i32).sum();
+                assert_eq!(total, notches * WHEEL_DELTA, "{} {:?}", notches, mode);
+                assert!(deltas
+                    .iter()
+                    .all(|&d| (d as i32).signum() == notches.signum()));
+            }
+        }
+    }
+
+    #[test]
+    fn split_scroll_message_counts() {
+        assert_eq!(split_scroll(3, ScrollMode::Notches), [120, 120, 120]);
+        assert_eq!(split_scroll(-2, ScrollMode::Smooth), [-30; 8]);
+        assert_eq!(split_scroll(5, ScrollMode::Instant), [600]);
+        assert!(split_scroll(0, ScrollMode::Notches).is_empty());
+
+        //a single message tops out at the largest multiple of WHEEL_DELTA that fits an i16
+        assert_eq!(split_scroll(300, ScrollMode::Instant), [32760, 3240]);
+        assert_eq!(split_scroll(-273, ScrollMode::Instant), [-32760]);
+    }
+
+    fn stroke(vk: u8, scancode: u16, extended: bool) -> KeyStroke {
+        KeyStroke {
+            vk,
+            scancode,
+            modifiers: 0,
+            extended,
+            dead: false,
+        }
+    }
+
+    #[test]
+    fn key_message_lparam_bits() {
+        let a = stroke(b'A', 0x1E, false);
+
+        let (msg, wparam, lparam) = key_message(&a, true, KeyFlags::default());
+        assert_eq!((msg, wparam), (WM_KEYDOWN, b'A' as usize));
+        assert_eq!(lparam as u32, 0x001E_0001);
+
+        //key up always has the previous state and transition bits and a repeat count of 1
+        let (msg, _, lparam) = key_message(&a, false, KeyFlags::default());
+        assert_eq!(msg, WM_KEYUP);
+        assert_eq!(lparam as u32, 0xC01E_0001);
+
+        //autorepeat
+        let flags = KeyFlags {
+            previous: true,
+            repeat: 5,
+            ..Default::default()
+        };
+        assert_eq!(key_message(&a, true, flags).2 as u32, 0x401E_0005);
+        assert_eq!(key_message(&a, false, flags).2 as u32, 0xC01E_0001);
+    }
+
+    #[test]
+    fn key_message_extended_keys() {
+        //the table says left arrow is extended even when the stroke doesn't
+        let left = stroke(0x25, 0x4B, false);
+        assert_eq!(
+            key_message(&left, true, KeyFlags::default()).2 as u32,
+            0x014B_0001
+        );
+
+        let custom = stroke(b'A', 0x1E, true);
+        assert_eq!(
+            key_message(&custom, true, KeyFlags::default()).2 as u32,
+            0x011E_0001
+        );
+
+        //only the low byte of the scancode fits, the E0 prefix is the extended bit
+        let prefixed = stroke(b'A', 0xE01E, false);
+        assert_eq!(
+            key_message(&prefixed, true, KeyFlags::default()).2 as u32,
+            0x001E_0001
+        );
+    }
+
+    #[test]
+    fn key_message_system_keys() {
+        let a = stroke(b'A', 0x1E, false);
+        let alt = stroke(VK_MENU, 0x38, false);
+        let held_alt = KeyFlags {
+            alt: true,
+            ..Default::default()
+        };
+        let altgr = KeyFlags {
+            alt: true,
+            ctrl: true,
+            ..Default::default()
+        };
+
+        //alt+A is a system key with the context bit, AltGr+A types normally
+        let (msg, _, lparam) = key_message(&a, true, held_alt);
+        assert_eq!((msg, lparam as u32), (WM_SYSKEYDOWN, 0x201E_0001));
+        let (msg, _, lparam) = key_message(&a, false, held_alt);
+        assert_eq!((msg, lparam as u32), (WM_SYSKEYUP, 0xE01E_0001));
+        let (msg, _, lparam) = key_message(&a, true, altgr);
+        assert_eq!((msg, lparam as u32), (WM_KEYDOWN, 0x001E_0001));
+
+        //alt itself only has the context bit going down
+        let (msg, _, lparam) = key_message(&alt, true, held_alt);
+        assert_eq!((msg, lparam as u32), (WM_SYSKEYDOWN, 0x2038_0001));
+        let (msg, _, lparam) = key_message(&alt, false, KeyFlags::default());
+        assert_eq!((msg, lparam as u32), (WM_SYSKEYUP, 0xC038_0001));
+        assert_eq!(key_message(&alt, true, altgr).0, WM_KEYDOWN);
+
+        //F10 is always a system key but without the context bit
+        let f10 = stroke(VK_F10, 0x44, false);
+        let (msg, _, lparam) = key_message(&f10, true, KeyFlags::default());
+        assert_eq!((msg, lparam as u32), (WM_SYSKEYDOWN, 0x0044_0001));
+    }
+
+    #[test]
+    fn mouse_key_state_sides() {
+        let mut keys = [false; 255];
+        assert_eq!(mouse_key_state(&[false; BUTTON_COUNT], &keys), 0);
+
+        for (vk, flag) in [
+            (0x10, MK_SHIFT),
+            (0xA0, MK_SHIFT),
+            (0xA1, MK_SHIFT),
+            (0x11, MK_CONTROL),
+            (0xA2, MK_CONTROL),
+            (0xA3, MK_CONTROL),
+        ] {
+            keys[vk] = true;
+            assert_eq!(mouse_key_state(&[false; BUTTON_COUNT], &keys), flag);
+            keys[vk] = false;
+        }
+
+        keys[0xA1] = true;
+        keys[0xA2] = true;
+        let buttons = [true, false, true, false, true];
+        assert_eq!(
+            mouse_key_state(&buttons, &keys),
+            MK_LBUTTON | MK_RBUTTON | MK_XBUTTON2 | MK_SHIFT | MK_CONTROL
+        );
+    }
+
+    #[test]
+    fn split_scroll_clamps_huge_counts() {
+        let max = MAX_SCROLL_NOTCHES as i32;
+        for notches in [i32::MAX, i32::MIN, i32::MIN + 1, max + 1] {
+            for mode in [ScrollMode::Instant, ScrollMode::Notches, ScrollMode::Smooth] {
+                let total: i32 = split_scroll(notches, mode).iter().map(|&d| d as i32).sum();
+                assert_eq!(total, notches.signum() * max * WHEEL_DELTA);
+            }
+        }
+        assert_eq!(
+            split_scroll(i32::MAX, ScrollMode::Smooth).len(),
+            MAX_SCROLL_NOTCHES as usize * SMOOTH_STEPS as usize
+        );
+    }
+}
@@ -1,4 +1,13 @@
+pub mod autorepeat;
+pub mod botinput;
+pub mod image;
+pub mod inputlog;
+pub mod keystate;
+pub mod layout;
 pub mod main;
 pub mod memory;
+pub mod message;
+pub mod rawinput;
+pub mod sequence;
 pub mod sync;
 pub mod windows;
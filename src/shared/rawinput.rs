@@ -0,0 +1,151 @@
+//Raw input records the client hands out through its GetRawInputData and GetRawInputBuffer hooks.
+use std::mem::size_of;
+
+use super::message::{pack_point, unpack_point, BUTTON_COUNT};
+
+pub const RIM_INPUT: usize = 0;
+pub const RIM_TYPEMOUSE: u32 = 0;
+pub const RIM_TYPEKEYBOARD: u32 = 1;
+pub const RID_INPUT: u32 = 0x10000003;
+pub const RID_HEADER: u32 = 0x10000005;
+
+pub const RI_KEY_BREAK: u16 = 0x01;
+pub const RI_KEY_E0: u16 = 0x02;
+
+pub const RI_MOUSE_WHEEL: u16 = 0x0400;
+pub const RI_MOUSE_HWHEEL: u16 = 0x0800;
+//Down flags of left, middle, right, X1 and X2, the up flag is always the next bit.
+const RI_MOUSE_BUTTON_DOWN: [u16; BUTTON_COUNT] = [0x0001, 0x0010, 0x0004, 0x0040, 0x0100];
+
+//RAWMOUSE and RAWKEYBOARD sizes, the same on 32 and 64 bit.
+const RAWMOUSE_SIZE: usize = 24;
+const RAWKEYBOARD_SIZE: usize = 16;
+
+//How bot input reaches the client.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputDelivery {
+    Messages = 0,
+    RawInput = 1,
+    Both = 2,
+}
+
+impl InputDelivery {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(InputDelivery::Messages),
+            1 => Some(InputDelivery::RawInput),
+            2 => Some(InputDelivery::Both),
+            _ => None,
+        }
+    }
+
+    pub fn messages(self) -> bool {
+        self != InputDelivery::RawInput
+    }
+
+    pub fn raw(self) -> bool {
+        self != InputDelivery::Messages
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RawMouse {
+    pub button_flags: u16,
+    pub button_data: u16,
+    pub dx: i32,
+    pub dy: i32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RawKey {
+    pub make_code: u16,
+    pub flags: u16,
+    pub vk: u16,
+    pub message: u32,
+}
+
+pub fn button_flags(index: usize, down: bool) -> u16 {
+    let flag = RI_MOUSE_BUTTON_DOWN[index];
+    if down {
+        flag
+    } else {
+        flag << 1
+    }
+}
+
+//wParam and lParam of WI_RAW_MOUSE, deltas are limited to 16 bits like every other point.
+pub fn encode_mouse(mouse: &RawMouse) -> (usize, isize) {
+    let wparam = (mouse.button_flags as u32) | ((mouse.button_data as u32) << 16);
+    (wparam as usize, pack_point(mouse.dx, mouse.dy))
+}
+
+pub fn decode_mouse(wparam: usize, lparam: isize) -> RawMouse {
+    let (dx, dy) = unpack_point(lparam);
+    RawMouse {
+        button_flags: (wparam & 0xFFFF) as u16,
+        button_data: ((wparam >> 16) & 0xFFFF) as u16,
+        dx,
+        dy,
+    }
+}
+
+//wParam and lParam of WI_RAW_KEY.
+pub fn encode_key(key: &RawKey) -> (usize, isize) {
+    let wparam = (key.make_code as u32) | ((key.flags as u32) << 16);
+    let lparam = (key.vk as u32) | (key.message << 16);
+    (wparam as usize, lparam as isize)
+}
+
+pub fn decode_key(wparam: usize, lparam: isize) -> RawKey {
+    RawKey {
+        make_code: (wparam & 0xFFFF) as u16,
+        flags: ((wparam >> 16) & 0xFFFF) as u16,
+        vk: (lparam & 0xFFFF) as u16,
+        message: ((lparam >> 16) & 0xFFFF) as u32,
+    }
+}
+
+//RAWINPUTHEADER is two DWORDs followed by a HANDLE and a WPARAM.
+pub fn header_size() -> usize {
+    8 + 2 * size_of::<usize>()
+}
+
+//GetRawInputBuffer places records on 8 byte boundaries.
+pub fn align_record(size: usize) -> usize {
+    (size + 7) & !7
+}
+
+fn header(kind: u32, body: usize) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(header_size() + body);
+    bytes.extend_from_slice(&kind.to_le_bytes());
+    bytes.extend_from_slice(&((header_size() + body) as u32).to_le_bytes());
+    bytes.extend_from_slice(&0usize.to_le_bytes()); //hDevice, no real device behind the record
+    bytes.extend_from_slice(&RIM_INPUT.to_le_bytes());
+    bytes
+}
+
+//Complete RAWINPUT of a mouse record.
+pub fn mouse_record(mouse: &RawMouse) -> Vec<u8> {
+    let mut bytes = header(RIM_TYPEMOUSE, RAWMOUSE_SIZE);
+    bytes.extend_from_slice(&0u16.to_le_bytes()); //usFlags, MOUSE_MOVE_RELATIVE
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+    bytes.extend_from_slice(&mouse.button_flags.to_le_bytes());
+    bytes.extend_from_slice(&mouse.button_data.to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes()); //ulRawButtons
+    bytes.extend_from_slice(&mouse.dx.to_le_bytes());
+    bytes.extend_from_slice(&mouse.dy.to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes()); //ulExtraInformation
+    bytes
+}
+
+//Complete RAWINPUT of a keyboard record.
+pub fn key_record(key: &RawKey) -> Vec<u8> {
+    let mut bytes = header(RIM_TYPEKEYBOARD, RAWKEYBOARD_SIZE);
+    bytes.extend_from_slice(&key.make_code.to_le_bytes());
+    bytes.extend_from_slice(&key.flags.to_le_bytes());
+    bytes.extend_from_slice(&0u16.to_le_bytes()); //Reserved
+    bytes.extend_from_slice(&key.vk.to_le_bytes());
+    bytes.extend_from_slice(&key.message.to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes()); //ExtraInformation
+    bytes
+}
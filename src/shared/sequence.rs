@@ -0,0 +1,193 @@
+//Input sequences submitted by Simba and replayed by the client.
+use super::message::button_index;
+
+pub const MAX_SEQUENCE_EVENTS: usize = 4096;
+
+//Wait for the next rendered frame before running the event.
+pub const FLAG_ALIGN_FRAME: u32 = 1 << 0;
+//Scroll events go sideways instead of up and down.
+pub const FLAG_HORIZONTAL: u32 = 1 << 1;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EventKind {
+    Move = 0,       //x, y
+    ButtonDown = 1, //data is the Simba mouse button, scroll buttons aren't allowed
+    ButtonUp = 2,
+    KeyDown = 3, //data is the virtual key
+    KeyUp = 4,
+    Scroll = 5, //data is the notches, positive goes down or right
+}
+
+impl EventKind {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(EventKind::Move),
+            1 => Some(EventKind::ButtonDown),
+            2 => Some(EventKind::ButtonUp),
+            3 => Some(EventKind::KeyDown),
+            4 => Some(EventKind::KeyUp),
+            5 => Some(EventKind::Scroll),
+            _ => None,
+        }
+    }
+}
+
+//Same layout as TInputEvent on the Pascal side.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct InputEvent {
+    pub kind: i32,
+    pub time: u32, //milliseconds since the sequence started
+    pub x: i32,
+    pub y: i32,
+    pub data: i32,
+    pub flags: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SequenceState {
+    Idle = 0, //also returned for ids that aren't the latest sequence
+    Pending = 1,
+    Running = 2,
+    Done = 3,
+    Cancelled = 4,
+}
+
+impl SequenceState {
+    pub fn from_i32(value: i32) -> Self {
+        match value {
+            1 => SequenceState::Pending,
+            2 => SequenceState::Running,
+            3 => SequenceState::Done,
+            4 => SequenceState::Cancelled,
+            _ => SequenceState::Idle,
+        }
+    }
+}
+
+//Checks a sequence before it's handed to the client.
+pub fn validate_sequence(events: &[InputEvent]) -> Result<(), String> {
+    if events.is_empty() {
+        return Err("Sequence is empty".to_string());
+    }
+
+    if events.len() > MAX_SEQUENCE_EVENTS {
+        return Err(format!(
+            "Sequence has {} events, the limit is {}",
+            events.len(),
+            MAX_SEQUENCE_EVENTS
+        ));
+    }
+
+    let mut time = 0;
+    for (i, event) in events.iter().enumerate() {
+        let kind = match EventKind::from_i32(event.kind) {
+            Some(kind) => kind,
+            None => return Err(format!("Event {} has unknown kind {}", i, event.kind)),
+        };
+
+        let valid = match kind {
+            EventKind::ButtonDown | EventKind::ButtonUp => button_index(event.data).is_some(),
+            EventKind::KeyDown | EventKind::KeyUp => (1..255).contains(&event.data),
+            _ => true,
+        };
+        if !valid {
+            return Err(format!("Event {} has invalid data {}", i, event.data));
+        }
+
+        if event.time < time {
+            return Err(format!("Event {} happens before the previous one", i));
+        }
+        time = event.time;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: EventKind, time: u32, data: i32) -> InputEvent {
+        InputEvent {
+            kind: kind as i32,
+            time,
+            data,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn valid_sequences_pass() {
+        let events = [
+            event(EventKind::Move, 0, 0),
+            event(EventKind::ButtonDown, 0, 1),
+            event(EventKind::ButtonUp, 40, 1),
+            event(EventKind::ButtonDown, 40, 7),
+            event(EventKind::ButtonUp, 90, 7),
+            event(EventKind::KeyDown, 90, 1),
+            event(EventKind::KeyUp, 120, 254),
+            event(EventKind::Scroll, 120, -3),
+        ];
+        assert_eq!(validate_sequence(&events), Ok(()));
+        assert!(
+            validate_sequence(&vec![event(EventKind::Move, 0, 0); MAX_SEQUENCE_EVENTS]).is_ok()
+        );
+    }
+
+    #[test]
+    fn empty_and_oversized_sequences_fail() {
+        assert!(validate_sequence(&[]).is_err());
+        let events = vec![event(EventKind::Move, 0, 0); MAX_SEQUENCE_EVENTS + 1];
+        assert!(validate_sequence(&events).is_err());
+    }
+
+    #[test]
+    fn out_of_range_data_fails() {
+        for key in [-1, 0, 255, 256, 0x141, i32::MIN, i32::MAX] {
+            for kind in [EventKind::KeyDown, EventKind::KeyUp] {
+                assert!(
+                    validate_sequence(&[event(kind, 0, key)]).is_err(),
+                    "{}",
+                    key
+                );
+            }
+        }
+
+        //scroll buttons and unknown ones
+        for button in [-1, 0, 4, 5, 8, 255] {
+            for kind in [EventKind::ButtonDown, EventKind::ButtonUp] {
+                assert!(
+                    validate_sequence(&[event(kind, 0, button)]).is_err(),
+                    "{}",
+                    button
+                );
+            }
+        }
+
+        let unknown = InputEvent {
+            kind: 6,
+            ..Default::default()
+        };
+        assert!(validate_sequence(&[unknown]).is_err());
+    }
+
+    #[test]
+    fn times_have_to_be_in_order() {
+        let events = [
+            event(EventKind::KeyDown, 100, 65),
+            event(EventKind::KeyUp, 99, 65),
+        ];
+        assert_eq!(
+            validate_sequence(&events),
+            Err("Event 1 happens before the previous one".to_string())
+        );
+
+        let events = [
+            event(EventKind::Move, 0, 0),
+            event(EventKind::Move, 50, 0),
+            event(EventKind::Move, 50, 0),
+            event(EventKind::Move, 10, 0),
+        ];
+        assert!(validate_sequence(&events).is_err());
+    }
+}
@@ -1,46 +1,90 @@
 use std::{
     cell::RefCell,
     ffi::{c_char, c_int, c_void},
-    mem::transmute,
+    mem::{size_of, transmute},
+    ptr::copy_nonoverlapping,
     slice::from_raw_parts,
-    thread::sleep,
-    time::Duration,
+    sync::{mpsc::channel, OnceLock},
+    thread::{sleep, spawn},
+    time::{Duration, Instant},
 };
 
 use windows::{
-    core::{s, BOOL, PCSTR},
+    core::{s, w, BOOL, PCSTR},
     Win32::{
         Foundation::{
-            CloseHandle, FALSE, HWND, LPARAM, POINT, TRUE, WAIT_OBJECT_0, WAIT_TIMEOUT, WPARAM,
+            CloseHandle, GlobalFree, FALSE, HANDLE, HGLOBAL, HWND, LPARAM, LRESULT, POINT, RECT,
+            TRUE, WAIT_OBJECT_0, WAIT_TIMEOUT, WPARAM,
+        },
+        Graphics::{
+            Dwm::{DwmGetWindowAttribute, DWMWA_CLOAKED},
+            Gdi::{
+                ClientToScreen, CombineRgn, CreateRectRgn, CreateRectRgnIndirect, DeleteObject,
+                MapWindowPoints, ScreenToClient, NULLREGION, RGN_DIFF,
+            },
         },
-        Graphics::Gdi::ScreenToClient,
         System::{
+            DataExchange::{
+                CloseClipboard, EmptyClipboard, EnumClipboardFormats, GetClipboardData,
+                GetClipboardSequenceNumber, OpenClipboard, SetClipboardData,
+            },
             Diagnostics::Debug::WriteProcessMemory,
             LibraryLoader::{GetModuleHandleA, GetProcAddress},
             Memory::{
-                VirtualAllocEx, VirtualFreeEx, MEM_COMMIT, MEM_RELEASE, MEM_RESERVE, PAGE_READWRITE,
+                GlobalAlloc, GlobalLock, GlobalSize, GlobalUnlock, VirtualAllocEx, VirtualFreeEx,
+                GMEM_MOVEABLE, MEM_COMMIT, MEM_RELEASE, MEM_RESERVE, PAGE_READWRITE,
             },
             Threading::{CreateRemoteThread, OpenProcess, WaitForSingleObject, PROCESS_ALL_ACCESS},
         },
         UI::{
             Input::KeyboardAndMouse::{
-                EnableWindow, IsWindowEnabled, MapVirtualKeyA, VkKeyScanA, MAPVK_VK_TO_VSC,
+                EnableWindow, GetDoubleClickTime, GetKeyboardLayout, IsWindowEnabled,
+                MapVirtualKeyExW, VkKeyScanExW, HKL, MAPVK_VK_TO_CHAR, MAPVK_VK_TO_VSC_EX,
             },
             WindowsAndMessaging::{
-                EnumChildWindows, EnumWindows, GetClassNameW, GetCursorPos,
-                GetWindowThreadProcessId, PostMessageW, WM_KEYDOWN, WM_KEYUP, WM_LBUTTONDOWN,
-                WM_LBUTTONUP, WM_MBUTTONDOWN, WM_MBUTTONUP, WM_MOUSEMOVE, WM_RBUTTONDOWN,
-                WM_RBUTTONUP, WM_USER,
+                CreateWindowExW, DefWindowProcW, DispatchMessageW, EnumChildWindows, EnumWindows,
+                GetAncestor, GetClassLongW, GetClassNameW, GetClientRect, GetCursorPos,
+                GetMessageW, GetSystemMetrics, GetWindow, GetWindowRect, GetWindowThreadProcessId,
+                IsIconic, IsWindowVisible, PostMessageW, RegisterClassW, SendMessageTimeoutW,
+                SystemParametersInfoW, CS_DBLCLKS, GA_ROOT, GCL_STYLE, GW_HWNDPREV, HWND_MESSAGE,
+                MSG, SMTO_ABORTIFHUNG, SM_CXDOUBLECLK, SM_CYDOUBLECLK, SPI_GETKEYBOARDDELAY,
+                SPI_GETKEYBOARDSPEED, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS, UNICODE_NOCHAR,
+                WINDOW_EX_STYLE, WINDOW_STYLE, WM_CHAR, WM_MOUSEHWHEEL, WM_MOUSEMOVE,
+                WM_MOUSEWHEEL, WM_UNICHAR, WM_USER, WNDCLASSW,
             },
         },
     },
 };
 
-use super::main::MODULE;
+use super::{
+    autorepeat::RepeatTiming,
+    image::PixelFormat,
+    layout::{key_sequence, KeyStroke, KeyboardLayout, Layout},
+    main::MODULE,
+    memory::{Visibility, MEMORY_MANAGER},
+    message::{
+        button_message, key_message, pack_point, wheel_wparam, DoubleClickLimits, KeyFlags,
+        BUTTON_KEYS,
+    },
+    rawinput::{
+        button_flags, encode_key, encode_mouse, InputDelivery, RawKey, RawMouse, RI_KEY_BREAK,
+        RI_KEY_E0, RI_MOUSE_HWHEEL, RI_MOUSE_WHEEL,
+    },
+};
+
+//The windows crate only has this behind Win32_System_Ole.
+pub const CF_UNICODETEXT: u32 = 13;
 
 pub const WI_CONSOLE: u32 = WM_USER + 1;
 pub const WI_MODIFIERS: u32 = WM_USER + 3;
 pub const WI_DETACH: u32 = WM_USER + 4;
+pub const WI_CAPTURE_FORMAT: u32 = WM_USER + 5;
+pub const WI_SEQUENCE: u32 = WM_USER + 6;
+pub const WI_RELEASE: u32 = WM_USER + 7;
+pub const WI_FOCUS: u32 = WM_USER + 8;
+pub const WI_RAW_MOUSE: u32 = WM_USER + 9;
+pub const WI_RAW_KEY: u32 = WM_USER + 10;
+pub const WI_INPUT_LOG: u32 = WM_USER + 11;
 
 pub unsafe fn get_proc_address(name: *const c_char) -> *mut c_void {
     let name_str = PCSTR::from_raw(name as *const u8);
@@ -205,11 +249,122 @@ pub fn toggle_input(hwnd: u64, state: bool) -> bool {
     unsafe { EnableWindow(HWND(hwnd as *mut c_void), state).as_bool() }
 }
 
+pub fn is_minimized(hwnd: u64) -> bool {
+    unsafe {
+        let root = GetAncestor(HWND(hwnd as *mut c_void), GA_ROOT);
+        IsIconic(root).as_bool()
+    }
+}
+
+pub fn is_visible(hwnd: u64) -> bool {
+    unsafe { IsWindowVisible(HWND(hwnd as *mut c_void)).as_bool() }
+}
+
+//Cloaked windows, like those on other virtual desktops, count as visible but aren't drawn.
+fn is_cloaked(hwnd: HWND) -> bool {
+    let mut cloaked = 0u32;
+    let size = size_of::<u32>() as u32;
+    let attribute = &mut cloaked as *mut u32 as *mut c_void;
+    unsafe { DwmGetWindowAttribute(hwnd, DWMWA_CLOAKED, attribute, size) }.is_ok() && cloaked != 0
+}
+
+//True if the windows above the client's root window cover all of its client area.
+pub fn is_occluded(hwnd: u64) -> bool {
+    let hwnd = HWND(hwnd as *mut c_void);
+    let mut rect = RECT::default();
+    if unsafe { GetClientRect(hwnd, &mut rect) }.is_err() {
+        return false;
+    }
+
+    let mut corners = [
+        POINT {
+            x: rect.left,
+            y: rect.top,
+        },
+        POINT {
+            x: rect.right,
+            y: rect.bottom,
+        },
+    ];
+    unsafe { MapWindowPoints(Some(hwnd), None, &mut corners) };
+
+    unsafe {
+        let uncovered = CreateRectRgn(corners[0].x, corners[0].y, corners[1].x, corners[1].y);
+        let mut occluded = false;
+        let mut above = GetWindow(GetAncestor(hwnd, GA_ROOT), GW_HWNDPREV);
+
+        while let Ok(window) = above {
+            let mut rect = RECT::default();
+            if IsWindowVisible(window).as_bool()
+                && !IsIconic(window).as_bool()
+                && !is_cloaked(window)
+                && GetWindowRect(window, &mut rect).is_ok()
+            {
+                let region = CreateRectRgnIndirect(&rect);
+                let left = CombineRgn(Some(uncovered), Some(uncovered), Some(region), RGN_DIFF);
+                let _ = DeleteObject(region.into());
+                if left == NULLREGION {
+                    occluded = true;
+                    break;
+                }
+            }
+            above = GetWindow(window, GW_HWNDPREV);
+        }
+
+        let _ = DeleteObject(uncovered.into());
+        occluded
+    }
+}
+
+//The one place visibility is worked out, the client publishes it and Simba asks it directly.
+pub fn window_visibility(hwnd: u64) -> Visibility {
+    if is_minimized(hwnd) {
+        Visibility::Minimized
+    } else if !is_visible(hwnd) {
+        Visibility::Hidden
+    } else if is_occluded(hwnd) {
+        Visibility::Occluded
+    } else {
+        Visibility::Visible
+    }
+}
+
 pub fn open_console(hwnd: u64) {
     let hwnd = Some(HWND(hwnd as *mut c_void));
     let _ = unsafe { PostMessageW(hwnd, WI_CONSOLE, WPARAM(0), LPARAM(0)) };
 }
 
+pub fn set_capture_format(hwnd: u64, format: PixelFormat) -> bool {
+    let hwnd = Some(HWND(hwnd as *mut c_void));
+    let wparam = WPARAM(format as usize);
+    unsafe { PostMessageW(hwnd, WI_CAPTURE_FORMAT, wparam, LPARAM(0)) }.is_ok()
+}
+
+//Tells the client a sequence with this id was written to the shared memory, keys are mapped with layout.
+pub fn start_sequence(hwnd: u64, id: u32, layout: Layout) -> bool {
+    let hwnd = Some(HWND(hwnd as *mut c_void));
+    let (wparam, lparam) = (WPARAM(id as usize), LPARAM(layout as isize));
+    unsafe { PostMessageW(hwnd, WI_SEQUENCE, wparam, lparam) }.is_ok()
+}
+
+//Asks the client to let go of the modifiers toggled with WI_MODIFIERS.
+pub fn release_modifiers(hwnd: u64) {
+    let hwnd = Some(HWND(hwnd as *mut c_void));
+    let _ = unsafe { PostMessageW(hwnd, WI_RELEASE, WPARAM(0), LPARAM(0)) };
+}
+
+//Turns the client's focus emulation on or off.
+pub fn fake_focus(hwnd: u64, enabled: bool) -> bool {
+    let hwnd = Some(HWND(hwnd as *mut c_void));
+    unsafe { PostMessageW(hwnd, WI_FOCUS, WPARAM(enabled as usize), LPARAM(0)) }.is_ok()
+}
+
+//Starts the client's input log at the path in shared memory or stops it.
+pub fn input_log(hwnd: u64, start: bool) -> bool {
+    let hwnd = Some(HWND(hwnd as *mut c_void));
+    unsafe { PostMessageW(hwnd, WI_INPUT_LOG, WPARAM(start as usize), LPARAM(0)) }.is_ok()
+}
+
 //mouse
 pub fn get_mouse_position(hwnd: u64) -> Option<POINT> {
     let mut point = POINT::default();
@@ -224,118 +379,229 @@ pub fn get_mouse_position(hwnd: u64) -> Option<POINT> {
     None
 }
 
-pub fn mouse_move(hwnd: u64, x: i32, y: i32) {
-    let hwnd = HWND(hwnd as *mut c_void);
-    let lparam = (y << 16) | x;
-    unsafe {
-        let _ = PostMessageW(Some(hwnd), WM_MOUSEMOVE, WPARAM(0), LPARAM(lparam as isize));
+//How bot input should reach the client, messages until shared memory says otherwise.
+fn input_delivery() -> InputDelivery {
+    match MEMORY_MANAGER.get() {
+        Some(mem_manager) => unsafe { mem_manager.lock().unwrap().get_input_delivery() },
+        None => InputDelivery::Messages,
     }
 }
 
-pub fn lbutton(hwnd: u64, down: bool, x: i32, y: i32) {
-    let hwnd = HWND(hwnd as *mut c_void);
-    let lparam = (y << 16) | x;
-    unsafe {
-        let _ = PostMessageW(
-            Some(hwnd),
-            if down { WM_LBUTTONDOWN } else { WM_LBUTTONUP },
-            WPARAM(0),
-            LPARAM(lparam as isize),
-        );
+//Tags the message before posting it so the client knows it's the bot's and not the user's.
+fn post_input(hwnd: HWND, msg: u32, wparam: usize, lparam: isize) {
+    if let Some(mem_manager) = MEMORY_MANAGER.get() {
+        let mem_manager = mem_manager.lock().unwrap();
+        unsafe { mem_manager.bot_tags() }.tag(msg, wparam as u32, lparam as u32);
     }
+    let _ = unsafe { PostMessageW(Some(hwnd), msg, WPARAM(wparam), LPARAM(lparam)) };
+}
+
+fn post_raw_mouse(hwnd: HWND, mouse: RawMouse) {
+    let (wparam, lparam) = encode_mouse(&mouse);
+    let _ = unsafe { PostMessageW(Some(hwnd), WI_RAW_MOUSE, WPARAM(wparam), LPARAM(lparam)) };
 }
 
-pub fn mbutton(hwnd: u64, down: bool, x: i32, y: i32) {
+fn post_raw_key(hwnd: HWND, key: RawKey) {
+    let (wparam, lparam) = encode_key(&key);
+    let _ = unsafe { PostMessageW(Some(hwnd), WI_RAW_KEY, WPARAM(wparam), LPARAM(lparam)) };
+}
+
+pub fn mouse_move(hwnd: u64, x: i32, y: i32, keys: u16) {
     let hwnd = HWND(hwnd as *mut c_void);
-    let lparam = (y << 16) | x;
-    unsafe {
-        let _ = PostMessageW(
-            Some(hwnd),
-            if down { WM_MBUTTONDOWN } else { WM_MBUTTONUP },
-            WPARAM(0),
-            LPARAM(lparam as isize),
+    let delivery = input_delivery();
+
+    if delivery.raw() {
+        //raw input only has deltas, and without WM_MOUSEMOVE the client won't record the position itself
+        let (dx, dy) = match MEMORY_MANAGER.get() {
+            Some(mem_manager) => {
+                let mem_manager = mem_manager.lock().unwrap();
+                let (px, py) = unsafe { mem_manager.get_mouse_position() };
+                unsafe { mem_manager.set_mouse_position(x, y) };
+                if (px == -1) || (py == -1) {
+                    (0, 0)
+                } else {
+                    (x - px, y - py)
+                }
+            }
+            None => (0, 0),
+        };
+
+        post_raw_mouse(
+            hwnd,
+            RawMouse {
+                button_flags: 0,
+                button_data: 0,
+                dx,
+                dy,
+            },
         );
     }
+
+    if !delivery.messages() {
+        return;
+    }
+
+    post_input(hwnd, WM_MOUSEMOVE, keys as usize, pack_point(x, y));
 }
 
-pub fn rbutton(hwnd: u64, down: bool, x: i32, y: i32) {
+//keys is the MK_* state after the button changed, so downs include their own button and ups don't.
+pub fn button(hwnd: u64, index: usize, down: bool, double: bool, x: i32, y: i32, keys: u16) {
+    set_virtual_key(BUTTON_KEYS[index], down);
     let hwnd = HWND(hwnd as *mut c_void);
-    let lparam = (y << 16) | x;
-    unsafe {
-        let _ = PostMessageW(
-            Some(hwnd),
-            if down { WM_RBUTTONDOWN } else { WM_RBUTTONUP },
-            WPARAM(0),
-            LPARAM(lparam as isize),
+    let delivery = input_delivery();
+
+    if delivery.raw() {
+        post_raw_mouse(
+            hwnd,
+            RawMouse {
+                button_flags: button_flags(index, down),
+                button_data: 0,
+                dx: 0,
+                dy: 0,
+            },
         );
     }
+
+    if !delivery.messages() {
+        return;
+    }
+
+    let (msg, wparam) = button_message(index, down, double, keys);
+    post_input(hwnd, msg, wparam, pack_point(x, y));
 }
 
-pub fn scroll(hwnd: u64, down: bool, scrolls: i32, x: i32, y: i32) {
-    //let hwnd = HWND(hwnd as *mut c_void);
-    let lparam = (y << 16) | x;
-    print!(
-        "[WaspInput]: TODO: scroll direction: {}, hwnd: {}, scrolls: {}, lparam: {}\r\n",
-        down, hwnd, scrolls, lparam
-    );
+//Windows only turns a second click into a double click for classes that ask for it.
+pub fn has_double_clicks(hwnd: u64) -> bool {
+    let style = unsafe { GetClassLongW(HWND(hwnd as *mut c_void), GCL_STYLE) };
+    (style & CS_DBLCLKS.0) != 0
 }
 
-//keyboard
-pub fn key_down(hwnd: u64, vkey: i32) {
-    let hwnd = HWND(hwnd as *mut c_void);
+pub fn double_click_limits() -> DoubleClickLimits {
     unsafe {
-        let key = vkey & 0xFF;
-        let scancode = MapVirtualKeyA(key as u32, MAPVK_VK_TO_VSC);
-        let lparam = 1 | (scancode << 16) | (0 << 24);
-        let _ = PostMessageW(
-            Some(hwnd),
-            WM_KEYDOWN,
-            WPARAM(key as usize),
-            LPARAM(lparam as isize),
-        );
+        DoubleClickLimits {
+            time: Duration::from_millis(GetDoubleClickTime() as u64),
+            width: GetSystemMetrics(SM_CXDOUBLECLK),
+            height: GetSystemMetrics(SM_CYDOUBLECLK),
+        }
+    }
+}
+
+//The user's keyboard repeat settings, Windows' defaults if they can't be read.
+pub fn keyboard_repeat() -> RepeatTiming {
+    let read = |action| {
+        let mut value = 0u32;
+        unsafe {
+            SystemParametersInfoW(
+                action,
+                0,
+                Some(&mut value as *mut u32 as *mut c_void),
+                SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+            )
+        }
+        .ok()
+        .map(|_| value)
+    };
+
+    match (read(SPI_GETKEYBOARDDELAY), read(SPI_GETKEYBOARDSPEED)) {
+        (Some(delay), Some(speed)) => RepeatTiming::from_system(delay, speed),
+        _ => RepeatTiming::default(),
     }
 }
 
-pub fn key_up(hwnd: u64, vkey: i32) {
+//Wheel messages carry screen coordinates unlike every other mouse message.
+pub fn scroll(hwnd: u64, delta: i16, horizontal: bool, keys: u16, x: i32, y: i32) {
     let hwnd = HWND(hwnd as *mut c_void);
-    unsafe {
-        let key = vkey & 0xFF;
-        let scancode = MapVirtualKeyA(key as u32, MAPVK_VK_TO_VSC);
-        let lparam = 1 | (scancode << 16) | (0 << 24) | (1 << 30) | (1 << 31);
-        let _ = PostMessageW(
-            Some(hwnd),
-            WM_KEYUP,
-            WPARAM(key as usize),
-            LPARAM(lparam as isize),
+    let delivery = input_delivery();
+
+    if delivery.raw() {
+        post_raw_mouse(
+            hwnd,
+            RawMouse {
+                button_flags: if horizontal {
+                    RI_MOUSE_HWHEEL
+                } else {
+                    RI_MOUSE_WHEEL
+                },
+                button_data: delta as u16,
+                dx: 0,
+                dy: 0,
+            },
         );
     }
-}
 
-fn key_press(hwnd: HWND, vkey: i32, duration: u64) {
-    let key = vkey & 0xFF;
-    let scancode = unsafe { MapVirtualKeyA(vkey as u32, MAPVK_VK_TO_VSC) };
+    if !delivery.messages() {
+        return;
+    }
 
-    let lparam = 1 | (scancode << 16) | (0 << 24);
-    let _ = unsafe {
-        PostMessageW(
-            Some(hwnd),
-            WM_KEYDOWN,
-            WPARAM(key as usize),
-            LPARAM(lparam as isize),
-        )
+    let mut point = POINT { x, y };
+    let _ = unsafe { ClientToScreen(hwnd, &mut point) };
+    let msg = if horizontal {
+        WM_MOUSEHWHEEL
+    } else {
+        WM_MOUSEWHEEL
     };
+    post_input(
+        hwnd,
+        msg,
+        wheel_wparam(delta, keys),
+        pack_point(point.x, point.y),
+    );
+}
 
-    sleep(Duration::from_millis(duration as u64));
+//keyboard
+//Keeps the key state the client's GetKeyState hooks report in step with what the bot sends.
+pub fn set_virtual_key(vk: u8, down: bool) {
+    if let Some(mem_manager) = MEMORY_MANAGER.get() {
+        unsafe { mem_manager.lock().unwrap().set_virtual_key(vk, down) };
+    }
+}
 
-    let lparam = 1 | (scancode << 16) | (0 << 24) | (1 << 30) | (1 << 31);
-    let _ = unsafe {
-        PostMessageW(
-            Some(hwnd),
-            WM_KEYUP,
-            WPARAM(key as usize),
-            LPARAM(lparam as isize),
-        )
+fn post_key(hwnd: HWND, key: &KeyStroke, down: bool, flags: KeyFlags) {
+    set_virtual_key(key.vk, down);
+    let (msg, wparam, lparam) = key_message(key, down, flags);
+    let delivery = input_delivery();
+
+    if delivery.raw() {
+        let mut raw_flags = if down { 0 } else { RI_KEY_BREAK };
+        if ((lparam >> 24) & 1) != 0 {
+            raw_flags |= RI_KEY_E0;
+        }
+
+        post_raw_key(
+            hwnd,
+            RawKey {
+                make_code: ((lparam >> 16) & 0xFF) as u16,
+                flags: raw_flags,
+                vk: key.vk as u16,
+                message: msg,
+            },
+        );
+    }
+
+    if delivery.messages() {
+        post_input(hwnd, msg, wparam, lparam);
+    }
+}
+
+pub fn key_down(hwnd: u64, key: KeyStroke, flags: KeyFlags) {
+    post_key(HWND(hwnd as *mut c_void), &key, true, flags);
+}
+
+pub fn key_up(hwnd: u64, key: KeyStroke, flags: KeyFlags) {
+    post_key(HWND(hwnd as *mut c_void), &key, false, flags);
+}
+
+fn key_press(hwnd: HWND, key: KeyStroke, duration: u64) {
+    //keys_send holds the modifiers the key needs while it's pressed
+    let flags = KeyFlags {
+        alt: key.alt(),
+        ctrl: key.ctrl(),
+        ..Default::default()
     };
+
+    post_key(hwnd, &key, true, flags);
+    sleep(Duration::from_millis(duration));
+    post_key(hwnd, &key, false, flags);
 }
 
 fn update_modifiers(hwnd: HWND, shift: bool, ctrl: bool, alt: bool) {
@@ -357,33 +623,431 @@ fn update_modifiers(hwnd: HWND, shift: bool, ctrl: bool, alt: bool) {
     let _ = unsafe { PostMessageW(Some(hwnd), WI_MODIFIERS, WPARAM(wparam), LPARAM(0)) };
 }
 
-fn get_key_modifiers(ch: i8) -> (i16, bool, bool, bool) {
-    let key = unsafe { VkKeyScanA(ch) } as i16;
-    let modifiers = ((key >> 8) & 0xFF) as u8;
+//Layout of the client's UI thread as Windows sees it, which can differ from Simba's.
+pub struct OsLayout {
+    hkl: HKL,
+}
+
+impl OsLayout {
+    pub fn new(hwnd: u64) -> Self {
+        unsafe {
+            let thread = GetWindowThreadProcessId(HWND(hwnd as *mut c_void), None);
+            OsLayout {
+                hkl: GetKeyboardLayout(thread),
+            }
+        }
+    }
+}
+
+impl KeyboardLayout for OsLayout {
+    fn name(&self) -> &str {
+        "OS"
+    }
+
+    fn char_key(&self, ch: char) -> Option<KeyStroke> {
+        let mut units = [0u16; 2];
+        if ch.encode_utf16(&mut units).len() > 1 {
+            return None;
+        }
+
+        let key = unsafe { VkKeyScanExW(units[0], self.hkl) };
+        if (key & 0xFF) == 0xFF {
+            return None;
+        }
+
+        let mut stroke = self.vk_key((key & 0xFF) as u8);
+        stroke.modifiers = ((key >> 8) & 0xFF) as u8;
+
+        //the top bit marks keys that only combine with the next one
+        let vk = stroke.vk as u32;
+        stroke.dead =
+            unsafe { MapVirtualKeyExW(vk, MAPVK_VK_TO_CHAR, Some(self.hkl)) } & 0x80000000 != 0;
+        Some(stroke)
+    }
+
+    fn vk_key(&self, vk: u8) -> KeyStroke {
+        //extended keys come back as 0xE0xx
+        let scancode = unsafe { MapVirtualKeyExW(vk as u32, MAPVK_VK_TO_VSC_EX, Some(self.hkl)) };
+        KeyStroke {
+            vk,
+            scancode: (scancode & 0xFF) as u16,
+            modifiers: 0,
+            extended: (scancode & 0xFF00) == 0xE000,
+            dead: false,
+        }
+    }
+}
+
+pub fn keyboard_layout(hwnd: u64, layout: Layout) -> Box<dyn KeyboardLayout> {
+    match layout.table() {
+        Some(table) => Box::new(*table),
+        None => Box::new(OsLayout::new(hwnd)),
+    }
+}
 
-    let shift = modifiers & 0x01 != 0;
-    let ctrl = modifiers & 0x02 != 0;
-    let alt = modifiers & 0x04 != 0;
+//A WM_CHAR without a scancode, how characters without a key are typed.
+pub fn post_char(hwnd: u64, unit: u16) {
+    post_input(HWND(hwnd as *mut c_void), WM_CHAR, unit as usize, 1);
+}
 
-    (key, shift, ctrl, alt)
+//Asks the client's window whether it takes WM_UNICHAR, windows that don't answer the UNICODE_NOCHAR probe with TRUE.
+fn accepts_unichar(hwnd: HWND) -> bool {
+    let mut result = 0;
+    let wparam = WPARAM(UNICODE_NOCHAR as usize);
+    let sent = unsafe {
+        SendMessageTimeoutW(
+            hwnd,
+            WM_UNICHAR,
+            wparam,
+            LPARAM(0),
+            SMTO_ABORTIFHUNG,
+            200,
+            Some(&mut result),
+        )
+    };
+    (sent.0 != 0) && (result != 0)
 }
 
-pub fn keys_send(hwnd: u64, text: *mut c_char, len: c_int, sleeptimes: *mut c_int) {
+//Characters outside the BMP go as one WM_UNICHAR when the window takes it, otherwise as two surrogate WM_CHARs.
+//unichar is the probe's answer, asked the first time a text needs it.
+fn send_char(hwnd: HWND, ch: char, unichar: &mut Option<bool>) {
+    if ch.len_utf16() == 2 && *unichar.get_or_insert_with(|| accepts_unichar(hwnd)) {
+        post_input(hwnd, WM_UNICHAR, ch as usize, 1);
+        return;
+    }
+
+    let mut units = [0u16; 2];
+    for &unit in ch.encode_utf16(&mut units).iter() {
+        post_char(hwnd.0 as u64, unit);
+    }
+}
+
+//text is UTF-8 and sleeptimes has one entry per byte, each character uses the time of its first byte.
+//gaps are laid out the same and wait before the character, an empty slice types without gaps.
+pub fn keys_send(
+    hwnd: u64,
+    layout: &dyn KeyboardLayout,
+    text: *mut c_char,
+    len: c_int,
+    sleeptimes: *mut c_int,
+    gaps: &[u64],
+) {
     let hwnd = HWND(hwnd as *mut c_void);
 
-    let text_chars = unsafe { from_raw_parts(text, len as usize) };
+    let bytes = unsafe { from_raw_parts(text as *const u8, len as usize) };
     let sleep_times = unsafe { from_raw_parts(sleeptimes, len as usize) };
+    let text = String::from_utf8_lossy(bytes);
 
     let (mut pshift, mut pctrl, mut palt) = (false, false, false); //previous
+    let mut unichar = None;
 
-    for (_, (&ch, &time)) in text_chars.iter().zip(sleep_times.iter()).enumerate() {
-        let (key, shift, ctrl, alt) = get_key_modifiers(ch);
+    for (index, ch) in text.char_indices() {
+        //replacement characters from invalid bytes can land past the end
+        let time = sleep_times.get(index).copied().unwrap_or(0) as u64;
+        if let Some(&gap) = gaps.get(index).filter(|&&g| g > 0) {
+            sleep(Duration::from_millis(gap));
+        }
 
-        update_modifiers(hwnd, shift != pshift, ctrl != pctrl, alt != palt);
-        key_press(hwnd, key as i32, time as u64);
+        match key_sequence(layout, ch) {
+            Some(keys) => {
+                for key in keys {
+                    let (shift, ctrl, alt) = (key.shift(), key.ctrl(), key.alt());
+                    update_modifiers(hwnd, shift != pshift, ctrl != pctrl, alt != palt);
+                    key_press(hwnd, key, time);
+                    (pshift, pctrl, palt) = (shift, ctrl, alt);
+                }
+            }
+            //no key on this layout, sent as WM_CHAR or WM_UNICHAR instead
+            None => {
+                update_modifiers(hwnd, pshift, pctrl, palt);
+                (pshift, pctrl, palt) = (false, false, false);
 
-        (pshift, pctrl, palt) = (shift, ctrl, alt);
+                send_char(hwnd, ch, &mut unichar);
+                sleep(Duration::from_millis(time));
+            }
+        }
     }
 
     update_modifiers(hwnd, pshift, pctrl, palt);
 }
+
+unsafe extern "system" fn clipboard_wndproc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
+
+//EmptyClipboard with no window open makes nobody the owner and SetClipboardData then fails.
+//Simba owns the clipboard through a message-only window, its thread answers what other owners send it.
+fn clipboard_window() -> Option<HWND> {
+    static WINDOW: OnceLock<Option<usize>> = OnceLock::new();
+    let window = WINDOW.get_or_init(|| {
+        let (sender, receiver) = channel();
+        spawn(move || unsafe {
+            let class = w!("WaspInputClipboard");
+            let wndclass = WNDCLASSW {
+                lpfnWndProc: Some(clipboard_wndproc),
+                lpszClassName: class,
+                ..Default::default()
+            };
+            RegisterClassW(&wndclass);
+
+            let hwnd = CreateWindowExW(
+                WINDOW_EX_STYLE(0),
+                class,
+                w!(""),
+                WINDOW_STYLE(0),
+                0,
+                0,
+                0,
+                0,
+                Some(HWND_MESSAGE),
+                None,
+                None,
+                None,
+            );
+            let _ = sender.send(hwnd.as_ref().ok().map(|hwnd| hwnd.0 as usize));
+            if hwnd.is_err() {
+                return;
+            }
+
+            let mut msg = MSG::default();
+            while GetMessageW(&mut msg, None, 0, 0).as_bool() {
+                DispatchMessageW(&msg);
+            }
+        });
+        receiver.recv().ok().flatten()
+    });
+    window.map(|hwnd| HWND(hwnd as *mut c_void))
+}
+
+//Another program can hold the clipboard for a moment, so opening is retried a few times.
+fn open_clipboard() -> bool {
+    let window = match clipboard_window() {
+        Some(hwnd) => hwnd,
+        None => {
+            println!("[WaspInput]: Failed to create the clipboard window.\r\n");
+            return false;
+        }
+    };
+
+    for _ in 0..10 {
+        if unsafe { OpenClipboard(Some(window)) }.is_ok() {
+            return true;
+        }
+        sleep(Duration::from_millis(10));
+    }
+    false
+}
+
+//Formats that aren't plain global memory and can't be copied byte for byte.
+const CF_BITMAP: u32 = 2;
+const CF_METAFILEPICT: u32 = 3;
+const CF_DIB: u32 = 8;
+const CF_PALETTE: u32 = 9;
+const CF_ENHMETAFILE: u32 = 14;
+const CF_DIBV5: u32 = 17;
+
+fn is_handle_format(format: u32) -> bool {
+    matches!(
+        format,
+        CF_BITMAP | CF_METAFILEPICT | CF_PALETTE | CF_ENHMETAFILE
+            | 0x0080..=0x008E // CF_OWNERDISPLAY and the CF_DSP* formats
+            | 0x0200..=0x03FF // CF_PRIVATEFIRST to CF_GDIOBJLAST
+    )
+}
+
+//Every format on the clipboard with its data, in the order the owner put them there.
+pub struct ClipboardContents(Vec<(u32, Vec<u8>)>);
+
+unsafe fn read_global(format: u32) -> Option<Vec<u8>> {
+    let hglobal = HGLOBAL(GetClipboardData(format).ok()?.0);
+    let size = GlobalSize(hglobal);
+    let ptr = GlobalLock(hglobal) as *const u8;
+    if (size == 0) || ptr.is_null() {
+        return None;
+    }
+
+    let data = from_raw_parts(ptr, size).to_vec();
+    let _ = GlobalUnlock(hglobal);
+    Some(data)
+}
+
+unsafe fn write_global(format: u32, data: &[u8]) -> bool {
+    let hglobal = match GlobalAlloc(GMEM_MOVEABLE, data.len()) {
+        Ok(h) => h,
+        Err(_) => return false,
+    };
+
+    let ptr = GlobalLock(hglobal) as *mut u8;
+    if ptr.is_null() {
+        let _ = GlobalFree(Some(hglobal));
+        return false;
+    }
+    copy_nonoverlapping(data.as_ptr(), ptr, data.len());
+    let _ = GlobalUnlock(hglobal);
+
+    //the clipboard owns the memory once this succeeds
+    if SetClipboardData(format, Some(HANDLE(hglobal.0))).is_err() {
+        let _ = GlobalFree(Some(hglobal));
+        return false;
+    }
+    true
+}
+
+unsafe fn read_clipboard() -> Option<ClipboardContents> {
+    let mut formats = Vec::new();
+    let mut format = EnumClipboardFormats(0);
+    while format != 0 {
+        formats.push(format);
+        format = EnumClipboardFormats(format);
+    }
+
+    //Windows makes CF_BITMAP and CF_PALETTE from a DIB again when it's put back
+    let has_dib = formats.iter().any(|&f| (f == CF_DIB) || (f == CF_DIBV5));
+
+    let mut contents = Vec::with_capacity(formats.len());
+    for format in formats {
+        if has_dib && ((format == CF_BITMAP) || (format == CF_PALETTE)) {
+            continue;
+        }
+        if is_handle_format(format) {
+            return None;
+        }
+        contents.push((format, read_global(format)?));
+    }
+    Some(ClipboardContents(contents))
+}
+
+//A copy of everything on the clipboard, None if it can't be opened or holds something that can't be put back.
+pub fn save_clipboard() -> Option<ClipboardContents> {
+    if !open_clipboard() {
+        return None;
+    }
+
+    let contents = unsafe { read_clipboard() };
+    let _ = unsafe { CloseClipboard() };
+    contents
+}
+
+//Empties the clipboard and puts contents on it.
+pub fn restore_clipboard(contents: &ClipboardContents) -> bool {
+    if !open_clipboard() {
+        return false;
+    }
+
+    let result = unsafe {
+        EmptyClipboard().is_ok()
+            && contents
+                .0
+                .iter()
+                .all(|(format, data)| write_global(*format, data))
+    };
+    let _ = unsafe { CloseClipboard() };
+    result
+}
+
+//Replaces the clipboard with UTF-16 text.
+pub fn set_clipboard_text(text: &[u16]) -> bool {
+    let data: Vec<u8> = text
+        .iter()
+        .chain(&[0])
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect();
+    restore_clipboard(&ClipboardContents(vec![(CF_UNICODETEXT, data)]))
+}
+
+fn clipboard_reads() -> u32 {
+    match MEMORY_MANAGER.get() {
+        Some(mem_manager) => unsafe { mem_manager.lock().unwrap().get_clipboard_reads() },
+        None => 0,
+    }
+}
+
+//Default ms the client gets to read the clipboard after ctrl+v, see SetPasteTimeout.
+pub const PASTE_TIMEOUT: u64 = 2000;
+//How long a paste the client didn't read in time keeps the text on the clipboard for a late read.
+const LATE_READ_LIMIT: Duration = Duration::from_secs(30);
+
+fn wait_for_read(reads: u32, timeout: Duration) -> bool {
+    let start = Instant::now();
+    loop {
+        if clipboard_reads() != reads {
+            return true;
+        }
+        if start.elapsed() >= timeout {
+            return false;
+        }
+        sleep(Duration::from_millis(10));
+    }
+}
+
+//Puts the old contents back unless something else was copied since the paste.
+//The client holds the clipboard open while it reads, so this waits for it in open_clipboard.
+fn restore_after_paste(previous: &ClipboardContents, sequence: u32) {
+    if unsafe { GetClipboardSequenceNumber() } != sequence {
+        return;
+    }
+    if !restore_clipboard(previous) {
+        println!("[WaspInput]: Failed to restore the clipboard.\r\n");
+    }
+}
+
+//Pastes text with ctrl+v and puts back everything that was on the clipboard.
+//Returns false without pasting if the clipboard holds data that couldn't be put back,
+//and false if the client never read the clipboard, nothing that takes a paste has focus then.
+//The clipboard is only put back once the client read it, a client that reads late still gets the text.
+pub fn paste(
+    hwnd: u64,
+    layout: &dyn KeyboardLayout,
+    text: &str,
+    duration: u64,
+    timeout: u64,
+) -> bool {
+    let previous = match save_clipboard() {
+        Some(contents) => contents,
+        None => {
+            println!(
+                "[WaspInput]: The clipboard holds data that can't be restored, not pasting.\r\n"
+            );
+            return false;
+        }
+    };
+
+    let units: Vec<u16> = text.encode_utf16().collect();
+    if !set_clipboard_text(&units) {
+        println!("[WaspInput]: Failed to set the clipboard.\r\n");
+        let _ = restore_clipboard(&previous);
+        return false;
+    }
+
+    let sequence = unsafe { GetClipboardSequenceNumber() };
+    let reads = clipboard_reads();
+    let ctrl = layout.vk_key(0x11);
+    let v = layout.vk_key(0x56);
+    let held = KeyFlags {
+        ctrl: true,
+        ..Default::default()
+    };
+
+    key_down(hwnd, ctrl, held);
+    key_down(hwnd, v, held);
+    sleep(Duration::from_millis(duration));
+    key_up(hwnd, v, held);
+    key_up(hwnd, ctrl, KeyFlags::default());
+
+    if wait_for_read(reads, Duration::from_millis(timeout)) {
+        restore_after_paste(&previous, sequence);
+        return true;
+    }
+
+    println!("[WaspInput]: The client didn't read the clipboard.\r\n");
+    spawn(move || {
+        wait_for_read(reads, LATE_READ_LIMIT);
+        restore_after_paste(&previous, sequence);
+    });
+    false
+}
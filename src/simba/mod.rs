@@ -1,2 +1,8 @@
+pub mod path;
 pub mod plugin;
+pub mod random;
+pub mod recorder;
+pub mod repeat;
+pub mod replay;
 pub mod target;
+pub mod timing;
@@ -0,0 +1,494 @@
+//Mouse path generation.
+use std::f64::consts::PI;
+
+use super::random::Random;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PathKind {
+    WindMouse = 0,
+    Bezier = 1,
+    MinimumJerk = 2,
+}
+
+impl PathKind {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(PathKind::WindMouse),
+            1 => Some(PathKind::Bezier),
+            2 => Some(PathKind::MinimumJerk),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PathSettings {
+    pub kind: PathKind,
+    pub speed: f64,     //average pixels per millisecond
+    pub gravity: f64,   //WindMouse pull towards the target
+    pub wind: f64,      //WindMouse random drift, also scales the curve of the other kinds
+    pub overshoot: f64, //chance from 0 to 1 of going past the target and correcting back
+    pub jitter: i32,    //radius around the target the final point is picked from
+}
+
+impl Default for PathSettings {
+    fn default() -> Self {
+        PathSettings {
+            kind: PathKind::WindMouse,
+            speed: 1.5,
+            gravity: 9.0,
+            wind: 3.0,
+            overshoot: 0.2,
+            jitter: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PathPoint {
+    pub x: i32,
+    pub y: i32,
+    pub delay: u64, //milliseconds to wait before moving to this point
+}
+
+//Milliseconds between points of the time based kinds.
+const STEP_TIME: f64 = 8.0;
+//Moves shorter than this never overshoot.
+const OVERSHOOT_MIN_DISTANCE: f64 = 80.0;
+
+//Picks a point around (x, y) uniformly inside a circle of the given radius.
+pub fn jitter_point(x: f64, y: f64, radius: i32, rng: &mut Random) -> (f64, f64) {
+    if radius <= 0 {
+        return (x, y);
+    }
+
+    let r = radius as f64 * rng.next_f64().sqrt();
+    let angle = rng.range(0.0, 2.0 * PI);
+    (x + r * angle.cos(), y + r * angle.sin())
+}
+
+//Picks a point inside the inclusive box, weighted towards the middle.
+pub fn box_point(x1: i32, y1: i32, x2: i32, y2: i32, rng: &mut Random) -> (f64, f64) {
+    let (x1, x2) = (x1.min(x2) as f64, x1.max(x2) as f64);
+    let (y1, y2) = (y1.min(y2) as f64, y1.max(y2) as f64);
+
+    let x = (x1 + x2) / 2.0 + rng.normal() * (x2 - x1) / 6.0;
+    let y = (y1 + y2) / 2.0 + rng.normal() * (y2 - y1) / 6.0;
+    (x.clamp(x1, x2), y.clamp(y1, y2))
+}
+
+//Full path from start to end, the last point is always the rounded end.
+pub fn generate(
+    start: (f64, f64),
+    end: (f64, f64),
+    settings: &PathSettings,
+    rng: &mut Random,
+) -> Vec<PathPoint> {
+    let end = jitter_point(end.0, end.1, settings.jitter, rng);
+    let distance = (end.0 - start.0).hypot(end.1 - start.1);
+    let speed = settings.speed.max(0.01);
+
+    let mut points = Vec::new();
+    let mut from = start;
+
+    if (distance >= OVERSHOOT_MIN_DISTANCE) && (rng.next_f64() < settings.overshoot) {
+        let (dx, dy) = ((end.0 - start.0) / distance, (end.1 - start.1) / distance);
+        let past = distance * rng.range(0.03, 0.08);
+        let side = rng.normal() * past / 2.0;
+        let target = (end.0 + dx * past - dy * side, end.1 + dy * past + dx * side);
+
+        segment(
+            from,
+            target,
+            settings.kind,
+            settings,
+            speed,
+            rng,
+            &mut points,
+        );
+        from = target;
+
+        //short pause before correcting back
+        let pause = rng.range(40.0, 120.0) as u64;
+        if let Some(last) = points.last_mut() {
+            last.delay += pause;
+        }
+        let slower = PathSettings {
+            wind: settings.wind / 2.0,
+            ..*settings
+        };
+        segment(
+            from,
+            end,
+            settings.kind,
+            &slower,
+            speed / 2.0,
+            rng,
+            &mut points,
+        );
+    } else {
+        segment(from, end, settings.kind, settings, speed, rng, &mut points);
+    }
+
+    let (x, y) = (end.0.round() as i32, end.1.round() as i32);
+    match points.last() {
+        Some(last) if (last.x == x) && (last.y == y) => {}
+        _ => points.push(PathPoint { x, y, delay: 1 }),
+    }
+    points
+}
+
+fn segment(
+    start: (f64, f64),
+    end: (f64, f64),
+    kind: PathKind,
+    settings: &PathSettings,
+    speed: f64,
+    rng: &mut Random,
+    points: &mut Vec<PathPoint>,
+) {
+    match kind {
+        PathKind::WindMouse => wind_mouse(start, end, settings, speed, rng, points),
+        PathKind::Bezier => {
+            let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+            let bend = (settings.wind / 10.0).clamp(0.0, 1.0) * 0.3;
+
+            //both control points sit along the line, pushed sideways by a random amount
+            let (t1, t2) = (rng.range(0.2, 0.4), rng.range(0.6, 0.8));
+            let mut control = |t: f64| {
+                let side = rng.normal() * bend;
+                (start.0 + dx * t - dy * side, start.1 + dy * t + dx * side)
+            };
+            let c1 = control(t1);
+            let c2 = control(t2);
+
+            timed(start, end, speed, rng, points, |s| {
+                let u = 1.0 - s;
+                let a = u * u * u;
+                let b = 3.0 * u * u * s;
+                let c = 3.0 * u * s * s;
+                let d = s * s * s;
+                (
+                    a * start.0 + b * c1.0 + c * c2.0 + d * end.0,
+                    a * start.1 + b * c1.1 + c * c2.1 + d * end.1,
+                )
+            });
+        }
+        PathKind::MinimumJerk => {
+            timed(start, end, speed, rng, points, |s| {
+                (
+                    start.0 + (end.0 - start.0) * s,
+                    start.1 + (end.1 - start.1) * s,
+                )
+            });
+        }
+    }
+}
+
+//Minimum jerk position profile, 0 at tau 0 and 1 at tau 1 with zero velocity and acceleration at both ends.
+pub fn minimum_jerk(tau: f64) -> f64 {
+    let tau = tau.clamp(0.0, 1.0);
+    let tau3 = tau * tau * tau;
+    tau3 * (10.0 - 15.0 * tau + 6.0 * tau * tau)
+}
+
+//Samples curve(s) at fixed time steps with s following the minimum jerk profile.
+fn timed(
+    start: (f64, f64),
+    end: (f64, f64),
+    speed: f64,
+    rng: &mut Random,
+    points: &mut Vec<PathPoint>,
+    curve: impl Fn(f64) -> (f64, f64),
+) {
+    let distance = (end.0 - start.0).hypot(end.1 - start.1);
+    if distance < 1.0 {
+        return;
+    }
+
+    //peak speed of the profile is 1.875 times the average one
+    let duration = (distance / speed) * rng.range(0.9, 1.1);
+    let steps = (duration / STEP_TIME).ceil().max(1.0) as usize;
+    let step_time = duration / steps as f64;
+
+    let mut last = (start.0.round() as i32, start.1.round() as i32);
+    let mut waited = 0.0;
+    for i in 1..=steps {
+        waited += step_time;
+        let (x, y) = curve(minimum_jerk(i as f64 / steps as f64));
+        let (x, y) = (x.round() as i32, y.round() as i32);
+        if (x, y) == last {
+            continue;
+        }
+
+        points.push(PathPoint {
+            x,
+            y,
+            delay: waited.round().max(1.0) as u64,
+        });
+        last = (x, y);
+        waited = 0.0;
+    }
+}
+
+//BenLand100's WindMouse with the step length scaled to the requested speed.
+fn wind_mouse(
+    start: (f64, f64),
+    end: (f64, f64),
+    settings: &PathSettings,
+    speed: f64,
+    rng: &mut Random,
+    points: &mut Vec<PathPoint>,
+) {
+    let sqrt3 = 3f64.sqrt();
+    let sqrt5 = 5f64.sqrt();
+
+    let (mut x, mut y) = start;
+    let (mut velo_x, mut velo_y) = (0.0, 0.0);
+    let (mut wind_x, mut wind_y) = (0.0, 0.0);
+
+    let mut max_step = (speed * STEP_TIME * 1.5).max(2.0);
+    let target_area = max_step * 3.0;
+    let mut wind = settings.wind;
+
+    let mut last = (x.round() as i32, y.round() as i32);
+    let mut waited = 0.0;
+
+    //bounded so extreme settings can't loop forever
+    for _ in 0..10_000 {
+        let distance = (end.0 - x).hypot(end.1 - y);
+        if distance <= 1.0 {
+            break;
+        }
+
+        wind = wind.min(distance);
+        if distance >= target_area {
+            wind_x = wind_x / sqrt3 + rng.range(-wind, wind) / sqrt5;
+            wind_y = wind_y / sqrt3 + rng.range(-wind, wind) / sqrt5;
+        } else {
+            wind_x /= sqrt3;
+            wind_y /= sqrt3;
+            if max_step < 3.0 {
+                max_step = rng.range(3.0, 6.0);
+            } else {
+                max_step /= sqrt5;
+            }
+        }
+
+        velo_x += wind_x + settings.gravity * (end.0 - x) / distance;
+        velo_y += wind_y + settings.gravity * (end.1 - y) / distance;
+
+        let velocity = velo_x.hypot(velo_y);
+        if velocity > max_step {
+            let clip = max_step / 2.0 + rng.range(0.0, max_step / 2.0);
+            velo_x = (velo_x / velocity) * clip;
+            velo_y = (velo_y / velocity) * clip;
+        }
+
+        x += velo_x;
+        y += velo_y;
+
+        let step = velo_x.hypot(velo_y);
+        waited += step / speed;
+
+        let point = (x.round() as i32, y.round() as i32);
+        if point != last {
+            points.push(PathPoint {
+                x: point.0,
+                y: point.1,
+                delay: waited.round().max(1.0) as u64,
+            });
+            last = point;
+            waited = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [PathKind; 3] = [PathKind::WindMouse, PathKind::Bezier, PathKind::MinimumJerk];
+
+    fn settings(kind: PathKind) -> PathSettings {
+        PathSettings {
+            kind,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn paths_end_on_the_target() {
+        for kind in KINDS {
+            for seed in 0..200 {
+                let mut rng = Random::new(seed);
+                let end = (rng.range(-50.0, 900.0), rng.range(-50.0, 600.0));
+                let path = generate((400.0, 300.0), end, &settings(kind), &mut rng);
+
+                let last = path.last().unwrap();
+                assert_eq!(
+                    (last.x, last.y),
+                    (end.0.round() as i32, end.1.round() as i32)
+                );
+                assert!(path.iter().all(|p| p.delay >= 1), "{:?} {}", kind, seed);
+                assert!(path
+                    .windows(2)
+                    .all(|w| (w[0].x, w[0].y) != (w[1].x, w[1].y)));
+            }
+        }
+    }
+
+    #[test]
+    fn paths_stay_near_the_line() {
+        for kind in KINDS {
+            for seed in 0..200 {
+                let mut rng = Random::new(seed);
+                let path = generate((100.0, 100.0), (700.0, 400.0), &settings(kind), &mut rng);
+
+                //nothing wanders further than the length of the move from either end
+                let length = 600f64.hypot(300.0);
+                for p in &path {
+                    let from_start = (p.x as f64 - 100.0).hypot(p.y as f64 - 100.0);
+                    let from_end = (p.x as f64 - 700.0).hypot(p.y as f64 - 400.0);
+                    assert!(from_start.min(from_end) <= length, "{:?} {}", kind, seed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wind_has_no_drift() {
+        //signed distance from the line, across a horizontal and a vertical move
+        let (mut across, mut points) = (0.0, 0.0);
+        for seed in 0..500 {
+            let mut rng = Random::new(seed);
+            let settings = settings(PathKind::WindMouse);
+            for p in generate((100.0, 300.0), (700.0, 300.0), &settings, &mut rng) {
+                across += (p.y - 300) as f64;
+                points += 1.0;
+            }
+            for p in generate((400.0, 50.0), (400.0, 650.0), &settings, &mut rng) {
+                across += (p.x - 400) as f64;
+                points += 1.0;
+            }
+        }
+
+        let mean = across / points;
+        assert!(mean.abs() < 1.0, "mean deviation {}", mean);
+    }
+
+    #[test]
+    fn duration_follows_speed() {
+        let distance = 600f64.hypot(300.0);
+        for kind in KINDS {
+            for speed in [0.5, 1.5, 4.0] {
+                let settings = PathSettings {
+                    kind,
+                    speed,
+                    overshoot: 0.0,
+                    ..Default::default()
+                };
+                let mut rng = Random::new(7);
+                let path = generate((100.0, 100.0), (700.0, 400.0), &settings, &mut rng);
+
+                let total: u64 = path.iter().map(|p| p.delay).sum();
+                let expected = distance / speed;
+                assert!(
+                    (total as f64 > expected * 0.5) && ((total as f64) < expected * 2.5),
+                    "{:?} {} took {} ms, expected about {}",
+                    kind,
+                    speed,
+                    total,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_same_path() {
+        for kind in KINDS {
+            let a = generate(
+                (0.0, 0.0),
+                (500.0, 250.0),
+                &settings(kind),
+                &mut Random::new(42),
+            );
+            let b = generate(
+                (0.0, 0.0),
+                (500.0, 250.0),
+                &settings(kind),
+                &mut Random::new(42),
+            );
+            let c = generate(
+                (0.0, 0.0),
+                (500.0, 250.0),
+                &settings(kind),
+                &mut Random::new(43),
+            );
+            assert_eq!(a, b);
+            assert_ne!(a, c);
+        }
+    }
+
+    #[test]
+    fn no_move_is_a_single_point() {
+        for kind in KINDS {
+            let path = generate(
+                (10.0, 20.0),
+                (10.0, 20.0),
+                &settings(kind),
+                &mut Random::new(1),
+            );
+            assert_eq!(
+                path,
+                [PathPoint {
+                    x: 10,
+                    y: 20,
+                    delay: 1
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn jitter_and_box_points_stay_inside() {
+        let mut rng = Random::new(3);
+        for _ in 0..1000 {
+            let (x, y) = jitter_point(50.0, 60.0, 5, &mut rng);
+            assert!((x - 50.0).hypot(y - 60.0) <= 5.0);
+
+            let (x, y) = box_point(30, 40, 10, 20, &mut rng);
+            assert!((10.0..=30.0).contains(&x) && (20.0..=40.0).contains(&y));
+        }
+        assert_eq!(jitter_point(50.0, 60.0, 0, &mut rng), (50.0, 60.0));
+
+        let mut rng = Random::new(3);
+        let path = generate(
+            (0.0, 0.0),
+            (300.0, 300.0),
+            &PathSettings {
+                jitter: 4,
+                ..Default::default()
+            },
+            &mut rng,
+        );
+        let last = path.last().unwrap();
+        assert!((last.x as f64 - 300.0).hypot(last.y as f64 - 300.0) <= 5.0);
+    }
+
+    #[test]
+    fn minimum_jerk_profile() {
+        assert_eq!(minimum_jerk(0.0), 0.0);
+        assert_eq!(minimum_jerk(1.0), 1.0);
+        assert!((minimum_jerk(0.5) - 0.5).abs() < 1e-12);
+        assert_eq!(minimum_jerk(-1.0), 0.0);
+        assert_eq!(minimum_jerk(2.0), 1.0);
+
+        let samples: Vec<f64> = (0..=100).map(|i| minimum_jerk(i as f64 / 100.0)).collect();
+        assert!(samples.windows(2).all(|w| w[0] <= w[1]));
+        for i in 0..=100 {
+            assert!((samples[i] + samples[100 - i] - 1.0).abs() < 1e-12);
+        }
+    }
+}
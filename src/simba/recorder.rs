@@ -0,0 +1,353 @@
+//Session recording, frames are sampled from the shared memory on a Simba side thread.
+use lazy_static::lazy_static;
+use std::{
+    collections::VecDeque,
+    fs::{create_dir_all, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{sleep, spawn, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::shared::{
+    image::{
+        bgra_to_yuv444, draw_cursor, fill_circle, resize, save_image, zlib_compress,
+        zlib_decompress,
+    },
+    memory::MEMORY_MANAGER,
+};
+
+use super::target::TARGETS;
+
+#[derive(Clone)]
+pub struct RecorderConfig {
+    pub pid: u32,
+    pub fps: u32,
+    pub width: i32,   //0 uses the client width
+    pub height: i32,  //0 uses the client height
+    pub rolling: u32, //seconds kept in memory, 0 streams to the path instead
+    pub annotate: bool,
+    pub path: PathBuf,
+}
+
+struct Frame {
+    width: i32,
+    height: i32,
+    data: Vec<u8>,
+}
+
+//Rolling buffer frames are kept compressed, raw frames of a big client add up to gigabytes in a few seconds.
+const MAX_ROLLING_BYTES: usize = 256 * 1024 * 1024;
+
+struct PackedFrame {
+    width: i32,
+    height: i32,
+    data: Vec<u8>,
+}
+
+impl PackedFrame {
+    fn pack(frame: &Frame) -> Self {
+        PackedFrame {
+            width: frame.width,
+            height: frame.height,
+            data: zlib_compress(&frame.data),
+        }
+    }
+
+    fn unpack(&self) -> io::Result<Frame> {
+        match zlib_decompress(&self.data) {
+            Some(data) => Ok(Frame {
+                width: self.width,
+                height: self.height,
+                data,
+            }),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "corrupt frame in the rolling buffer",
+            )),
+        }
+    }
+}
+
+//Oldest frames are dropped once there are more than capacity or they take more than MAX_ROLLING_BYTES.
+struct RollingBuffer {
+    frames: VecDeque<PackedFrame>,
+    capacity: usize,
+    bytes: usize,
+}
+
+impl RollingBuffer {
+    fn new(capacity: usize) -> Self {
+        RollingBuffer {
+            frames: VecDeque::new(),
+            capacity,
+            bytes: 0,
+        }
+    }
+
+    fn push(&mut self, frame: PackedFrame) {
+        self.bytes += frame.data.len();
+        self.frames.push_back(frame);
+        while (self.frames.len() > self.capacity) || (self.bytes > MAX_ROLLING_BYTES) {
+            match self.frames.pop_front() {
+                Some(old) => self.bytes -= old.data.len(),
+                None => break,
+            }
+        }
+    }
+}
+
+//Writes either a single .y4m file or a folder of numbered png files.
+enum FrameSink {
+    Y4m(BufWriter<File>, Option<(i32, i32)>, u32),
+    Png(PathBuf, u32),
+}
+
+impl FrameSink {
+    fn open(path: &Path, fps: u32) -> io::Result<Self> {
+        let is_y4m = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("y4m"));
+
+        if is_y4m {
+            let file = File::create(path)?;
+            return Ok(FrameSink::Y4m(BufWriter::new(file), None, fps));
+        }
+
+        create_dir_all(path)?;
+        Ok(FrameSink::Png(path.to_path_buf(), 0))
+    }
+
+    fn write(&mut self, frame: &Frame) -> io::Result<()> {
+        match self {
+            FrameSink::Y4m(writer, size, fps) => {
+                //Y4M can't change size mid stream, later frames are scaled to the first one.
+                let (w, h) = match *size {
+                    Some(s) => s,
+                    None => {
+                        let (w, h) = (frame.width, frame.height);
+                        writeln!(writer, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444", w, h, fps)?;
+                        *size = Some((w, h));
+                        (w, h)
+                    }
+                };
+
+                let data = resize(&frame.data, frame.width, frame.height, w, h);
+                let mut yuv = Vec::with_capacity((w * h * 3) as usize);
+                bgra_to_yuv444(&data, &mut yuv);
+                writer.write_all(b"FRAME\n")?;
+                writer.write_all(&yuv)
+            }
+            FrameSink::Png(dir, index) => {
+                *index += 1;
+                let path = dir.join(format!("frame_{:06}.png", index));
+                save_image(&path, frame.width, frame.height, &frame.data)
+            }
+        }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        match self {
+            FrameSink::Y4m(mut writer, _, _) => writer.flush(),
+            FrameSink::Png(_, _) => Ok(()),
+        }
+    }
+}
+
+struct Recorder {
+    config: RecorderConfig,
+    running: Arc<AtomicBool>,
+    frames: Arc<Mutex<RollingBuffer>>,
+    thread: JoinHandle<()>,
+}
+
+lazy_static! {
+    static ref RECORDER: Mutex<Option<Recorder>> = Mutex::new(None);
+}
+
+fn capture(config: &RecorderConfig) -> Option<Frame> {
+    let (width, height, mut data, mouse) = {
+        let mem_manager = MEMORY_MANAGER.get()?.lock().unwrap();
+        let (w, h, data) = unsafe { mem_manager.copy_frame() }?;
+        (w, h, data, unsafe { mem_manager.get_mouse_position() })
+    };
+
+    if config.annotate {
+        let buttons = match TARGETS.lock().unwrap().get(&config.pid) {
+            Some(target) => target.mouse,
+            None => Default::default(),
+        };
+
+        //left red, middle green, right blue, X1 yellow, X2 magenta
+        const COLORS: [[u8; 4]; 5] = [
+            [0, 0, 255, 255],
+            [0, 255, 0, 255],
+            [255, 0, 0, 255],
+            [0, 255, 255, 255],
+            [255, 0, 255, 255],
+        ];
+        for (&held, color) in buttons.iter().zip(COLORS) {
+            if held {
+                fill_circle(&mut data, width, height, mouse.0, mouse.1, 8, color);
+            }
+        }
+    }
+    draw_cursor(&mut data, width, height, mouse.0, mouse.1);
+
+    let new_width = if config.width > 0 {
+        config.width
+    } else {
+        width
+    };
+    let new_height = if config.height > 0 {
+        config.height
+    } else {
+        height
+    };
+
+    Some(Frame {
+        width: new_width,
+        height: new_height,
+        data: resize(&data, width, height, new_width, new_height),
+    })
+}
+
+fn record(
+    config: RecorderConfig,
+    running: Arc<AtomicBool>,
+    frames: Arc<Mutex<RollingBuffer>>,
+    mut sink: Option<FrameSink>,
+) {
+    let interval = Duration::from_secs_f64(1.0 / config.fps as f64);
+    let mut next = Instant::now();
+
+    while running.load(Ordering::Acquire) {
+        if let Some(frame) = capture(&config) {
+            match sink.as_mut() {
+                Some(sink) => {
+                    if let Err(e) = sink.write(&frame) {
+                        println!("[WaspInput]: Recording failed: {}\r\n", e);
+                        break;
+                    }
+                }
+                None => {
+                    let frame = PackedFrame::pack(&frame);
+                    frames.lock().unwrap().push(frame);
+                }
+            }
+        }
+
+        next += interval;
+        let now = Instant::now();
+        if next > now {
+            sleep(next - now);
+        } else {
+            next = now;
+        }
+    }
+
+    if let Some(sink) = sink {
+        if let Err(e) = sink.finish() {
+            println!("[WaspInput]: Failed to finish recording: {}\r\n", e);
+        }
+    }
+}
+
+pub fn start_recording(config: RecorderConfig) -> bool {
+    if config.fps == 0 {
+        println!("[WaspInput]: Recording FPS has to be above 0.\r\n");
+        return false;
+    }
+
+    let capacity = match config.rolling.checked_mul(config.fps) {
+        Some(capacity) => capacity as usize,
+        None => {
+            println!("[WaspInput]: Rolling recording is too long for its FPS.\r\n");
+            return false;
+        }
+    };
+
+    stop_recording();
+
+    let sink = if config.rolling == 0 {
+        match FrameSink::open(&config.path, config.fps) {
+            Ok(sink) => Some(sink),
+            Err(e) => {
+                println!("[WaspInput]: Failed to open recording: {}\r\n", e);
+                return false;
+            }
+        }
+    } else {
+        None
+    };
+
+    let running = Arc::new(AtomicBool::new(true));
+    let frames = Arc::new(Mutex::new(RollingBuffer::new(capacity)));
+
+    let thread = {
+        let (config, running, frames) = (config.clone(), running.clone(), frames.clone());
+        spawn(move || record(config, running, frames, sink))
+    };
+
+    *RECORDER.lock().unwrap() = Some(Recorder {
+        config,
+        running,
+        frames,
+        thread,
+    });
+    true
+}
+
+//The thread locks TARGETS to annotate frames, don't call these while holding it.
+pub fn stop_recording() {
+    let recorder = RECORDER.lock().unwrap().take();
+    if let Some(recorder) = recorder {
+        recorder.running.store(false, Ordering::Release);
+        let _ = recorder.thread.join();
+    }
+}
+
+//Stops the recording if it's of pid, for when the target is released.
+pub fn stop_recording_of(pid: u32) {
+    let recording = RECORDER
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|r| r.config.pid == pid);
+    if recording {
+        stop_recording();
+    }
+}
+
+//Writes the rolling buffer to path, recording keeps going.
+pub fn dump_recording(path: &Path) -> bool {
+    let recorder = RECORDER.lock().unwrap();
+    let recorder = match recorder.as_ref() {
+        Some(r) if r.config.rolling > 0 => r,
+        _ => {
+            println!("[WaspInput]: No rolling recording is running.\r\n");
+            return false;
+        }
+    };
+
+    let frames = recorder.frames.lock().unwrap();
+    let result = FrameSink::open(path, recorder.config.fps).and_then(|mut sink| {
+        for frame in frames.frames.iter() {
+            sink.write(&frame.unpack()?)?;
+        }
+        sink.finish()
+    });
+
+    match result {
+        Ok(_) => true,
+        Err(e) => {
+            println!("[WaspInput]: Failed to dump recording: {}\r\n", e);
+            false
+        }
+    }
+}
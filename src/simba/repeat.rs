@@ -0,0 +1,99 @@
+//Autorepeat of keys held through KeyDown, one thread posts the repeats of every target.
+use lazy_static::lazy_static;
+use std::{
+    collections::HashMap,
+    sync::{Condvar, Mutex, Once},
+    thread::spawn,
+    time::Duration,
+};
+
+use crate::shared::{
+    autorepeat::{RepeatTiming, Repeater, SystemClock},
+    layout::KeyStroke,
+    message::KeyFlags,
+    windows::key_down,
+};
+
+use super::target::{repeat_allowed, HiddenInput};
+
+struct HeldKey {
+    repeater: Repeater<SystemClock>,
+    key: KeyStroke,
+    flags: KeyFlags,
+    hidden_input: HiddenInput,
+}
+
+lazy_static! {
+    //Keyed by the target's hwnd.
+    static ref HELD: Mutex<HashMap<u64, HeldKey>> = Mutex::new(HashMap::new());
+}
+
+static WAKE: Condvar = Condvar::new();
+static START: Once = Once::new();
+
+fn repeat_thread() {
+    let mut held = HELD.lock().unwrap();
+    loop {
+        for (&hwnd, entry) in held.iter_mut() {
+            //repeats that come due while paused or hidden are dropped, not sent late
+            if let Some((_, count)) = entry.repeater.poll() {
+                if !repeat_allowed(hwnd, entry.hidden_input) {
+                    continue;
+                }
+
+                let flags = KeyFlags {
+                    previous: true,
+                    repeat: count,
+                    ..entry.flags
+                };
+                key_down(hwnd, entry.key, flags);
+            }
+        }
+
+        held.retain(|_, entry| entry.repeater.wait().is_some());
+        held = match held.values().filter_map(|e| e.repeater.wait()).min() {
+            Some(wait) => {
+                WAKE.wait_timeout(held, Duration::from_millis(wait.max(1)))
+                    .unwrap()
+                    .0
+            }
+            None => WAKE.wait(held).unwrap(),
+        };
+    }
+}
+
+//Starts repeating key after it went down with flags, None only stops the key repeating before it.
+pub fn press_repeat(
+    hwnd: u64,
+    key: KeyStroke,
+    flags: KeyFlags,
+    timing: Option<RepeatTiming>,
+    hidden_input: HiddenInput,
+) {
+    START.call_once(|| {
+        spawn(repeat_thread);
+    });
+
+    let mut held = HELD.lock().unwrap();
+    let entry = held.entry(hwnd).or_insert_with(|| HeldKey {
+        repeater: Repeater::new(SystemClock::default()),
+        key,
+        flags,
+        hidden_input,
+    });
+    entry.repeater.press(key.vk, timing);
+    entry.key = key;
+    entry.flags = flags;
+    entry.hidden_input = hidden_input;
+    WAKE.notify_one();
+}
+
+pub fn release_repeat(hwnd: u64, vk: u8) {
+    if let Some(entry) = HELD.lock().unwrap().get_mut(&hwnd) {
+        entry.repeater.release(vk);
+    }
+}
+
+pub fn stop_repeat(hwnd: u64) {
+    HELD.lock().unwrap().remove(&hwnd);
+}
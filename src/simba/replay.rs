@@ -0,0 +1,109 @@
+//Replays input logs through the same functions the SimbaPluginTarget exports use.
+use std::{fs::File, io::BufReader, path::Path, thread::sleep, time::Duration};
+
+use crate::shared::{
+    inputlog::{read_log, replay, InputSink},
+    windows::{mouse_move, post_char, scroll},
+};
+
+use super::target::{
+    check_point, client_size, get_mouse_pos, key_target, mouse_button, SimbaTarget, TARGETS,
+};
+
+//Simba's numbers for the buttons of a log.
+const SIMBA_BUTTONS: [i32; 5] = [1, 2, 3, 6, 7];
+
+//Locks TARGETS for each event only, a long replay doesn't hold up other exports.
+//Events for a target that was released meanwhile are dropped.
+struct TargetSink {
+    pid: u32,
+}
+
+impl TargetSink {
+    fn with_target(&self, f: impl FnOnce(&mut SimbaTarget)) {
+        if let Some(target) = TARGETS.lock().unwrap().get_mut(&self.pid) {
+            f(target);
+        }
+    }
+}
+
+impl InputSink for TargetSink {
+    fn size(&self) -> Option<(i32, i32)> {
+        client_size()
+    }
+
+    fn wait(&mut self, ms: u64) {
+        sleep(Duration::from_millis(ms));
+    }
+
+    fn mouse_move(&mut self, x: i32, y: i32) {
+        self.with_target(|target| {
+            if let Some((x, y)) = check_point(target, x, y) {
+                mouse_move(target.hwnd, x, y, target.key_state());
+            }
+        });
+    }
+
+    fn button(&mut self, index: usize, down: bool) {
+        self.with_target(|target| {
+            mouse_button(target, SIMBA_BUTTONS[index], down);
+        });
+    }
+
+    fn key(&mut self, vk: u8, down: bool) {
+        self.with_target(|target| {
+            if (vk as usize) < target.keyboard.len() {
+                key_target(target, vk, down);
+            }
+        });
+    }
+
+    fn scroll(&mut self, delta: i16, horizontal: bool) {
+        self.with_target(|target| {
+            let pt = get_mouse_pos(target.hwnd);
+            scroll(
+                target.hwnd,
+                delta,
+                horizontal,
+                target.key_state(),
+                pt.x,
+                pt.y,
+            );
+        });
+    }
+
+    fn char(&mut self, unit: u16) {
+        self.with_target(|target| post_char(target.hwnd, unit));
+    }
+}
+
+//Reads the log at path and plays it on pid's target, returns once it's done.
+//Don't call this while holding TARGETS.
+pub fn replay_log(pid: u32, path: &Path) -> bool {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            println!(
+                "[WaspInput]: Failed to open input log {}: {}\r\n",
+                path.display(),
+                e
+            );
+            return false;
+        }
+    };
+
+    let events = match read_log(BufReader::new(file)) {
+        Ok(events) => events,
+        Err(e) => {
+            println!(
+                "[WaspInput]: Failed to read input log {}: {}\r\n",
+                path.display(),
+                e
+            );
+            return false;
+        }
+    };
+
+    replay(&events, &mut TargetSink { pid });
+    true
+}
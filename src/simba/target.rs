@@ -5,28 +5,158 @@ use std::{
     ffi::CStr,
     os::raw::{c_char, c_int, c_void},
     ptr::null_mut,
+    slice::from_raw_parts,
     sync::Mutex,
+    thread::sleep,
+    time::{Duration, Instant},
 };
 
 use windows::Win32::Foundation::POINT;
 
 use crate::shared::{
-    memory::{MemoryManager, MEMORY_MANAGER},
+    autorepeat::{RepeatMode, RepeatTiming},
+    layout::{KeyboardLayout, Layout},
+    memory::{MemoryManager, Visibility, MEMORY_MANAGER},
+    message::{
+        button_index, mouse_key_state, split_scroll, ClickTracker, KeyFlags, ScrollMode,
+        BUTTON_COUNT,
+    },
     sync::call_event,
     windows::{
-        get_jagrenderview, get_mouse_position, key_down, key_up, keys_send, lbutton, mbutton,
-        mouse_move, rbutton, scroll,
+        button, double_click_limits, get_jagrenderview, get_mouse_position, has_double_clicks,
+        key_down, key_up, keyboard_layout, keyboard_repeat, keys_send, mouse_move, paste,
+        release_modifiers, scroll, window_visibility, PASTE_TIMEOUT,
     },
 };
 
-use super::plugin::PLUGIN_SIMBA_METHODS;
+use super::{
+    path::{generate, PathSettings},
+    plugin::PLUGIN_SIMBA_METHODS,
+    random::Random,
+    recorder::stop_recording_of,
+    repeat::{press_repeat, release_repeat, stop_repeat},
+    timing::{Timing, TimingKind},
+};
+
+//What input exports do while the client is minimized or hidden.
+//Releasing keys and buttons always goes through so nothing gets stuck.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HiddenInput {
+    Allow = 0,
+    Refuse = 1,
+    Wait = 2, //hold the input until the client renders again or the timeout passes
+}
+
+impl HiddenInput {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(HiddenInput::Allow),
+            1 => Some(HiddenInput::Refuse),
+            2 => Some(HiddenInput::Wait),
+            _ => None,
+        }
+    }
+}
+
+//What input exports do with points outside the published client dimensions.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CoordinatePolicy {
+    Allow = 0,
+    Clamp = 1,
+    Reject = 2,
+}
+
+impl CoordinatePolicy {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(CoordinatePolicy::Allow),
+            1 => Some(CoordinatePolicy::Clamp),
+            2 => Some(CoordinatePolicy::Reject),
+            _ => None,
+        }
+    }
+}
 
 #[repr(C)]
 pub struct SimbaTarget {
     pub pid: u32,
     pub hwnd: u64,
     pub keyboard: [bool; 255],
-    pub mouse: [bool; 3],
+    pub mouse: [bool; BUTTON_COUNT], //left, middle, right, X1, X2
+    pub hidden_input: HiddenInput,
+    pub hidden_timeout: u64,
+    pub coordinates: CoordinatePolicy,
+    pub scroll_mode: ScrollMode,
+    pub scroll_delay: u64,
+    pub repeat_mode: RepeatMode,
+    pub repeat: RepeatTiming, //used by RepeatMode::Custom
+    pub path: PathSettings,
+    pub rng: Random,
+    pub layout: Layout,
+    pub clicks: ClickTracker,
+    pub timing: Option<Timing>, //used when a script doesn't pass its own timings
+    pub paste_timeout: u64,
+}
+
+impl SimbaTarget {
+    pub fn new(pid: u32, hwnd: u64) -> Self {
+        SimbaTarget {
+            pid,
+            hwnd,
+            keyboard: [false; 255],
+            mouse: [false; BUTTON_COUNT],
+            hidden_input: HiddenInput::Allow,
+            hidden_timeout: 0,
+            coordinates: CoordinatePolicy::Clamp,
+            scroll_mode: ScrollMode::Instant,
+            scroll_delay: 0,
+            repeat_mode: RepeatMode::System,
+            repeat: RepeatTiming::default(),
+            path: PathSettings::default(),
+            rng: Random::from_time(),
+            layout: Layout::Os,
+            clicks: ClickTracker::default(),
+            timing: None,
+            paste_timeout: PASTE_TIMEOUT,
+        }
+    }
+
+    //MK_* flags of the buttons and modifiers currently held by the script.
+    pub fn key_state(&self) -> u16 {
+        mouse_key_state(&self.mouse, &self.keyboard)
+    }
+
+    //Alt, ctrl and the previous key state as the client sees them once vk goes down or up.
+    pub fn key_flags(&self, vk: u8, down: bool) -> KeyFlags {
+        let held = |keys: [usize; 3]| {
+            keys.iter().any(|&k| {
+                if k == vk as usize {
+                    down
+                } else {
+                    self.keyboard[k]
+                }
+            })
+        };
+        KeyFlags {
+            alt: held([0x12, 0xA4, 0xA5]),
+            ctrl: held([0x11, 0xA2, 0xA3]),
+            previous: self.keyboard[vk as usize],
+            repeat: 1,
+        }
+    }
+
+    //Delay and interval of held keys, None when they don't repeat.
+    pub fn key_repeat(&self) -> Option<RepeatTiming> {
+        match self.repeat_mode {
+            RepeatMode::System => Some(keyboard_repeat()),
+            RepeatMode::Custom => Some(self.repeat),
+            RepeatMode::Off => None,
+        }
+    }
+
+    pub fn keyboard_layout(&self) -> Box<dyn KeyboardLayout> {
+        keyboard_layout(self.hwnd, self.layout)
+    }
 }
 
 lazy_static! {
@@ -50,6 +180,402 @@ pub fn get_mouse_pos(hwnd: u64) -> POINT {
     POINT { x: x, y: y }
 }
 
+fn can_render(hwnd: u64) -> bool {
+    matches!(
+        window_visibility(hwnd),
+        Visibility::Visible | Visibility::Occluded | Visibility::Unknown
+    )
+}
+
+//Input waiting for the user to hand the client back is dropped after this long.
+const PAUSE_TIMEOUT: Duration = Duration::from_secs(60);
+
+fn bot_paused() -> bool {
+    match MEMORY_MANAGER.get() {
+        Some(mem_manager) => unsafe { mem_manager.lock().unwrap().bot_paused() },
+        None => false,
+    }
+}
+
+//Blocks while the user has the bot paused and, with HiddenInput::Wait, until the client renders.
+//Waits without holding TARGETS, callers lock it afterwards and check input_allowed.
+pub fn wait_for_input(pid: u32) -> bool {
+    let (hwnd, hidden_input, hidden_timeout) = match TARGETS.lock().unwrap().get(&pid) {
+        Some(target) => (target.hwnd, target.hidden_input, target.hidden_timeout),
+        None => return false,
+    };
+
+    if bot_paused() {
+        println!("[WaspInput]: Waiting for the user to stop using the client.\r\n");
+        if !wait_until(PAUSE_TIMEOUT, || !bot_paused()) {
+            println!("[WaspInput]: Timed out waiting for the user to stop using the client.\r\n");
+            return false;
+        }
+    }
+
+    if hidden_input == HiddenInput::Wait {
+        let timeout = Duration::from_millis(hidden_timeout);
+        if !wait_until(timeout, || can_render(hwnd)) {
+            println!("[WaspInput]: Timed out waiting for the client to render.\r\n");
+            return false;
+        }
+    }
+    true
+}
+
+fn wait_until(timeout: Duration, done: impl Fn() -> bool) -> bool {
+    let start = Instant::now();
+    while !done() {
+        if start.elapsed() >= timeout {
+            return false;
+        }
+        sleep(Duration::from_millis(50));
+    }
+    true
+}
+
+//Same as input_allowed for the autorepeat thread, which can't lock TARGETS and checks before every repeat.
+pub fn repeat_allowed(hwnd: u64, hidden_input: HiddenInput) -> bool {
+    !bot_paused() && ((hidden_input == HiddenInput::Allow) || can_render(hwnd))
+}
+
+//Doesn't wait, anything that changed since wait_for_input refuses the input.
+pub fn input_allowed(target: &SimbaTarget) -> bool {
+    if bot_paused() {
+        println!("[WaspInput]: The user is using the client, input refused.\r\n");
+        return false;
+    }
+
+    if (target.hidden_input != HiddenInput::Allow) && !can_render(target.hwnd) {
+        println!("[WaspInput]: Client is minimized or hidden, input refused.\r\n");
+        return false;
+    }
+    true
+}
+
+//Client width and height, None until the client has published a frame.
+pub fn client_size() -> Option<(i32, i32)> {
+    let (width, height) = unsafe { MEMORY_MANAGER.get()?.lock().unwrap().get_dimensions() };
+    if (width <= 0) || (height <= 0) {
+        return None;
+    }
+    Some((width, height))
+}
+
+//Applies the target's coordinate policy to (x, y), None if the point is rejected.
+pub fn check_point(target: &SimbaTarget, x: i32, y: i32) -> Option<(i32, i32)> {
+    if target.coordinates == CoordinatePolicy::Allow {
+        return Some((x, y));
+    }
+
+    let (width, height) = match client_size() {
+        Some(size) => size,
+        None => return Some((x, y)),
+    };
+
+    let inside = (0..width).contains(&x) && (0..height).contains(&y);
+    match target.coordinates {
+        _ if inside => Some((x, y)),
+        CoordinatePolicy::Reject => {
+            println!(
+                "[WaspInput]: Point ({}, {}) is outside the client ({}x{}).\r\n",
+                x, y, width, height
+            );
+            None
+        }
+        _ => Some((x.clamp(0, width - 1), y.clamp(0, height - 1))),
+    }
+}
+
+//Positive scrolls go down or right, same as Simba.
+pub fn scroll_target(target: &SimbaTarget, scrolls: i32, horizontal: bool) {
+    let pt = get_mouse_pos(target.hwnd);
+    let notches = if horizontal {
+        scrolls
+    } else {
+        scrolls.saturating_neg()
+    };
+    let keys = target.key_state();
+
+    for (i, delta) in split_scroll(notches, target.scroll_mode)
+        .into_iter()
+        .enumerate()
+    {
+        if (i > 0) && (target.scroll_delay > 0) {
+            sleep(Duration::from_millis(target.scroll_delay));
+        }
+        scroll(target.hwnd, delta, horizontal, keys, pt.x, pt.y);
+    }
+}
+
+//Moves the cursor to (x, y) along a generated path, sleeping between the steps.
+//Returns false if the coordinate policy rejects (x, y).
+pub fn move_along_path(target: &mut SimbaTarget, x: f64, y: f64) -> bool {
+    let (x, y) = match check_point(target, x.round() as i32, y.round() as i32) {
+        Some(point) => point,
+        None => return false,
+    };
+
+    //overshoot and jitter can leave the client even when (x, y) is inside, those points are clamped
+    let bounds = match target.coordinates {
+        CoordinatePolicy::Allow => None,
+        _ => client_size(),
+    };
+
+    let pt = get_mouse_pos(target.hwnd);
+    let start = (pt.x as f64, pt.y as f64);
+
+    for point in generate(start, (x as f64, y as f64), &target.path, &mut target.rng) {
+        let (px, py) = match bounds {
+            Some((width, height)) => (point.x.clamp(0, width - 1), point.y.clamp(0, height - 1)),
+            None => (point.x, point.y),
+        };
+
+        sleep(Duration::from_millis(point.delay));
+        mouse_move(target.hwnd, px, py, target.key_state());
+    }
+    true
+}
+
+//Presses or releases a Simba mouse button at the current position, false for unknown buttons.
+pub fn mouse_button(target: &mut SimbaTarget, mouse_button: c_int, down: bool) -> bool {
+    //the scroll buttons scroll a notch when pressed and are never held
+    if matches!(mouse_button, 4 | 5) {
+        if down {
+            scroll_target(target, if mouse_button == 4 { -1 } else { 1 }, false);
+        }
+        return true;
+    }
+
+    let index = match button_index(mouse_button) {
+        Some(index) => index,
+        None => {
+            println!("[WaspInput]: Unknown mouse button: {}\r\n", mouse_button);
+            return false;
+        }
+    };
+
+    let pt = get_mouse_pos(target.hwnd);
+    let double = down
+        && has_double_clicks(target.hwnd)
+        && target
+            .clicks
+            .press(index, Instant::now(), pt.x, pt.y, &double_click_limits());
+
+    target.mouse[index] = down;
+    button(
+        target.hwnd,
+        index,
+        down,
+        double,
+        pt.x,
+        pt.y,
+        target.key_state(),
+    );
+    true
+}
+
+//Two clicks close enough together for the second one to arrive as a double click.
+pub fn double_click_target(target: &mut SimbaTarget, button: c_int) -> bool {
+    let limit = double_click_limits().time.as_millis() as f64;
+    target.clicks = ClickTracker::default();
+
+    for i in 0..2 {
+        if i > 0 {
+            //both clicks have to land within the double click time
+            let gap = match target.timing.as_mut() {
+                Some(timing) => timing
+                    .sample(TimingKind::Interval)
+                    .min((limit / 2.0) as u64),
+                None => target.rng.range(40.0, (limit / 2.0).clamp(41.0, 140.0)) as u64,
+            };
+            sleep(Duration::from_millis(gap));
+        }
+
+        if !mouse_button(target, button, true) {
+            return false;
+        }
+        let hold = match target.timing.as_mut() {
+            Some(timing) => timing.sample(TimingKind::Click).min((limit / 4.0) as u64),
+            None => target.rng.range(30.0, 80.0) as u64,
+        };
+        sleep(Duration::from_millis(hold));
+        mouse_button(target, button, false);
+    }
+    true
+}
+
+//Holds the button down while moving along a path to (x, y).
+pub fn drag_target(target: &mut SimbaTarget, button: c_int, x: f64, y: f64) -> bool {
+    if check_point(target, x.round() as i32, y.round() as i32).is_none() {
+        return false;
+    }
+
+    if !mouse_button(target, button, true) {
+        return false;
+    }
+
+    move_along_path(target, x, y);
+    mouse_button(target, button, false)
+}
+
+//Presses or releases a virtual key, held keys autorepeat until they're released.
+//Virtual keys the exports take, keyboard has no slot for 0 or 255.
+fn virtual_key(key: c_int) -> Option<u8> {
+    match key {
+        1..=254 => Some(key as u8),
+        _ => {
+            println!("[WaspInput]: Invalid key: {}\r\n", key);
+            None
+        }
+    }
+}
+
+pub fn key_target(target: &mut SimbaTarget, vk: u8, down: bool) {
+    let flags = target.key_flags(vk, down);
+    let stroke = target.keyboard_layout().vk_key(vk);
+
+    if down {
+        key_down(target.hwnd, stroke, flags);
+        press_repeat(
+            target.hwnd,
+            stroke,
+            flags,
+            target.key_repeat(),
+            target.hidden_input,
+        );
+    } else {
+        release_repeat(target.hwnd, vk);
+        key_up(target.hwnd, stroke, flags);
+    }
+    target.keyboard[vk as usize] = down;
+}
+
+//Types text with keys_send, sleeptimes that are null or all zero use the target's timing profile instead.
+pub fn keys_target(
+    target: &mut SimbaTarget,
+    text: *mut c_char,
+    len: c_int,
+    sleeptimes: *mut c_int,
+) {
+    if text.is_null() || (len <= 0) {
+        println!("[WaspInput]: Invalid text\r\n");
+        return;
+    }
+
+    let explicit = !sleeptimes.is_null()
+        && unsafe { from_raw_parts(sleeptimes, len as usize) }
+            .iter()
+            .any(|&t| t != 0);
+
+    let layout = target.keyboard_layout();
+    match target.timing.as_mut() {
+        Some(timing) if !explicit => {
+            let mut presses: Vec<c_int> = (0..len)
+                .map(|_| timing.sample(TimingKind::Press) as c_int)
+                .collect();
+            let gaps: Vec<u64> = (0..len)
+                .map(|i| match i {
+                    0 => 0,
+                    _ => timing.sample(TimingKind::Interval),
+                })
+                .collect();
+            keys_send(
+                target.hwnd,
+                &*layout,
+                text,
+                len,
+                presses.as_mut_ptr(),
+                &gaps,
+            );
+        }
+        _ if sleeptimes.is_null() => println!("[WaspInput]: sleeptimes is null!\r\n"),
+        _ => keys_send(target.hwnd, &*layout, text, len, sleeptimes, &[]),
+    }
+}
+
+//Pastes text through the clipboard and types it with keys_target instead if the client doesn't take the paste.
+//text and sleeptimes are laid out like KeySend's, ctrl+v is held for the first sleep time.
+pub fn paste_target(
+    target: &mut SimbaTarget,
+    text: *mut c_char,
+    len: c_int,
+    sleeptimes: *mut c_int,
+) {
+    if text.is_null() || (len <= 0) {
+        println!("[WaspInput]: Invalid text\r\n");
+        return;
+    }
+
+    let bytes = unsafe { from_raw_parts(text as *const u8, len as usize) };
+    let duration = if sleeptimes.is_null() {
+        0
+    } else {
+        unsafe { from_raw_parts(sleeptimes, len as usize) }
+            .first()
+            .copied()
+            .unwrap_or(0)
+            .max(0) as u64
+    };
+    let duration = match target.timing.as_mut() {
+        Some(timing) if duration == 0 => timing.sample(TimingKind::Press),
+        _ => duration,
+    };
+
+    let layout = target.keyboard_layout();
+    if !paste(
+        target.hwnd,
+        &*layout,
+        &String::from_utf8_lossy(bytes),
+        duration,
+        target.paste_timeout,
+    ) {
+        println!("[WaspInput]: Typing the text instead.\r\n");
+        keys_target(target, text, len, sleeptimes);
+    }
+}
+
+//Lets go of every key and button the script holds, cancels its sequence and the client's toggled modifiers.
+//Ignores the hidden input policy, releasing has to get through.
+pub fn release_all(target: &mut SimbaTarget) {
+    if let Some(mem_manager) = MEMORY_MANAGER.get() {
+        let mem_manager = mem_manager.lock().unwrap();
+        unsafe { mem_manager.cancel_sequence(mem_manager.get_sequence_id()) };
+    }
+    stop_repeat(target.hwnd);
+
+    if target.mouse.iter().any(|&down| down) {
+        let pt = get_mouse_pos(target.hwnd);
+        for index in 0..BUTTON_COUNT {
+            if target.mouse[index] {
+                target.mouse[index] = false;
+                button(
+                    target.hwnd,
+                    index,
+                    false,
+                    false,
+                    pt.x,
+                    pt.y,
+                    target.key_state(),
+                );
+            }
+        }
+    }
+
+    if target.keyboard.iter().any(|&down| down) {
+        let layout = target.keyboard_layout();
+        for vk in 0..target.keyboard.len() {
+            if target.keyboard[vk] {
+                let flags = target.key_flags(vk as u8, false);
+                key_up(target.hwnd, layout.vk_key(vk as u8), flags);
+                target.keyboard[vk] = false;
+            }
+        }
+    }
+
+    release_modifiers(target.hwnd);
+}
+
 #[no_mangle]
 pub extern "system" fn SimbaPluginTarget_Request(args: *const c_char) -> *mut SimbaTarget {
     if args.is_null() {
@@ -132,12 +658,18 @@ pub extern "system" fn SimbaPluginTarget_Release(target: *mut SimbaTarget) {
     let mut targets = TARGETS.lock().unwrap();
     let target = unsafe { &*target };
 
-    if let Some(removed) = targets.remove(&target.pid) {
+    let pid = target.pid;
+
+    if let Some(mut removed) = targets.remove(&pid) {
+        release_all(&mut removed);
         println!(
             "Releasing Client PID: {} and HWND: {}\r\n",
             removed.pid, removed.hwnd
         );
     }
+
+    drop(targets);
+    stop_recording_of(pid);
 }
 
 #[no_mangle]
@@ -188,16 +720,21 @@ pub extern "system" fn SimbaPluginTarget_GetImageData(
         return false;
     }
 
-    let mem_manager = MEMORY_MANAGER
+    let mut mem_manager = MEMORY_MANAGER
         .get()
         .expect("[WaspInput]: Memory manager is not initialized!\r\n")
         .lock()
         .unwrap();
 
+    //RGBA and RGB captures are converted, Simba needs BGRA
+    let img_data = match unsafe { mem_manager.bgra_buffer() } {
+        Some(ptr) => ptr,
+        None => return false,
+    };
+
     let (w, _h) = unsafe { mem_manager.get_dimensions() };
     unsafe { *data_width = w };
 
-    let img_data = unsafe { mem_manager.image_ptr() };
     let offset = ((y * (w) + x) * 4) as isize;
     unsafe { *bgra = img_data.offset(offset) as *mut c_void };
 
@@ -216,11 +753,10 @@ pub extern "system" fn SimbaPluginTarget_MousePressed(
 
     let _lock = TARGETS.lock().unwrap();
     let target = unsafe { &*target };
-    match mouse_button {
-        1 => target.keyboard[0],
-        2 | 4 | 5 => target.keyboard[1],
-        3 => target.keyboard[2],
-        _ => {
+    match button_index(mouse_button) {
+        Some(index) => target.mouse[index],
+        None if matches!(mouse_button, 4 | 5) => false,
+        None => {
             println!("[WaspInput]: Unknown mouse button: {}\r\n", mouse_button);
             false
         }
@@ -260,14 +796,24 @@ pub extern "system" fn SimbaPluginTarget_MouseTeleport(
         return;
     }
 
+    if !wait_for_input(unsafe { (*target).pid }) {
+        return;
+    }
+
     let _lock = TARGETS.lock().unwrap();
     let target = unsafe { &*target };
 
-    mouse_move(target.hwnd, x, y);
+    if !input_allowed(target) {
+        return;
+    }
+
+    if let Some((x, y)) = check_point(target, x, y) {
+        mouse_move(target.hwnd, x, y, target.key_state());
+    }
 }
 
 #[no_mangle]
-pub extern "system" fn SimbaPluginTarget_MouseUp(target: *mut SimbaTarget, mouse_button: c_int) {
+pub extern "system" fn SimbaPluginTarget_MouseUp(target: *mut SimbaTarget, button: c_int) {
     if target.is_null() {
         println!("[WaspInput]: target is null!\r\n");
         return;
@@ -276,57 +822,28 @@ pub extern "system" fn SimbaPluginTarget_MouseUp(target: *mut SimbaTarget, mouse
     let _lock = TARGETS.lock().unwrap();
     let target = unsafe { &mut *target };
 
-    let pt = get_mouse_pos(target.hwnd);
-    match mouse_button {
-        1 => {
-            lbutton(target.hwnd, false, pt.x, pt.y);
-            target.mouse[0] = false;
-        }
-        2 | 4 | 5 => {
-            mbutton(target.hwnd, false, pt.x, pt.y);
-            target.mouse[1] = false;
-        }
-        3 => {
-            rbutton(target.hwnd, false, pt.x, pt.y);
-            target.mouse[2] = false;
-        }
-        _ => {
-            println!("[WaspInput]: Unknown mouse button: {}\r\n", mouse_button);
-            return;
-        }
-    };
+    mouse_button(target, button, false);
 }
 
 #[no_mangle]
-pub extern "system" fn SimbaPluginTarget_MouseDown(target: *mut SimbaTarget, mouse_button: c_int) {
+pub extern "system" fn SimbaPluginTarget_MouseDown(target: *mut SimbaTarget, button: c_int) {
     if target.is_null() {
         println!("[WaspInput]: target is null!\r\n");
         return;
     }
 
+    if !wait_for_input(unsafe { (*target).pid }) {
+        return;
+    }
+
     let _lock = TARGETS.lock().unwrap();
     let target = unsafe { &mut *target };
 
-    let pt = get_mouse_pos(target.hwnd);
+    if !input_allowed(target) {
+        return;
+    }
 
-    match mouse_button {
-        1 => {
-            lbutton(target.hwnd, true, pt.x, pt.y);
-            target.mouse[0] = true;
-        }
-        2 | 4 | 5 => {
-            mbutton(target.hwnd, true, pt.x, pt.y);
-            target.mouse[1] = true;
-        }
-        3 => {
-            rbutton(target.hwnd, true, pt.x, pt.y);
-            target.mouse[2] = true;
-        }
-        _ => {
-            println!("[WaspInput]: Unknown mouse button: {}\r\n", mouse_button);
-            return;
-        }
-    };
+    mouse_button(target, button, true);
 }
 
 #[no_mangle]
@@ -336,12 +853,18 @@ pub extern "system" fn SimbaPluginTarget_MouseScroll(target: *mut SimbaTarget, s
         return;
     }
 
+    if !wait_for_input(unsafe { (*target).pid }) {
+        return;
+    }
+
     let _lock = TARGETS.lock().unwrap();
     let target = unsafe { &mut *target };
 
-    let pt = get_mouse_pos(target.hwnd);
-    scroll(target.hwnd, true, scrolls, pt.x, pt.y);
-    println!("[WaspInput]: TODO: Implement SimbaPluginTarget_MouseScroll\r\n");
+    if !input_allowed(target) {
+        return;
+    }
+
+    scroll_target(target, scrolls, false);
 }
 
 #[no_mangle]
@@ -351,11 +874,23 @@ pub extern "system" fn SimbaPluginTarget_KeyDown(target: *mut SimbaTarget, key:
         return;
     }
 
+    let vk = match virtual_key(key) {
+        Some(vk) => vk,
+        None => return,
+    };
+
+    if !wait_for_input(unsafe { (*target).pid }) {
+        return;
+    }
+
     let _lock = TARGETS.lock().unwrap();
     let target = unsafe { &mut *target };
 
-    key_down(target.hwnd, key);
-    target.keyboard[key as usize] = true;
+    if !input_allowed(target) {
+        return;
+    }
+
+    key_target(target, vk, true);
 }
 
 #[no_mangle]
@@ -365,11 +900,15 @@ pub extern "system" fn SimbaPluginTarget_KeyUp(target: *mut SimbaTarget, key: c_
         return;
     }
 
+    let vk = match virtual_key(key) {
+        Some(vk) => vk,
+        None => return,
+    };
+
     let _lock = TARGETS.lock().unwrap();
     let target = unsafe { &mut *target };
 
-    key_up(target.hwnd, key);
-    target.keyboard[key as usize] = false;
+    key_target(target, vk, false);
 }
 
 #[no_mangle]
@@ -389,20 +928,33 @@ pub extern "system" fn SimbaPluginTarget_KeySend(
         return;
     }
 
-    if sleeptimes.is_null() {
-        println!("[WaspInput]: sleeptimes is null!\r\n");
+    if !wait_for_input(unsafe { (*target).pid }) {
         return;
     }
 
     let _lock = TARGETS.lock().unwrap();
-    let target = unsafe { &*target };
-    keys_send(target.hwnd, text, len, sleeptimes);
+    let target = unsafe { &mut *target };
+
+    if !input_allowed(target) {
+        return;
+    }
+    keys_target(target, text, len, sleeptimes);
 }
 
 #[no_mangle]
 pub extern "system" fn SimbaPluginTarget_KeyPressed(target: *mut SimbaTarget, key: c_int) -> bool {
+    if target.is_null() {
+        println!("[WaspInput]: target is null!\r\n");
+        return false;
+    }
+
+    let vk = match virtual_key(key) {
+        Some(vk) => vk,
+        None => return false,
+    };
+
     let _lock = TARGETS.lock().unwrap();
     let target = unsafe { &*target };
 
-    target.keyboard[key as usize]
+    target.keyboard[vk as usize]
 }
@@ -0,0 +1,342 @@
+//Named timing profiles for press durations, gaps between keys and click holds.
+//Used by the input exports whenever the script doesn't pass its own timings.
+use std::time::Instant;
+
+use super::random::Random;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Distribution {
+    LogNormal { median: f64, sigma: f64 },
+    Gamma { shape: f64, scale: f64 },
+}
+
+impl Distribution {
+    //Log-normal (0) from a median in ms and sigma, or gamma (1) from a shape and a scale in ms.
+    //None for other kinds and for parameters that aren't finite or are out of range.
+    pub fn from_params(kind: i32, a: f64, b: f64) -> Option<Self> {
+        if !a.is_finite() || !b.is_finite() || (a <= 0.0) {
+            return None;
+        }
+
+        match kind {
+            0 if b >= 0.0 => Some(Distribution::LogNormal {
+                median: a,
+                sigma: b,
+            }),
+            1 if b > 0.0 => Some(Distribution::Gamma { shape: a, scale: b }),
+            _ => None,
+        }
+    }
+
+    pub fn sample(&self, rng: &mut Random) -> f64 {
+        match *self {
+            Distribution::LogNormal { median, sigma } => (median.ln() + sigma * rng.normal()).exp(),
+            Distribution::Gamma { shape, scale } => gamma(rng, shape) * scale,
+        }
+    }
+}
+
+//Marsaglia and Tsang, shapes below 1 are boosted and scaled back down.
+fn gamma(rng: &mut Random, shape: f64) -> f64 {
+    if shape < 1.0 {
+        let u = 1.0 - rng.next_f64();
+        return gamma(rng, shape + 1.0) * u.powf(1.0 / shape);
+    }
+
+    let d = shape - 1.0 / 3.0;
+    let c = 1.0 / (9.0 * d).sqrt();
+    loop {
+        let x = rng.normal();
+        let v = (1.0 + c * x).powi(3);
+        if v <= 0.0 {
+            continue;
+        }
+
+        let u = 1.0 - rng.next_f64();
+        if u.ln() < 0.5 * x * x + d - d * v + d * v.ln() {
+            return d * v;
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TimingKind {
+    Press,    //how long a key is held
+    Interval, //gap between two keys
+    Click,    //how long a mouse button is held
+}
+
+impl TimingKind {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(TimingKind::Press),
+            1 => Some(TimingKind::Interval),
+            2 => Some(TimingKind::Click),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TimingProfile {
+    pub name: &'static str,
+    pub press: Distribution,
+    pub interval: Distribution,
+    pub click: Distribution,
+    pub fatigue: f64, //how much slower every hour of the session gets, 0.1 is 10%
+}
+
+//Fatigue stops growing after this many hours.
+const MAX_FATIGUE_HOURS: f64 = 4.0;
+//Samples are clamped to this range in ms.
+const MIN_TIME: f64 = 5.0;
+const MAX_TIME: f64 = 2000.0;
+
+pub const PROFILES: [TimingProfile; 3] = [
+    TimingProfile {
+        name: "fast",
+        press: Distribution::LogNormal {
+            median: 45.0,
+            sigma: 0.25,
+        },
+        interval: Distribution::LogNormal {
+            median: 60.0,
+            sigma: 0.35,
+        },
+        click: Distribution::Gamma {
+            shape: 9.0,
+            scale: 7.0,
+        },
+        fatigue: 0.05,
+    },
+    TimingProfile {
+        name: "normal",
+        press: Distribution::LogNormal {
+            median: 70.0,
+            sigma: 0.3,
+        },
+        interval: Distribution::Gamma {
+            shape: 4.0,
+            scale: 30.0,
+        },
+        click: Distribution::Gamma {
+            shape: 8.0,
+            scale: 11.0,
+        },
+        fatigue: 0.1,
+    },
+    TimingProfile {
+        name: "relaxed",
+        press: Distribution::LogNormal {
+            median: 95.0,
+            sigma: 0.35,
+        },
+        interval: Distribution::Gamma {
+            shape: 3.0,
+            scale: 70.0,
+        },
+        click: Distribution::Gamma {
+            shape: 6.0,
+            scale: 20.0,
+        },
+        fatigue: 0.15,
+    },
+];
+
+pub fn find_profile(name: &str) -> Option<TimingProfile> {
+    PROFILES
+        .iter()
+        .find(|p| p.name.eq_ignore_ascii_case(name))
+        .copied()
+}
+
+impl TimingProfile {
+    pub fn distribution_mut(&mut self, kind: TimingKind) -> &mut Distribution {
+        match kind {
+            TimingKind::Press => &mut self.press,
+            TimingKind::Interval => &mut self.interval,
+            TimingKind::Click => &mut self.click,
+        }
+    }
+
+    //A sample in ms after the session has run for elapsed ms.
+    pub fn sample(&self, kind: TimingKind, rng: &mut Random, elapsed: u64) -> u64 {
+        let distribution = match kind {
+            TimingKind::Press => self.press,
+            TimingKind::Interval => self.interval,
+            TimingKind::Click => self.click,
+        };
+
+        let hours = (elapsed as f64 / 3_600_000.0).min(MAX_FATIGUE_HOURS);
+        let time = distribution.sample(rng) * (1.0 + self.fatigue * hours);
+        time.clamp(MIN_TIME, MAX_TIME).round() as u64
+    }
+}
+
+//A target's profile with its own generator, seeded separately from the path generator so timings can be reproduced alone.
+pub struct Timing {
+    pub profile: TimingProfile,
+    rng: Random,
+    start: Instant,
+}
+
+impl Timing {
+    pub fn new(profile: TimingProfile, rng: Random) -> Self {
+        Timing {
+            profile,
+            rng,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn sample(&mut self, kind: TimingKind) -> u64 {
+        let elapsed = self.start.elapsed().as_millis() as u64;
+        self.profile.sample(kind, &mut self.rng, elapsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [TimingKind; 3] = [TimingKind::Press, TimingKind::Interval, TimingKind::Click];
+
+    fn samples(profile: &TimingProfile, kind: TimingKind, seed: u64, elapsed: u64) -> Vec<u64> {
+        let mut rng = Random::new(seed);
+        (0..20_000)
+            .map(|_| profile.sample(kind, &mut rng, elapsed))
+            .collect()
+    }
+
+    fn mean(samples: &[u64]) -> f64 {
+        samples.iter().sum::<u64>() as f64 / samples.len() as f64
+    }
+
+    #[test]
+    fn same_seed_same_timings() {
+        for profile in PROFILES {
+            for kind in KINDS {
+                let a = samples(&profile, kind, 42, 0);
+                assert_eq!(a, samples(&profile, kind, 42, 0));
+                assert_ne!(a, samples(&profile, kind, 43, 0));
+            }
+        }
+
+        let profile = find_profile("normal").unwrap();
+        let mut a = Timing::new(profile, Random::new(9));
+        let mut b = Timing::new(profile, Random::new(9));
+        for kind in KINDS {
+            assert_eq!(a.sample(kind), b.sample(kind));
+        }
+    }
+
+    #[test]
+    fn means_match_the_distributions() {
+        for profile in PROFILES {
+            for kind in KINDS {
+                let mut copy = profile;
+                let expected = match *copy.distribution_mut(kind) {
+                    Distribution::LogNormal { median, sigma } => {
+                        median * (sigma * sigma / 2.0).exp()
+                    }
+                    Distribution::Gamma { shape, scale } => shape * scale,
+                };
+                let mean = mean(&samples(&profile, kind, 7, 0));
+                assert!(
+                    (mean / expected - 1.0).abs() < 0.03,
+                    "{} {:?} mean {} expected {}",
+                    profile.name,
+                    kind,
+                    mean,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn small_gamma_shapes() {
+        let mut rng = Random::new(5);
+        let distribution = Distribution::Gamma {
+            shape: 0.5,
+            scale: 100.0,
+        };
+        let total: f64 = (0..50_000).map(|_| distribution.sample(&mut rng)).sum();
+        assert!((total / 50_000.0 / 50.0 - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn samples_are_clamped() {
+        let mut profile = PROFILES[0];
+        *profile.distribution_mut(TimingKind::Press) = Distribution::LogNormal {
+            median: 1.0,
+            sigma: 0.0,
+        };
+        *profile.distribution_mut(TimingKind::Click) = Distribution::LogNormal {
+            median: 1e9,
+            sigma: 0.0,
+        };
+
+        let mut rng = Random::new(1);
+        assert_eq!(
+            profile.sample(TimingKind::Press, &mut rng, 0),
+            MIN_TIME as u64
+        );
+        assert_eq!(
+            profile.sample(TimingKind::Click, &mut rng, 0),
+            MAX_TIME as u64
+        );
+    }
+
+    #[test]
+    fn fatigue_slows_down_and_stops_growing() {
+        let profile = find_profile("normal").unwrap();
+        let hour = 3_600_000;
+        let fresh = mean(&samples(&profile, TimingKind::Press, 3, 0));
+        let tired = mean(&samples(&profile, TimingKind::Press, 3, 2 * hour));
+        let exhausted = mean(&samples(&profile, TimingKind::Press, 3, 10 * hour));
+        let capped = mean(&samples(&profile, TimingKind::Press, 3, 4 * hour));
+
+        assert!((tired / fresh - 1.2).abs() < 0.01);
+        assert!((exhausted / fresh - 1.4).abs() < 0.01);
+        assert_eq!(exhausted, capped);
+
+        let mut rested = profile;
+        rested.fatigue = 0.0;
+        assert_eq!(
+            samples(&rested, TimingKind::Press, 3, 10 * hour),
+            samples(&rested, TimingKind::Press, 3, 0)
+        );
+    }
+
+    #[test]
+    fn parameters_from_the_export() {
+        assert_eq!(
+            Distribution::from_params(0, 80.0, 0.3),
+            Some(Distribution::LogNormal {
+                median: 80.0,
+                sigma: 0.3
+            })
+        );
+        assert_eq!(
+            Distribution::from_params(1, 4.0, 25.0),
+            Some(Distribution::Gamma {
+                shape: 4.0,
+                scale: 25.0
+            })
+        );
+        assert!(Distribution::from_params(0, 80.0, 0.0).is_some());
+
+        assert_eq!(Distribution::from_params(2, 80.0, 0.3), None);
+        assert_eq!(Distribution::from_params(0, 0.0, 0.3), None);
+        assert_eq!(Distribution::from_params(0, 80.0, -0.1), None);
+        assert_eq!(Distribution::from_params(1, 4.0, 0.0), None);
+        assert_eq!(Distribution::from_params(1, f64::NAN, 1.0), None);
+        assert_eq!(Distribution::from_params(1, 4.0, f64::INFINITY), None);
+
+        assert_eq!(TimingKind::from_i32(1), Some(TimingKind::Interval));
+        assert_eq!(TimingKind::from_i32(3), None);
+        assert_eq!(find_profile("RELAXED").map(|p| p.name), Some("relaxed"));
+        assert!(find_profile("slow").is_none());
+    }
+}